@@ -0,0 +1,498 @@
+use crate::compressors::{CompressionAlgorithm, CompressorFactory};
+use crate::error::CompressorError;
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::path::{Component, Path, PathBuf};
+
+/// Longest entry name `extract_directory` reads; a longer length in an entry header is
+/// treated as corruption rather than allocated
+const MAX_ENTRY_NAME_LEN: usize = 64 * 1024;
+
+/// A set of named byte entries compressed together into a single blob.
+///
+/// Entries are serialized in name order as a u32 entry count followed by, for each entry,
+/// a u32 name length, the UTF-8 name, a u64 data length and the data (all little-endian),
+/// and the whole buffer is then compressed. Names are unique: adding an existing name fails.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Archive {
+    entries: BTreeMap<String, Vec<u8>>,
+}
+
+impl Archive {
+    pub fn new() -> Self {
+        Archive::default()
+    }
+
+    /// Adds an entry, failing with `ConfigError` if `name` is already present
+    pub fn add(&mut self, name: &str, data: &[u8]) -> Result<(), CompressorError> {
+        if self.entries.contains_key(name) {
+            return Err(CompressorError::ConfigError(format!(
+                "duplicate archive entry '{}'",
+                name
+            )));
+        }
+
+        self.entries.insert(name.to_string(), data.to_vec());
+        Ok(())
+    }
+
+    pub fn get(&self, name: &str) -> Option<&[u8]> {
+        self.entries.get(name).map(Vec::as_slice)
+    }
+
+    /// Entry names in sorted order
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.entries.keys().map(String::as_str)
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn into_compressed(
+        self,
+        algorithm: CompressionAlgorithm,
+    ) -> Result<Vec<u8>, CompressorError> {
+        let entry_count = u32::try_from(self.entries.len()).map_err(|_| {
+            CompressorError::SerializationError("too many archive entries".to_string())
+        })?;
+
+        let mut serialized = Vec::new();
+        serialized.extend_from_slice(&entry_count.to_le_bytes());
+        for (name, data) in &self.entries {
+            let name_len = u32::try_from(name.len()).map_err(|_| {
+                CompressorError::SerializationError(format!(
+                    "archive entry name too long: {}",
+                    name
+                ))
+            })?;
+            serialized.extend_from_slice(&name_len.to_le_bytes());
+            serialized.extend_from_slice(name.as_bytes());
+            serialized.extend_from_slice(&(data.len() as u64).to_le_bytes());
+            serialized.extend_from_slice(data);
+        }
+
+        CompressorFactory::get_compressor(algorithm)
+            .compress(&serialized)
+            .map_err(|err| err.with_algorithm(algorithm))
+    }
+
+    pub fn from_compressed(
+        data: &[u8],
+        algorithm: CompressionAlgorithm,
+    ) -> Result<Archive, CompressorError> {
+        let serialized = CompressorFactory::get_compressor(algorithm)
+            .decompress(data)
+            .map_err(|err| err.with_algorithm(algorithm))?;
+
+        let mut reader = EntryReader {
+            remaining: &serialized,
+        };
+        let entry_count = reader.read_u32()?;
+
+        let mut archive = Archive::new();
+        for _ in 0..entry_count {
+            let name_len = reader.read_u32()? as usize;
+            let name = std::str::from_utf8(reader.take(name_len)?).map_err(|err| {
+                CompressorError::DeserializationError(format!(
+                    "invalid archive entry name: {}",
+                    err
+                ))
+            })?;
+            let data_len = usize::try_from(reader.read_u64()?).map_err(|_| truncated())?;
+            let data = reader.take(data_len)?;
+
+            archive.add(name, data).map_err(|_| {
+                CompressorError::DeserializationError(format!("duplicate archive entry '{}'", name))
+            })?;
+        }
+
+        if !reader.remaining.is_empty() {
+            return Err(CompressorError::TrailingData(reader.remaining.len()));
+        }
+
+        Ok(archive)
+    }
+}
+
+/// Compresses every regular file under `root` into one blob in the `Archive` layout, named
+/// by its path relative to `root` with `/` separators, so `Archive::from_compressed` reads
+/// it too.
+///
+/// Files are streamed one at a time through the compressor's encoder and never held in
+/// memory whole. Symbolic links are skipped rather than followed, and empty directories
+/// are not recorded. Paths that are not valid UTF-8 fail with `SerializationError`.
+pub fn compress_directory(
+    root: &Path,
+    algorithm: CompressionAlgorithm,
+) -> Result<Vec<u8>, CompressorError> {
+    let mut files = Vec::new();
+    collect_files(root, Path::new(""), &mut files)?;
+    files.sort();
+
+    let entry_count = u32::try_from(files.len())
+        .map_err(|_| CompressorError::SerializationError("too many archive entries".to_string()))?;
+
+    let compressor = CompressorFactory::get_compressor(algorithm);
+    let mut compressed = Vec::new();
+    let mut encoder = compressor
+        .encoder(Box::new(&mut compressed))
+        .map_err(|err| err.with_algorithm(algorithm))?;
+    let write_error = |err: io::Error| CompressorError::CompressionError(err.to_string());
+
+    encoder
+        .write_all(&entry_count.to_le_bytes())
+        .map_err(write_error)?;
+    for name in &files {
+        let path = root.join(name);
+        let io_error =
+            |err: io::Error| CompressorError::IoError(format!("{}: {}", path.display(), err));
+        let file = File::open(&path).map_err(io_error)?;
+        let len = file.metadata().map_err(io_error)?.len();
+
+        let name_len = u32::try_from(name.len()).map_err(|_| {
+            CompressorError::SerializationError(format!("archive entry name too long: {}", name))
+        })?;
+        encoder
+            .write_all(&name_len.to_le_bytes())
+            .and_then(|_| encoder.write_all(name.as_bytes()))
+            .and_then(|_| encoder.write_all(&len.to_le_bytes()))
+            .map_err(write_error)?;
+
+        let copied = io::copy(&mut file.take(len), &mut encoder).map_err(io_error)?;
+        if copied != len {
+            return Err(CompressorError::IoError(format!(
+                "{}: file shrank from {} to {} bytes while archiving",
+                path.display(),
+                len,
+                copied
+            )));
+        }
+    }
+
+    encoder
+        .finish()
+        .map_err(|err| err.with_algorithm(algorithm))?;
+    Ok(compressed)
+}
+
+/// Restores the files of a `compress_directory` (or `Archive`) blob under `dst`, creating
+/// directories as needed and overwriting existing files. Entries are decoded and written
+/// one at a time.
+///
+/// Entry names must be relative paths of plain components: names that are absolute or
+/// contain `..` fail with `DeserializationError` before anything is written for them, so
+/// no entry can escape `dst`.
+pub fn extract_directory(
+    data: &[u8],
+    dst: &Path,
+    algorithm: CompressionAlgorithm,
+) -> Result<(), CompressorError> {
+    let mut decoder = CompressorFactory::get_compressor(algorithm)
+        .decoder(Box::new(data))
+        .map_err(|err| err.with_algorithm(algorithm))?;
+
+    let entry_count = read_u32_from(&mut decoder)?;
+    for _ in 0..entry_count {
+        let name_len = read_u32_from(&mut decoder)? as usize;
+        if name_len > MAX_ENTRY_NAME_LEN {
+            return Err(CompressorError::DeserializationError(format!(
+                "archive entry name of {} bytes exceeds the limit of {} bytes",
+                name_len, MAX_ENTRY_NAME_LEN
+            )));
+        }
+        let mut name = Vec::with_capacity(name_len);
+        (&mut decoder)
+            .take(name_len as u64)
+            .read_to_end(&mut name)
+            .map_err(read_error)?;
+        if name.len() != name_len {
+            return Err(truncated());
+        }
+        let name = String::from_utf8(name).map_err(|err| {
+            CompressorError::DeserializationError(format!("invalid archive entry name: {}", err))
+        })?;
+        let len = read_u64_from(&mut decoder)?;
+
+        let path = dst.join(safe_relative_path(&name)?);
+        let io_error =
+            |err: io::Error| CompressorError::IoError(format!("{}: {}", path.display(), err));
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).map_err(io_error)?;
+        }
+
+        let mut file = File::create(&path).map_err(io_error)?;
+        let copied = io::copy(&mut (&mut decoder).take(len), &mut file).map_err(io_error)?;
+        if copied != len {
+            return Err(truncated());
+        }
+    }
+
+    let mut trailing = Vec::new();
+    decoder.read_to_end(&mut trailing).map_err(read_error)?;
+    if !trailing.is_empty() {
+        return Err(CompressorError::TrailingData(trailing.len()));
+    }
+
+    Ok(())
+}
+
+/// Appends the `/`-separated paths of the regular files under `dir`, prefixed with
+/// `prefix`, to `files`
+fn collect_files(
+    dir: &Path,
+    prefix: &Path,
+    files: &mut Vec<String>,
+) -> Result<(), CompressorError> {
+    let io_error = |err: io::Error| CompressorError::IoError(format!("{}: {}", dir.display(), err));
+
+    for entry in fs::read_dir(dir).map_err(io_error)? {
+        let entry = entry.map_err(io_error)?;
+        let file_type = entry.file_type().map_err(io_error)?;
+        let relative = prefix.join(entry.file_name());
+
+        if file_type.is_dir() {
+            collect_files(&entry.path(), &relative, files)?;
+        } else if file_type.is_file() {
+            let name = relative
+                .components()
+                .map(|component| component.as_os_str().to_str())
+                .collect::<Option<Vec<_>>>()
+                .ok_or_else(|| {
+                    CompressorError::SerializationError(format!(
+                        "archive entry path is not UTF-8: {}",
+                        relative.display()
+                    ))
+                })?
+                .join("/");
+            files.push(name);
+        }
+    }
+
+    Ok(())
+}
+
+/// `name` as a relative path, rejecting absolute paths, `..` and other non-plain components
+fn safe_relative_path(name: &str) -> Result<PathBuf, CompressorError> {
+    let path = Path::new(name);
+    let is_plain = |component| matches!(component, Component::Normal(_) | Component::CurDir);
+
+    if name.is_empty() || name.contains('\\') || !path.components().all(is_plain) {
+        return Err(CompressorError::DeserializationError(format!(
+            "unsafe archive entry path '{}'",
+            name
+        )));
+    }
+
+    Ok(path.to_path_buf())
+}
+
+fn read_error(err: io::Error) -> CompressorError {
+    match err.kind() {
+        io::ErrorKind::UnexpectedEof => truncated(),
+        _ => CompressorError::DecompressionError(err.to_string()),
+    }
+}
+
+fn read_u32_from(reader: &mut impl Read) -> Result<u32, CompressorError> {
+    let mut bytes = [0u8; 4];
+    reader.read_exact(&mut bytes).map_err(read_error)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+fn read_u64_from(reader: &mut impl Read) -> Result<u64, CompressorError> {
+    let mut bytes = [0u8; 8];
+    reader.read_exact(&mut bytes).map_err(read_error)?;
+    Ok(u64::from_le_bytes(bytes))
+}
+
+fn truncated() -> CompressorError {
+    CompressorError::DeserializationError("truncated archive".to_string())
+}
+
+struct EntryReader<'a> {
+    remaining: &'a [u8],
+}
+
+impl<'a> EntryReader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], CompressorError> {
+        if self.remaining.len() < len {
+            return Err(truncated());
+        }
+
+        let (taken, rest) = self.remaining.split_at(len);
+        self.remaining = rest;
+        Ok(taken)
+    }
+
+    fn read_u32(&mut self) -> Result<u32, CompressorError> {
+        let bytes = self.take(4)?;
+        Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    fn read_u64(&mut self) -> Result<u64, CompressorError> {
+        let mut bytes = [0u8; 8];
+        bytes.copy_from_slice(self.take(8)?);
+        Ok(u64::from_le_bytes(bytes))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_archive_round_trip() {
+        let mut archive = Archive::new();
+        archive.add("config.json", br#"{"debug":false}"#).unwrap();
+        archive
+            .add("notes.txt", &b"archived notes ".repeat(100))
+            .unwrap();
+        archive.add("empty", b"").unwrap();
+
+        let compressed = archive
+            .clone()
+            .into_compressed(CompressionAlgorithm::Snappy)
+            .unwrap();
+        let restored = Archive::from_compressed(&compressed, CompressionAlgorithm::Snappy).unwrap();
+
+        assert_eq!(restored, archive);
+        assert_eq!(restored.len(), 3);
+        assert_eq!(restored.get("config.json").unwrap(), br#"{"debug":false}"#);
+        assert_eq!(
+            restored.get("notes.txt").unwrap(),
+            b"archived notes ".repeat(100)
+        );
+        assert_eq!(restored.get("empty").unwrap(), b"");
+        assert_eq!(restored.get("missing"), None);
+        assert_eq!(
+            restored.names().collect::<Vec<_>>(),
+            ["config.json", "empty", "notes.txt"]
+        );
+    }
+
+    #[test]
+    fn test_archive_rejects_duplicates() {
+        let mut archive = Archive::new();
+        archive.add("entry", b"first").unwrap();
+
+        assert!(matches!(
+            archive.add("entry", b"second"),
+            Err(CompressorError::ConfigError(_))
+        ));
+        assert_eq!(archive.get("entry").unwrap(), b"first");
+    }
+
+    fn read_tree(root: &Path) -> BTreeMap<String, Vec<u8>> {
+        let mut files = Vec::new();
+        collect_files(root, Path::new(""), &mut files).unwrap();
+        files
+            .into_iter()
+            .map(|name| {
+                let data = fs::read(root.join(&name)).unwrap();
+                (name, data)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_directory_round_trip() {
+        let source = tempfile::tempdir().unwrap();
+        fs::create_dir_all(source.path().join("logs/2024")).unwrap();
+        fs::create_dir_all(source.path().join("empty")).unwrap();
+        fs::write(source.path().join("README"), b"backup root").unwrap();
+        fs::write(
+            source.path().join("logs/2024/app.log"),
+            b"line\n".repeat(20_000),
+        )
+        .unwrap();
+        fs::write(source.path().join("logs/empty.log"), b"").unwrap();
+
+        let compressed = compress_directory(source.path(), CompressionAlgorithm::Snappy).unwrap();
+
+        let archive = Archive::from_compressed(&compressed, CompressionAlgorithm::Snappy).unwrap();
+        assert_eq!(
+            archive.names().collect::<Vec<_>>(),
+            ["README", "logs/2024/app.log", "logs/empty.log"]
+        );
+
+        let destination = tempfile::tempdir().unwrap();
+        extract_directory(
+            &compressed,
+            destination.path(),
+            CompressionAlgorithm::Snappy,
+        )
+        .unwrap();
+        assert_eq!(read_tree(destination.path()), read_tree(source.path()));
+    }
+
+    #[test]
+    fn test_extract_rejects_path_traversal() {
+        let destination = tempfile::tempdir().unwrap();
+        let target = destination.path().join("inner");
+
+        for name in [
+            "../escaped",
+            "a/../../escaped",
+            "/etc/escaped",
+            "a\\..\\b",
+            "",
+        ] {
+            let mut archive = Archive::new();
+            archive.add(name, b"payload").unwrap();
+            let compressed = archive
+                .into_compressed(CompressionAlgorithm::Snappy)
+                .unwrap();
+
+            assert!(
+                matches!(
+                    extract_directory(&compressed, &target, CompressionAlgorithm::Snappy),
+                    Err(CompressorError::DeserializationError(_))
+                ),
+                "{:?} should be rejected",
+                name
+            );
+        }
+        assert!(!destination.path().join("escaped").exists());
+        assert!(!Path::new("/etc/escaped").exists());
+    }
+
+    #[test]
+    fn test_extract_rejects_oversized_name() {
+        let destination = tempfile::tempdir().unwrap();
+        let compressor = CompressorFactory::get_compressor(CompressionAlgorithm::Snappy);
+
+        // One entry whose header claims a 4 GiB name, then one cut off inside its name
+        for header in [
+            &[1, 0, 0, 0, 0xff, 0xff, 0xff, 0xff][..],
+            &[1, 0, 0, 0, 10, 0, 0, 0, b'a', b'b'],
+        ] {
+            let compressed = compressor.compress(header).unwrap();
+            assert!(matches!(
+                extract_directory(
+                    &compressed,
+                    destination.path(),
+                    CompressionAlgorithm::Snappy
+                ),
+                Err(CompressorError::DeserializationError(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn test_archive_corrupt_input() {
+        let compressed = CompressorFactory::get_compressor(CompressionAlgorithm::Snappy)
+            .compress(&[2, 0, 0, 0, 9])
+            .unwrap();
+
+        assert!(matches!(
+            Archive::from_compressed(&compressed, CompressionAlgorithm::Snappy),
+            Err(CompressorError::DeserializationError(_))
+        ));
+    }
+}
@@ -0,0 +1,85 @@
+//! PEM-like ASCII armor for carrying compressed bytes through text-only channels:
+//!
+//! ```text
+//! -----BEGIN BERING COMPRESSED-----
+//! <standard base64, wrapped at 64 columns>
+//! -----END BERING COMPRESSED-----
+//! ```
+
+use crate::error::CompressorError;
+use base64::{engine::general_purpose::STANDARD, Engine};
+
+pub const ARMOR_BEGIN: &str = "-----BEGIN BERING COMPRESSED-----";
+pub const ARMOR_END: &str = "-----END BERING COMPRESSED-----";
+/// Width of the base64 lines `armor` emits
+const LINE_WIDTH: usize = 64;
+
+/// Wraps `data` in armor, ending with a newline after the footer
+pub fn armor(data: &[u8]) -> String {
+    let encoded = STANDARD.encode(data);
+
+    let mut armored = String::with_capacity(encoded.len() * 65 / 64 + 72);
+    armored.push_str(ARMOR_BEGIN);
+    armored.push('\n');
+    for start in (0..encoded.len()).step_by(LINE_WIDTH) {
+        armored.push_str(&encoded[start..encoded.len().min(start + LINE_WIDTH)]);
+        armored.push('\n');
+    }
+    armored.push_str(ARMOR_END);
+    armored.push('\n');
+    armored
+}
+
+/// Extracts the bytes from the first armored block in `text`.
+///
+/// Text before the header and after the footer is ignored, and whitespace between them
+/// is dropped, so lines may be wrapped at any width or indented. A missing header or
+/// footer or invalid base64 fails with `DecodingError`.
+pub fn dearmor(text: &str) -> Result<Vec<u8>, CompressorError> {
+    let missing = |marker: &str| CompressorError::DecodingError(format!("missing `{}`", marker));
+
+    let start = text.find(ARMOR_BEGIN).ok_or_else(|| missing(ARMOR_BEGIN))? + ARMOR_BEGIN.len();
+    let len = text[start..]
+        .find(ARMOR_END)
+        .ok_or_else(|| missing(ARMOR_END))?;
+
+    let body: String = text[start..start + len]
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .collect();
+
+    STANDARD
+        .decode(body)
+        .map_err(|err| CompressorError::DecodingError(format!("invalid base64: {}", err)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_armor_round_trip() {
+        let data: Vec<u8> = (0..1000).map(|i| (i * 7 % 256) as u8).collect();
+        let armored = armor(&data);
+
+        let lines: Vec<&str> = armored.lines().collect();
+        assert_eq!(lines[0], ARMOR_BEGIN);
+        assert_eq!(*lines.last().unwrap(), ARMOR_END);
+        assert!(lines[1..lines.len() - 1]
+            .iter()
+            .all(|line| line.len() <= LINE_WIDTH));
+
+        assert_eq!(dearmor(&armored).unwrap(), data);
+        assert_eq!(dearmor(&armor(b"")).unwrap(), b"");
+    }
+
+    #[test]
+    fn test_dearmor_errors() {
+        assert!(matches!(
+            dearmor("no armor here"),
+            Err(CompressorError::DecodingError(_))
+        ));
+        assert!(dearmor(&format!("{}\nAAAA\n", ARMOR_BEGIN)).is_err());
+        assert!(dearmor(&format!("{}\n!!!!\n{}", ARMOR_BEGIN, ARMOR_END)).is_err());
+    }
+}
@@ -0,0 +1,106 @@
+//! Content-defined chunking with a gear rolling hash.
+//!
+//! Boundaries depend only on the bytes shortly before them, so an insertion or deletion
+//! moves the boundaries next to it while later chunks come out identical, which keeps
+//! them deduplicable across versions of similar data.
+
+use alloc::vec::Vec;
+
+/// Random per-byte values mixed into the rolling hash, generated with splitmix64
+const GEAR: [u64; 256] = {
+    let mut table = [0u64; 256];
+    let mut state = 0x9e37_79b9_7f4a_7c15u64;
+    let mut i = 0;
+    while i < 256 {
+        state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        table[i] = z ^ (z >> 31);
+        i += 1;
+    }
+    table
+};
+
+/// Splits `data` into chunks averaging about `avg_chunk` bytes at content-defined
+/// boundaries.
+///
+/// Chunks are at least a quarter and at most four times `avg_chunk` (rounded up to a
+/// power of two), except that the last chunk may be shorter. Concatenated, the chunks
+/// are `data`.
+pub fn chunk_content_defined(data: &[u8], avg_chunk: usize) -> Vec<&[u8]> {
+    let avg_chunk = avg_chunk.max(1).next_power_of_two();
+    let min_chunk = (avg_chunk / 4).max(1);
+    let max_chunk = avg_chunk.saturating_mul(4);
+    // The top bits of a gear hash depend on the most recent 64 bytes, the low bits on
+    // fewer, so boundaries are chosen on the top bits
+    let mask = match avg_chunk.trailing_zeros() {
+        0 => 0,
+        bits => u64::MAX << (64 - bits),
+    };
+
+    let mut chunks = Vec::new();
+    let mut rest = data;
+    while !rest.is_empty() {
+        let len = next_boundary(rest, min_chunk, max_chunk, mask);
+        let (chunk, tail) = rest.split_at(len);
+        chunks.push(chunk);
+        rest = tail;
+    }
+    chunks
+}
+
+/// Length of the chunk at the start of `data`
+fn next_boundary(data: &[u8], min_chunk: usize, max_chunk: usize, mask: u64) -> usize {
+    let end = data.len().min(max_chunk);
+    let mut hash = 0u64;
+
+    for (i, &byte) in data[..end].iter().enumerate() {
+        hash = (hash << 1).wrapping_add(GEAR[byte as usize]);
+        if i + 1 >= min_chunk && hash & mask == 0 {
+            return i + 1;
+        }
+    }
+    end
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_chunks_cover_data() {
+        let data = crate::noise(0x2545_f491_4f6c_dd1d, 200_000);
+        let chunks = chunk_content_defined(&data, 4096);
+
+        assert_eq!(chunks.concat(), data);
+        assert!(chunks.len() > 20 && chunks.len() < 200);
+        let (last, rest) = chunks.split_last().unwrap();
+        assert!(rest
+            .iter()
+            .all(|chunk| (1024..=16_384).contains(&chunk.len())));
+        assert!(last.len() <= 16_384);
+
+        assert!(chunk_content_defined(b"", 4096).is_empty());
+        assert_eq!(chunk_content_defined(b"abc", 0).concat(), b"abc");
+    }
+
+    #[test]
+    fn test_insertion_keeps_later_boundaries() {
+        let original = crate::noise(0x2545_f491_4f6c_dd1d, 500_000);
+        let mut edited = original.clone();
+        edited.splice(100..100, b"inserted near the start".iter().copied());
+
+        let before = chunk_content_defined(&original, 4096);
+        let after = chunk_content_defined(&edited, 4096);
+
+        let unchanged = after.iter().filter(|chunk| before.contains(chunk)).count();
+        assert!(
+            unchanged + 2 >= after.len(),
+            "{} of {}",
+            unchanged,
+            after.len()
+        );
+        assert_eq!(before.last(), after.last());
+    }
+}
@@ -0,0 +1,88 @@
+use super::TCompressor;
+use crate::error::CompressorError;
+use alloc::vec::Vec;
+
+/// Runs two compressors in sequence: `first`, then `then` as a post-stage over its output,
+/// e.g. a custom entropy coder after Snappy.
+///
+/// Compression feeds the input to `first.compress` and its output to `then.compress`;
+/// decompression undoes them in reverse, `then.decompress` before `first.decompress`.
+/// The first error of either stage is returned unchanged and stops the chain. Any
+/// `TCompressor` can be a stage, and chains nest to build longer pipelines.
+pub struct ChainCompressor<A, B> {
+    first: A,
+    then: B,
+}
+
+impl<A: TCompressor, B: TCompressor> ChainCompressor<A, B> {
+    pub fn new(first: A, then: B) -> Self {
+        ChainCompressor { first, then }
+    }
+
+    pub fn into_inner(self) -> (A, B) {
+        (self.first, self.then)
+    }
+}
+
+impl<A: TCompressor, B: TCompressor> TCompressor for ChainCompressor<A, B> {
+    fn compress(&self, value: &[u8]) -> Result<Vec<u8>, CompressorError> {
+        self.then.compress(&self.first.compress(value)?)
+    }
+
+    fn decompress(&self, compressed: &[u8]) -> Result<Vec<u8>, CompressorError> {
+        self.first.decompress(&self.then.decompress(compressed)?)
+    }
+
+    fn decompress_strict(&self, compressed: &[u8]) -> Result<Vec<u8>, CompressorError> {
+        self.first
+            .decompress_strict(&self.then.decompress_strict(compressed)?)
+    }
+
+    fn overhead_bytes(&self) -> usize {
+        self.first.overhead_bytes() + self.then.overhead_bytes()
+    }
+
+    fn preferred_chunk_size(&self) -> usize {
+        self.first.preferred_chunk_size()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compressors::ReverseCompressor;
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_post_stage_after_snappy() {
+        use crate::compressors::SnappyCompressor;
+
+        let chain = ChainCompressor::new(SnappyCompressor, ReverseCompressor);
+        let data = b"entropy coder research ".repeat(100);
+
+        let compressed = chain.compress(&data).unwrap();
+        let snappy = SnappyCompressor.compress(&data).unwrap();
+        assert!(compressed.iter().eq(snappy.iter().rev()));
+        assert_eq!(chain.decompress(&compressed).unwrap(), data);
+        assert_eq!(chain.overhead_bytes(), SnappyCompressor.overhead_bytes());
+
+        // Errors of the first stage propagate through the second
+        assert!(chain.decompress(b"not snappy").is_err());
+        let mut corrupted = compressed.clone();
+        corrupted.drain(..10);
+        assert!(chain.decompress(&corrupted).is_err());
+    }
+
+    #[test]
+    fn test_nested_chain() {
+        let chain = ChainCompressor::new(
+            ChainCompressor::new(ReverseCompressor, ReverseCompressor),
+            ReverseCompressor,
+        );
+        let data = b"three stages";
+
+        let compressed = chain.compress(data).unwrap();
+        assert!(compressed.iter().eq(data.iter().rev()));
+        assert_eq!(chain.decompress(&compressed).unwrap(), data);
+    }
+}
@@ -0,0 +1,110 @@
+use super::{IdentityCompressor, TCompressor};
+use crate::error::CompressorError;
+use alloc::{format, vec::Vec};
+
+/// Length of the CRC32 trailer appended by `CheckedCompressor`
+const CHECKSUM_LEN: usize = 4;
+
+/// Wraps a compressor and appends the CRC32 of the uncompressed data to its output.
+///
+/// Decompression recomputes the checksum and fails with `IntegrityError` on mismatch,
+/// catching corruption or tampering anywhere in the payload.
+pub struct CheckedCompressor<C> {
+    inner: C,
+}
+
+impl<C: TCompressor> CheckedCompressor<C> {
+    pub fn new(inner: C) -> Self {
+        CheckedCompressor { inner }
+    }
+
+    pub fn into_inner(self) -> C {
+        self.inner
+    }
+}
+
+impl CheckedCompressor<IdentityCompressor> {
+    /// Integrity checking without compression: raw bytes followed by their CRC32
+    pub fn checksum_only() -> Self {
+        CheckedCompressor::new(IdentityCompressor)
+    }
+}
+
+impl<C: TCompressor> TCompressor for CheckedCompressor<C> {
+    fn compress(&self, value: &[u8]) -> Result<Vec<u8>, CompressorError> {
+        let mut compressed = self.inner.compress(value)?;
+        compressed.extend_from_slice(&crc32fast::hash(value).to_le_bytes());
+
+        Ok(compressed)
+    }
+
+    fn decompress(&self, compressed: &[u8]) -> Result<Vec<u8>, CompressorError> {
+        let split = compressed.len().checked_sub(CHECKSUM_LEN).ok_or_else(|| {
+            CompressorError::IntegrityError("input is too short to hold a checksum".into())
+        })?;
+        let (payload, trailer) = compressed.split_at(split);
+
+        let decompressed = self.inner.decompress(payload)?;
+
+        let expected = u32::from_le_bytes([trailer[0], trailer[1], trailer[2], trailer[3]]);
+        let actual = crc32fast::hash(&decompressed);
+        if actual != expected {
+            return Err(CompressorError::IntegrityError(format!(
+                "checksum mismatch: expected {:08x}, got {:08x}",
+                expected, actual
+            )));
+        }
+
+        Ok(decompressed)
+    }
+
+    fn overhead_bytes(&self) -> usize {
+        self.inner.overhead_bytes() + CHECKSUM_LEN
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_checksum_only_round_trip() {
+        let compressor = CheckedCompressor::checksum_only();
+        let data = b"integrity without compression";
+
+        let stored = compressor.compress(data).unwrap();
+        assert_eq!(stored.len(), data.len() + CHECKSUM_LEN);
+        assert_eq!(&stored[..data.len()], data);
+        assert_eq!(compressor.decompress(&stored).unwrap(), data);
+    }
+
+    #[test]
+    fn test_corrupted_byte_fails_verification() {
+        let compressor = CheckedCompressor::checksum_only();
+
+        let mut stored = compressor
+            .compress(b"integrity without compression")
+            .unwrap();
+        stored[3] ^= 0x01;
+
+        assert!(matches!(
+            compressor.decompress(&stored),
+            Err(CompressorError::IntegrityError(_))
+        ));
+        assert!(matches!(
+            compressor.decompress(&[1, 2]),
+            Err(CompressorError::IntegrityError(_))
+        ));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_checked_snappy_round_trip() {
+        let compressor = CheckedCompressor::new(crate::compressors::SnappyCompressor);
+        let data = b"checked snappy ".repeat(100);
+
+        let compressed = compressor.compress(&data).unwrap();
+        assert!(compressed.len() < data.len());
+        assert_eq!(compressor.decompress(&compressed).unwrap(), data);
+    }
+}
@@ -0,0 +1,143 @@
+use super::TCompressor;
+use crate::error::CompressorError;
+use alloc::vec::Vec;
+use chacha20poly1305::aead::{Aead, KeyInit};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use core::sync::atomic::{AtomicU64, Ordering};
+
+/// Length of the nonce header in front of every ciphertext
+pub const NONCE_LEN: usize = 12;
+/// Length of the Poly1305 authentication tag after every ciphertext
+const TAG_LEN: usize = 16;
+
+/// Wraps a compressor and encrypts its output with ChaCha20-Poly1305, compressing
+/// first so the ciphertext stays small.
+///
+/// Each output is the 12-byte nonce followed by the ciphertext and its tag. The nonce of
+/// the n-th call is the caller-supplied nonce with n added to its last 8 bytes (little
+/// endian), so one instance never repeats a nonce. Two instances must never share the
+/// same key and starting nonce.
+///
+/// Ciphertext that was tampered with or encrypted under another key fails with
+/// `AuthenticationFailed` before the inner compressor ever sees it.
+pub struct EncryptedCompressor<C> {
+    inner: C,
+    cipher: ChaCha20Poly1305,
+    nonce: [u8; NONCE_LEN],
+    counter: AtomicU64,
+}
+
+impl<C: TCompressor> EncryptedCompressor<C> {
+    pub fn new(inner: C, key: &[u8; 32], nonce: [u8; NONCE_LEN]) -> Self {
+        EncryptedCompressor {
+            inner,
+            cipher: ChaCha20Poly1305::new(Key::from_slice(key)),
+            nonce,
+            counter: AtomicU64::new(0),
+        }
+    }
+
+    pub fn into_inner(self) -> C {
+        self.inner
+    }
+
+    fn next_nonce(&self) -> [u8; NONCE_LEN] {
+        let count = self.counter.fetch_add(1, Ordering::Relaxed);
+        let mut nonce = self.nonce;
+
+        let mut low = [0u8; 8];
+        low.copy_from_slice(&nonce[4..]);
+        let low = u64::from_le_bytes(low).wrapping_add(count);
+        nonce[4..].copy_from_slice(&low.to_le_bytes());
+        nonce
+    }
+}
+
+impl<C: TCompressor> TCompressor for EncryptedCompressor<C> {
+    fn compress(&self, value: &[u8]) -> Result<Vec<u8>, CompressorError> {
+        let compressed = self.inner.compress(value)?;
+        let nonce = self.next_nonce();
+
+        let ciphertext = self
+            .cipher
+            .encrypt(Nonce::from_slice(&nonce), compressed.as_slice())
+            .map_err(|_| CompressorError::CompressionError("encryption failed".into()))?;
+
+        let mut output = Vec::with_capacity(NONCE_LEN + ciphertext.len());
+        output.extend_from_slice(&nonce);
+        output.extend_from_slice(&ciphertext);
+        Ok(output)
+    }
+
+    fn decompress(&self, compressed: &[u8]) -> Result<Vec<u8>, CompressorError> {
+        if compressed.len() < NONCE_LEN + TAG_LEN {
+            return Err(CompressorError::DecompressionError(
+                "input is too short to hold a nonce and authentication tag".into(),
+            ));
+        }
+
+        let (nonce, ciphertext) = compressed.split_at(NONCE_LEN);
+        let plaintext = self
+            .cipher
+            .decrypt(Nonce::from_slice(nonce), ciphertext)
+            .map_err(|_| CompressorError::AuthenticationFailed)?;
+
+        self.inner.decompress(&plaintext)
+    }
+
+    fn overhead_bytes(&self) -> usize {
+        NONCE_LEN + TAG_LEN + self.inner.overhead_bytes()
+    }
+
+    fn preferred_chunk_size(&self) -> usize {
+        self.inner.preferred_chunk_size()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compressors::SnappyRawCompressor;
+
+    const KEY: [u8; 32] = [7; 32];
+
+    #[test]
+    fn test_round_trip() {
+        let compressor = EncryptedCompressor::new(SnappyRawCompressor, &KEY, [1; NONCE_LEN]);
+        let data = b"compress then encrypt ".repeat(200);
+
+        let first = compressor.compress(&data).unwrap();
+        let second = compressor.compress(&data).unwrap();
+        assert!(first.len() < data.len());
+        assert_ne!(first[..NONCE_LEN], second[..NONCE_LEN]);
+        assert_ne!(first, second);
+
+        assert_eq!(compressor.decompress(&first).unwrap(), data);
+        assert_eq!(compressor.decompress(&second).unwrap(), data);
+    }
+
+    #[test]
+    fn test_tampered_ciphertext_fails_authentication() {
+        let compressor = EncryptedCompressor::new(SnappyRawCompressor, &KEY, [1; NONCE_LEN]);
+        let mut encrypted = compressor.compress(b"authenticated payload").unwrap();
+
+        let last = encrypted.len() - 1;
+        encrypted[last] ^= 0x01;
+        assert!(matches!(
+            compressor.decompress(&encrypted),
+            Err(CompressorError::AuthenticationFailed)
+        ));
+
+        encrypted[last] ^= 0x01;
+        let other_key = EncryptedCompressor::new(SnappyRawCompressor, &[8; 32], [1; NONCE_LEN]);
+        assert!(matches!(
+            other_key.decompress(&encrypted),
+            Err(CompressorError::AuthenticationFailed)
+        ));
+
+        assert!(matches!(
+            compressor.decompress(&encrypted[..NONCE_LEN]),
+            Err(CompressorError::DecompressionError(_))
+        ));
+    }
+}
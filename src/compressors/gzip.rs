@@ -0,0 +1,166 @@
+use super::{TCompressor, TEncoder};
+use crate::error::CompressorError;
+use flate2::read::MultiGzDecoder;
+use flate2::write::GzEncoder;
+use flate2::{Compression, GzBuilder};
+use std::io::{self, Read, Write};
+
+/// OS byte for "unknown" in the gzip header (RFC 1952)
+const OS_UNKNOWN: u8 = 255;
+
+/// Gzip compressor producing deterministic output.
+///
+/// The header's modification time is zeroed and its OS byte set to "unknown", so identical
+/// input always yields identical bytes regardless of when or where it was compressed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GzipCompressor {
+    level: u32,
+}
+
+impl GzipCompressor {
+    /// Creates a compressor with a deflate level from 0 (store) to 9 (best)
+    pub fn new(level: u32) -> Self {
+        GzipCompressor {
+            level: level.min(9),
+        }
+    }
+
+    pub fn level(&self) -> u32 {
+        self.level
+    }
+
+    fn new_encoder<W: Write>(&self, writer: W) -> GzEncoder<W> {
+        GzBuilder::new()
+            .mtime(0)
+            .operating_system(OS_UNKNOWN)
+            .write(writer, Compression::new(self.level))
+    }
+}
+
+impl Default for GzipCompressor {
+    fn default() -> Self {
+        GzipCompressor::new(Compression::default().level())
+    }
+}
+
+impl TCompressor for GzipCompressor {
+    fn compress(&self, mut value: &[u8]) -> Result<Vec<u8>, CompressorError> {
+        let mut encoder = self.new_encoder(Vec::new());
+
+        io::copy(&mut value, &mut encoder)
+            .and_then(|_| encoder.finish())
+            .map_err(|err| CompressorError::CompressionError(err.to_string()))
+    }
+
+    fn decompress(&self, compressed: &[u8]) -> Result<Vec<u8>, CompressorError> {
+        let mut decoder = MultiGzDecoder::new(compressed);
+        let mut decompressed = Vec::new();
+
+        decoder
+            .read_to_end(&mut decompressed)
+            .map_err(|err| CompressorError::DecompressionError(err.to_string()))?;
+
+        Ok(decompressed)
+    }
+
+    fn decompress_strict(&self, compressed: &[u8]) -> Result<Vec<u8>, CompressorError> {
+        // The buffered single-member decoder stops right after the member's trailer
+        let mut decoder = flate2::bufread::GzDecoder::new(compressed);
+        let mut decompressed = Vec::new();
+
+        decoder
+            .read_to_end(&mut decompressed)
+            .map_err(|err| CompressorError::DecompressionError(err.to_string()))?;
+
+        match decoder.into_inner().len() {
+            0 => Ok(decompressed),
+            trailing => Err(CompressorError::TrailingData(trailing)),
+        }
+    }
+
+    fn overhead_bytes(&self) -> usize {
+        // 10-byte header, 8-byte CRC32 + size trailer, and an empty final deflate block
+        10 + 8 + 2
+    }
+
+    fn decoder<'a>(
+        &self,
+        reader: Box<dyn Read + 'a>,
+    ) -> Result<Box<dyn Read + 'a>, CompressorError> {
+        Ok(Box::new(MultiGzDecoder::new(reader)))
+    }
+
+    fn encoder<'a>(
+        &'a self,
+        writer: Box<dyn Write + 'a>,
+    ) -> Result<Box<dyn TEncoder + 'a>, CompressorError> {
+        Ok(Box::new(self.new_encoder(writer)))
+    }
+}
+
+impl<W: Write> TEncoder for GzEncoder<W> {
+    fn finish(self: Box<Self>) -> Result<(), CompressorError> {
+        (*self)
+            .finish()
+            .map(drop)
+            .map_err(|err| CompressorError::CompressionError(err.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let compressor = GzipCompressor::default();
+        let data = b"gzip round trip ".repeat(1000);
+
+        let compressed = compressor.compress(&data).unwrap();
+        assert!(compressed.len() < data.len());
+        assert_eq!(compressor.decompress(&compressed).unwrap(), data);
+        assert_eq!(
+            compressor.compress(b"").unwrap().len(),
+            compressor.overhead_bytes()
+        );
+    }
+
+    #[test]
+    fn test_decompress_strict() {
+        let compressor = GzipCompressor::default();
+        let member = compressor.compress(b"strict payload").unwrap();
+        assert_eq!(
+            compressor.decompress_strict(&member).unwrap(),
+            b"strict payload"
+        );
+
+        let mut smuggled = member.clone();
+        smuggled.extend_from_slice(&compressor.compress(b"smuggled").unwrap());
+        assert!(compressor.decompress(&smuggled).is_ok());
+        assert!(matches!(
+            compressor.decompress_strict(&smuggled),
+            Err(CompressorError::TrailingData(len)) if len == smuggled.len() - member.len()
+        ));
+
+        let mut extra_byte = member;
+        extra_byte.push(0);
+        assert!(matches!(
+            compressor.decompress_strict(&extra_byte),
+            Err(CompressorError::TrailingData(1))
+        ));
+    }
+
+    #[test]
+    fn test_deterministic_output() {
+        let compressor = GzipCompressor::default();
+        let data = b"same input, same bytes".repeat(100);
+
+        let first = compressor.compress(&data).unwrap();
+        let second = compressor.compress(&data).unwrap();
+
+        assert_eq!(first, second);
+        // MTIME (bytes 4..8) is zero rather than the clock, and the OS byte is "unknown"
+        assert_eq!(&first[4..8], &[0, 0, 0, 0]);
+        assert_eq!(first[9], OS_UNKNOWN);
+    }
+}
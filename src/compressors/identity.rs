@@ -0,0 +1,76 @@
+#[cfg(feature = "std")]
+use super::TEncoder;
+use super::{allocated, TCompressor};
+use crate::error::CompressorError;
+use alloc::{borrow::Cow, vec::Vec};
+#[cfg(feature = "std")]
+use std::io::{self, Read, Write};
+
+/// Stores data as-is, for paths that want the crate's formats without spending CPU on compression
+pub struct IdentityCompressor;
+
+impl TCompressor for IdentityCompressor {
+    fn compress(&self, value: &[u8]) -> Result<Vec<u8>, CompressorError> {
+        Ok(value.to_vec())
+    }
+
+    fn decompress(&self, compressed: &[u8]) -> Result<Vec<u8>, CompressorError> {
+        Ok(compressed.to_vec())
+    }
+
+    fn compress_cow<'a>(&self, value: Cow<'a, [u8]>) -> Result<Cow<'a, [u8]>, CompressorError> {
+        Ok(value)
+    }
+
+    fn decompressed_len_hint(&self, compressed: &[u8]) -> Option<usize> {
+        Some(compressed.len())
+    }
+
+    fn decompress_with_allocator<'a>(
+        &self,
+        compressed: &[u8],
+        mut alloc: impl FnMut(usize) -> &'a mut [u8],
+    ) -> Result<usize, CompressorError> {
+        allocated(alloc(compressed.len()), compressed.len())?.copy_from_slice(compressed);
+        Ok(compressed.len())
+    }
+
+    #[cfg(feature = "std")]
+    fn decoder<'a>(
+        &self,
+        reader: Box<dyn Read + 'a>,
+    ) -> Result<Box<dyn Read + 'a>, CompressorError> {
+        Ok(reader)
+    }
+
+    #[cfg(feature = "std")]
+    fn encoder<'a>(
+        &'a self,
+        writer: Box<dyn Write + 'a>,
+    ) -> Result<Box<dyn TEncoder + 'a>, CompressorError> {
+        Ok(Box::new(PassThrough(writer)))
+    }
+}
+
+#[cfg(feature = "std")]
+struct PassThrough<W>(W);
+
+#[cfg(feature = "std")]
+impl<W: Write> Write for PassThrough<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.0.flush()
+    }
+}
+
+#[cfg(feature = "std")]
+impl<W: Write> TEncoder for PassThrough<W> {
+    fn finish(mut self: Box<Self>) -> Result<(), CompressorError> {
+        self.0
+            .flush()
+            .map_err(|err| CompressorError::CompressionError(err.to_string()))
+    }
+}
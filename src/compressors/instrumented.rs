@@ -0,0 +1,160 @@
+use super::TCompressor;
+use crate::error::CompressorError;
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+
+/// Counters collected by an `InstrumentedCompressor`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CompressionStats {
+    /// Successful compress and decompress calls
+    pub calls: u64,
+    pub bytes_in: u64,
+    pub bytes_out: u64,
+    /// Largest buffer capacity seen, not peak memory: the output buffer as a call returns
+    /// for `InstrumentedCompressor`, the read buffer for `compress_stream_with_stats`
+    pub peak_buffer_bytes: usize,
+}
+
+impl CompressionStats {
+    /// `bytes_out` divided by `bytes_in`, or 1.0 before any input was seen
+    pub fn ratio(&self) -> f64 {
+        if self.bytes_in == 0 {
+            return 1.0;
+        }
+        self.bytes_out as f64 / self.bytes_in as f64
+    }
+}
+
+/// Wraps a compressor and records call counts, byte totals and the largest output buffer
+/// capacity.
+///
+/// `peak_buffer_bytes` is the capacity of the buffer the wrapper hands to
+/// `compress_into` and `decompress_into`, read once the call returns, so it includes the
+/// slack left by its growth. It is not the call's peak memory: the old allocation that
+/// briefly coexists with the new one on each reallocation, the intermediate `Vec` of the
+/// default `compress_into`, and scratch state a backend allocates internally (e.g. zstd
+/// contexts) are not visible and not included.
+pub struct InstrumentedCompressor<C> {
+    inner: C,
+    calls: AtomicU64,
+    bytes_in: AtomicU64,
+    bytes_out: AtomicU64,
+    peak_buffer_bytes: AtomicUsize,
+}
+
+impl<C: TCompressor> InstrumentedCompressor<C> {
+    pub fn new(inner: C) -> Self {
+        InstrumentedCompressor {
+            inner,
+            calls: AtomicU64::new(0),
+            bytes_in: AtomicU64::new(0),
+            bytes_out: AtomicU64::new(0),
+            peak_buffer_bytes: AtomicUsize::new(0),
+        }
+    }
+
+    pub fn stats(&self) -> CompressionStats {
+        CompressionStats {
+            calls: self.calls.load(Ordering::Relaxed),
+            bytes_in: self.bytes_in.load(Ordering::Relaxed),
+            bytes_out: self.bytes_out.load(Ordering::Relaxed),
+            peak_buffer_bytes: self.peak_buffer_bytes.load(Ordering::Relaxed),
+        }
+    }
+
+    pub fn reset_stats(&self) {
+        self.calls.store(0, Ordering::Relaxed);
+        self.bytes_in.store(0, Ordering::Relaxed);
+        self.bytes_out.store(0, Ordering::Relaxed);
+        self.peak_buffer_bytes.store(0, Ordering::Relaxed);
+    }
+
+    pub fn into_inner(self) -> C {
+        self.inner
+    }
+
+    fn measured(
+        &self,
+        input: &[u8],
+        run: impl FnOnce(&mut Vec<u8>) -> Result<(), CompressorError>,
+    ) -> Result<Vec<u8>, CompressorError> {
+        let mut output = Vec::new();
+        run(&mut output)?;
+
+        self.calls.fetch_add(1, Ordering::Relaxed);
+        self.bytes_in
+            .fetch_add(input.len() as u64, Ordering::Relaxed);
+        self.bytes_out
+            .fetch_add(output.len() as u64, Ordering::Relaxed);
+        self.peak_buffer_bytes
+            .fetch_max(output.capacity(), Ordering::Relaxed);
+
+        Ok(output)
+    }
+}
+
+impl<C: TCompressor> TCompressor for InstrumentedCompressor<C> {
+    fn compress(&self, value: &[u8]) -> Result<Vec<u8>, CompressorError> {
+        self.measured(value, |output| self.inner.compress_into(value, output))
+    }
+
+    fn decompress(&self, compressed: &[u8]) -> Result<Vec<u8>, CompressorError> {
+        self.measured(compressed, |output| {
+            self.inner.decompress_into(compressed, output)
+        })
+    }
+
+    fn overhead_bytes(&self) -> usize {
+        self.inner.overhead_bytes()
+    }
+
+    fn preferred_chunk_size(&self) -> usize {
+        self.inner.preferred_chunk_size()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compressors::IdentityCompressor;
+
+    #[test]
+    fn test_peak_buffer_bytes() {
+        let compressor = InstrumentedCompressor::new(IdentityCompressor);
+        let large: Vec<u8> = (0..1_000_000).map(|i| (i % 256) as u8).collect();
+
+        compressor.compress(b"small").unwrap();
+        let compressed = compressor.compress(&large).unwrap();
+        compressor.decompress(&compressed).unwrap();
+
+        let stats = compressor.stats();
+        assert_eq!(stats.calls, 3);
+        assert_eq!(stats.bytes_in, 5 + 2 * large.len() as u64);
+        assert_eq!(stats.bytes_out, stats.bytes_in);
+        assert!(stats.peak_buffer_bytes >= large.len());
+        assert!(stats.peak_buffer_bytes <= 2 * large.len());
+
+        assert_eq!(stats.ratio(), 1.0);
+
+        compressor.reset_stats();
+        assert_eq!(compressor.stats(), CompressionStats::default());
+        assert_eq!(compressor.stats().ratio(), 1.0);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_peak_buffer_bytes_snappy() {
+        let compressor = InstrumentedCompressor::new(crate::compressors::SnappyCompressor);
+        let large = b"instrumented snappy input ".repeat(40_000);
+
+        let compressed = compressor.compress(&large).unwrap();
+        let compress_peak = compressor.stats().peak_buffer_bytes;
+        assert!(compress_peak >= compressed.len());
+        assert!(compress_peak <= 2 * compressed.len());
+
+        compressor.decompress(&compressed).unwrap();
+        let peak = compressor.stats().peak_buffer_bytes;
+        assert!(peak >= large.len());
+        assert!(peak <= 2 * large.len());
+    }
+}
@@ -0,0 +1,270 @@
+use super::{TCompressor, TEncoder};
+use crate::error::CompressorError;
+use lz4::{Decoder, Encoder, EncoderBuilder};
+use std::io::{self, BufRead, BufReader, Read, Write};
+
+/// Lowest level at which the LZ4 frame encoder switches to the HC algorithm
+pub const HC_MIN_LEVEL: u32 = 2;
+pub const HC_MAX_LEVEL: u32 = 12;
+
+/// LZ4 frame-format compressor.
+///
+/// The fast mode favors throughput, while `high_compression` spends more time searching
+/// for matches. Both produce standard LZ4 frames that decompress equally fast with the
+/// same `decompress` path.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Lz4Compressor {
+    level: u32,
+}
+
+impl Lz4Compressor {
+    /// Creates a compressor using the fast LZ4 encoder
+    pub fn new() -> Self {
+        Lz4Compressor { level: 0 }
+    }
+
+    /// Creates a compressor using the HC encoder, with `level` clamped to
+    /// `HC_MIN_LEVEL..=HC_MAX_LEVEL`
+    pub fn high_compression(level: u32) -> Self {
+        Lz4Compressor {
+            level: level.clamp(HC_MIN_LEVEL, HC_MAX_LEVEL),
+        }
+    }
+
+    /// The frame compression level, 0 for the fast encoder
+    pub fn level(&self) -> u32 {
+        self.level
+    }
+
+    pub fn is_high_compression(&self) -> bool {
+        self.level >= HC_MIN_LEVEL
+    }
+
+    fn new_encoder<W: Write>(&self, writer: W) -> Result<Encoder<W>, CompressorError> {
+        EncoderBuilder::new()
+            .level(self.level)
+            .build(writer)
+            .map_err(|err| CompressorError::CompressionError(err.to_string()))
+    }
+}
+
+impl Default for Lz4Compressor {
+    fn default() -> Self {
+        Lz4Compressor::new()
+    }
+}
+
+impl TCompressor for Lz4Compressor {
+    fn compress(&self, value: &[u8]) -> Result<Vec<u8>, CompressorError> {
+        let mut encoder = self.new_encoder(Vec::new())?;
+
+        encoder
+            .write_all(value)
+            .map_err(|err| CompressorError::CompressionError(err.to_string()))?;
+
+        let (compressed, result) = encoder.finish();
+        result.map_err(|err| CompressorError::CompressionError(err.to_string()))?;
+        Ok(compressed)
+    }
+
+    fn decompress(&self, compressed: &[u8]) -> Result<Vec<u8>, CompressorError> {
+        let mut decompressed = Vec::new();
+
+        FrameSequenceDecoder::new(compressed)
+            .read_to_end(&mut decompressed)
+            .map_err(|err| CompressorError::DecompressionError(err.to_string()))?;
+
+        Ok(decompressed)
+    }
+
+    fn decompress_strict(&self, compressed: &[u8]) -> Result<Vec<u8>, CompressorError> {
+        if compressed.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        // The frame decoder only ever reads as far as the end of its frame
+        let mut decoder = Decoder::new(compressed)
+            .map_err(|err| CompressorError::DecompressionError(err.to_string()))?;
+        let mut decompressed = Vec::new();
+
+        decoder
+            .read_to_end(&mut decompressed)
+            .map_err(|err| CompressorError::DecompressionError(err.to_string()))?;
+
+        let (rest, result) = decoder.finish();
+        result.map_err(|_| truncated_frame())?;
+
+        match rest.len() {
+            0 => Ok(decompressed),
+            trailing => Err(CompressorError::TrailingData(trailing)),
+        }
+    }
+
+    fn overhead_bytes(&self) -> usize {
+        // 7-byte frame header, 4-byte end mark and content checksum,
+        // plus the size and checksum of a single block
+        7 + 4 + 4 + 4 + 4
+    }
+
+    fn decoder<'a>(
+        &self,
+        reader: Box<dyn Read + 'a>,
+    ) -> Result<Box<dyn Read + 'a>, CompressorError> {
+        Ok(Box::new(FrameSequenceDecoder::new(reader)))
+    }
+
+    fn encoder<'a>(
+        &'a self,
+        writer: Box<dyn Write + 'a>,
+    ) -> Result<Box<dyn TEncoder + 'a>, CompressorError> {
+        Ok(Box::new(self.new_encoder(writer)?))
+    }
+}
+
+impl<W: Write> TEncoder for Encoder<W> {
+    fn finish(self: Box<Self>) -> Result<(), CompressorError> {
+        (*self)
+            .finish()
+            .1
+            .map_err(|err| CompressorError::CompressionError(err.to_string()))
+    }
+}
+
+fn truncated_frame() -> CompressorError {
+    CompressorError::DecompressionError("truncated LZ4 frame".to_string())
+}
+
+/// Decodes one or more concatenated LZ4 frames.
+///
+/// `lz4::Decoder` stops at the end of its frame and reports a truncated frame as a plain
+/// end of input, so each frame is checked for completeness before the next one starts.
+struct FrameSequenceDecoder<R: Read> {
+    state: FrameState<R>,
+}
+
+enum FrameState<R: Read> {
+    /// Between frames, or before the first one
+    Idle(BufReader<R>),
+    Frame(Decoder<BufReader<R>>),
+    Done,
+}
+
+impl<R: Read> FrameSequenceDecoder<R> {
+    fn new(reader: R) -> Self {
+        FrameSequenceDecoder {
+            state: FrameState::Idle(BufReader::new(reader)),
+        }
+    }
+}
+
+impl<R: Read> Read for FrameSequenceDecoder<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        loop {
+            match std::mem::replace(&mut self.state, FrameState::Done) {
+                FrameState::Idle(mut reader) => {
+                    if reader.fill_buf()?.is_empty() {
+                        return Ok(0);
+                    }
+                    self.state = FrameState::Frame(Decoder::new(reader)?);
+                }
+                FrameState::Frame(mut decoder) => match decoder.read(buf)? {
+                    0 => {
+                        let (reader, result) = decoder.finish();
+                        result.map_err(|_| {
+                            io::Error::new(io::ErrorKind::UnexpectedEof, "truncated LZ4 frame")
+                        })?;
+                        self.state = FrameState::Idle(reader);
+                    }
+                    read => {
+                        self.state = FrameState::Frame(decoder);
+                        return Ok(read);
+                    }
+                },
+                FrameState::Done => return Ok(0),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let compressor = Lz4Compressor::default();
+        let data = b"lz4 round trip ".repeat(1000);
+
+        let compressed = compressor.compress(&data).unwrap();
+        assert!(compressed.len() < data.len());
+        assert_eq!(compressor.decompress(&compressed).unwrap(), data);
+        assert_eq!(
+            compressor
+                .decompress(&compressor.compress(b"").unwrap())
+                .unwrap(),
+            b""
+        );
+        assert_eq!(
+            compressor.compress(b"a").unwrap().len(),
+            compressor.overhead_bytes() + 1
+        );
+    }
+
+    #[test]
+    fn test_high_compression() {
+        let text: Vec<u8> = (0..2000)
+            .map(|i| {
+                format!(
+                    "line {} of a log with repeated structure, id={}\n",
+                    i,
+                    i % 37
+                )
+            })
+            .collect::<String>()
+            .into_bytes();
+
+        let fast = Lz4Compressor::new();
+        let high = Lz4Compressor::high_compression(9);
+        assert!(!fast.is_high_compression());
+        assert!(high.is_high_compression());
+        assert_eq!(Lz4Compressor::high_compression(99).level(), HC_MAX_LEVEL);
+
+        let fast_compressed = fast.compress(&text).unwrap();
+        let high_compressed = high.compress(&text).unwrap();
+        assert!(high_compressed.len() < fast_compressed.len());
+
+        // Either compressor reads the other's frames
+        assert_eq!(fast.decompress(&high_compressed).unwrap(), text);
+        assert_eq!(high.decompress(&fast_compressed).unwrap(), text);
+    }
+
+    #[test]
+    fn test_decompress_strict() {
+        let compressor = Lz4Compressor::default();
+        let frame = compressor.compress(b"strict payload").unwrap();
+        assert_eq!(
+            compressor.decompress_strict(&frame).unwrap(),
+            b"strict payload"
+        );
+
+        let mut smuggled = frame.clone();
+        smuggled.extend_from_slice(&compressor.compress(b"smuggled").unwrap());
+        assert_eq!(
+            compressor.decompress(&smuggled).unwrap(),
+            b"strict payloadsmuggled"
+        );
+        assert!(matches!(
+            compressor.decompress_strict(&smuggled),
+            Err(CompressorError::TrailingData(len)) if len == smuggled.len() - frame.len()
+        ));
+
+        assert!(compressor.decompress(&frame[..frame.len() - 3]).is_err());
+        assert!(compressor
+            .decompress_strict(&frame[..frame.len() - 3])
+            .is_err());
+    }
+}
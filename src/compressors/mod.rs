@@ -1,29 +1,1334 @@
+use crate::chunking::chunk_content_defined;
+#[cfg(feature = "std")]
+use crate::diagnostic::{detect_compressed, estimate_entropy, CompressDiagnostic};
 use crate::error::CompressorError;
+use alloc::{borrow::Cow, format, sync::Arc, vec::Vec};
+use core::fmt;
+use core::str::FromStr;
+#[cfg(feature = "std")]
+use std::collections::HashMap;
+#[cfg(feature = "std")]
+use std::hash::Hash;
+#[cfg(feature = "std")]
+use std::io::{self, Read, Write};
+#[cfg(feature = "std")]
+use std::time::Instant;
 
+pub mod chain;
+pub mod checked;
+#[cfg(feature = "aead")]
+pub mod encrypted;
+#[cfg(feature = "gzip")]
+pub mod gzip;
+pub mod identity;
+pub mod instrumented;
+#[cfg(feature = "lz4")]
+pub mod lz4;
+pub mod padded;
+#[cfg(feature = "std")]
+pub mod pooled;
+pub mod resilient;
+mod shard;
+#[cfg(feature = "std")]
 pub mod snappy;
+pub mod snappy_raw;
+#[cfg(feature = "zstd")]
+pub mod zstd;
 
-pub use snappy::SnappyCompressor;
+pub use chain::ChainCompressor;
+pub use checked::CheckedCompressor;
+#[cfg(feature = "aead")]
+pub use encrypted::EncryptedCompressor;
+#[cfg(feature = "gzip")]
+pub use gzip::GzipCompressor;
+pub use identity::IdentityCompressor;
+pub use instrumented::{CompressionStats, InstrumentedCompressor};
+#[cfg(feature = "lz4")]
+pub use lz4::Lz4Compressor;
+pub use padded::PaddedCompressor;
+#[cfg(feature = "std")]
+pub use pooled::PooledCompressor;
+pub use resilient::ResilientCompressor;
+#[cfg(feature = "std")]
+pub use snappy::{
+    snappy_raw_compress, snappy_raw_decompress, snappy_raw_decompress_auto,
+    ChunkedSnappyCompressor, SnappyBodyCompressor, SnappyCompressor, SnappyDecoder,
+};
+pub use snappy_raw::SnappyRawCompressor;
+#[cfg(feature = "zstd")]
+pub use zstd::{ZstdCompressor, ZstdDictionaryCompressor};
 
+/// Default chunk size for streaming when a compressor has no natural block size
+const DEFAULT_CHUNK_SIZE: usize = 64 * 1024;
+
+/// A streaming encoder created by `TCompressor::encoder`
+#[cfg(feature = "std")]
+pub trait TEncoder: Write {
+    /// Flushes buffered data and writes the end of the compressed stream
+    fn finish(self: Box<Self>) -> Result<(), CompressorError>;
+}
+
+/// A compression algorithm over byte buffers.
+///
+/// Every method takes `&self`, and the built-in compressors are `Send + Sync` with no
+/// state shared between calls beyond what is synchronized (counters, buffer pools), so
+/// one instance can serve any number of threads concurrently, e.g. behind an `Arc`,
+/// without calls interfering. Custom implementations shared this way must uphold the same.
 pub trait TCompressor {
     fn compress(&self, value: &[u8]) -> Result<Vec<u8>, CompressorError>;
     fn decompress(&self, compressed: &[u8]) -> Result<Vec<u8>, CompressorError>;
+
+    /// Decompresses exactly one frame, failing with `TrailingData` if any bytes follow it.
+    /// Unlike `decompress`, which accepts concatenated frames, this rejects data smuggled
+    /// after the stream. The default implementation has no notion of frames and simply
+    /// delegates to `decompress`.
+    fn decompress_strict(&self, compressed: &[u8]) -> Result<Vec<u8>, CompressorError> {
+        self.decompress(compressed)
+    }
+
+    /// Decompresses into a shared buffer so many readers can hold the result;
+    /// cloning the returned `Arc` never copies the data
+    fn decompress_arc(&self, compressed: &[u8]) -> Result<Arc<[u8]>, CompressorError> {
+        self.decompress(compressed).map(Arc::from)
+    }
+
+    /// Compresses possibly borrowed data, returning it still borrowed when compression is
+    /// a no-op so that path never allocates. The default always compresses into an owned
+    /// buffer; pass-through compressors such as `IdentityCompressor` override it.
+    fn compress_cow<'a>(&self, value: Cow<'a, [u8]>) -> Result<Cow<'a, [u8]>, CompressorError> {
+        self.compress(&value).map(Cow::Owned)
+    }
+
+    /// `compress_cow` for text, e.g. strings that may borrow from a request buffer. The
+    /// borrow is threaded through as bytes and an owned `String` is reused as is.
+    fn compress_cow_str<'a>(&self, value: Cow<'a, str>) -> Result<Cow<'a, [u8]>, CompressorError> {
+        self.compress_cow(match value {
+            Cow::Borrowed(text) => Cow::Borrowed(text.as_bytes()),
+            Cow::Owned(text) => Cow::Owned(text.into_bytes()),
+        })
+    }
+
+    /// Appends the compressed `value` to `output`, letting callers reuse its allocation.
+    /// On error `output` may hold a partial result. The default compresses into a new
+    /// buffer and copies it.
+    fn compress_into(&self, value: &[u8], output: &mut Vec<u8>) -> Result<(), CompressorError> {
+        output.extend_from_slice(&self.compress(value)?);
+        Ok(())
+    }
+
+    /// Appends the decompressed `compressed` to `output`, see `compress_into`
+    fn decompress_into(
+        &self,
+        compressed: &[u8],
+        output: &mut Vec<u8>,
+    ) -> Result<(), CompressorError> {
+        output.extend_from_slice(&self.decompress(compressed)?);
+        Ok(())
+    }
+
+    /// Uncompressed length of `compressed` if the format records it up front, read
+    /// without decoding. The default knows no such header and returns `None`.
+    fn decompressed_len_hint(&self, _compressed: &[u8]) -> Option<usize> {
+        None
+    }
+
+    /// Decompresses into a buffer provided by `alloc`, e.g. from a frame arena, instead of
+    /// the global heap. `alloc` is called once with the decompressed length and must return
+    /// a slice at least that long; the number of bytes written is returned. A shorter slice
+    /// fails with `DecompressionError`.
+    ///
+    /// Formats with a `decompressed_len_hint` that can decode into a slice override this to
+    /// write straight into it; the default decodes into a temporary `Vec` and copies.
+    fn decompress_with_allocator<'a>(
+        &self,
+        compressed: &[u8],
+        mut alloc: impl FnMut(usize) -> &'a mut [u8],
+    ) -> Result<usize, CompressorError>
+    where
+        Self: Sized,
+    {
+        let decompressed = self.decompress(compressed)?;
+        allocated(alloc(decompressed.len()), decompressed.len())?.copy_from_slice(&decompressed);
+        Ok(decompressed.len())
+    }
+
+    /// `compress` that also returns the number of input bytes consumed, like `io::copy`
+    fn compress_counted(&self, value: &[u8]) -> Result<(Vec<u8>, u64), CompressorError> {
+        let compressed = self.compress(value)?;
+        Ok((compressed, value.len() as u64))
+    }
+
+    /// `decompress` that also returns the number of compressed bytes consumed
+    fn decompress_counted(&self, compressed: &[u8]) -> Result<(Vec<u8>, u64), CompressorError> {
+        let decompressed = self.decompress(compressed)?;
+        Ok((decompressed, compressed.len() as u64))
+    }
+
+    /// Decompresses and checks the CRC32 of the output against `expected_crc32`, a
+    /// checksum the caller computed over the original data. A mismatch fails with
+    /// `IntegrityError`.
+    fn decompress_verify(
+        &self,
+        compressed: &[u8],
+        expected_crc32: u32,
+    ) -> Result<Vec<u8>, CompressorError> {
+        let decompressed = self.decompress(compressed)?;
+
+        let actual = crc32fast::hash(&decompressed);
+        if actual != expected_crc32 {
+            return Err(CompressorError::IntegrityError(format!(
+                "checksum mismatch: expected {:08x}, got {:08x}",
+                expected_crc32, actual
+            )));
+        }
+
+        Ok(decompressed)
+    }
+
+    /// Streams the decompressed `compressed` into `out` while computing its CRC32, then
+    /// checks it against `expected_crc32` like `decompress_verify`, in a single pass.
+    ///
+    /// The checksum is only known once the stream ends, so `out` has already received the
+    /// entire output when a mismatch fails with `IntegrityError`, and a prefix of it when
+    /// decoding fails; callers must discard what was written on any error.
+    #[cfg(feature = "std")]
+    fn decompress_stream_checked<W: Write>(
+        &self,
+        compressed: &[u8],
+        mut out: W,
+        expected_crc32: u32,
+    ) -> Result<(), CompressorError>
+    where
+        Self: Sized,
+    {
+        let mut decoder = self.decoder(Box::new(compressed))?;
+        let mut hasher = crc32fast::Hasher::new();
+        let mut buffer = vec![0; self.preferred_chunk_size()];
+
+        loop {
+            let read = match decoder.read(&mut buffer) {
+                Ok(0) => break,
+                Ok(read) => read,
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                Err(err) => return Err(CompressorError::DecompressionError(err.to_string())),
+            };
+            hasher.update(&buffer[..read]);
+            out.write_all(&buffer[..read])
+                .map_err(|err| CompressorError::IoError(err.to_string()))?;
+        }
+        out.flush()
+            .map_err(|err| CompressorError::IoError(err.to_string()))?;
+
+        let actual = hasher.finalize();
+        if actual != expected_crc32 {
+            return Err(CompressorError::IntegrityError(format!(
+                "checksum mismatch: expected {:08x}, got {:08x}",
+                expected_crc32, actual
+            )));
+        }
+
+        Ok(())
+    }
+
+    /// Compresses `value` and splits the result into shards of at most `max_shard` bytes,
+    /// each with a small header recording its index and the shard count
+    fn compress_sharded(
+        &self,
+        value: &[u8],
+        max_shard: usize,
+    ) -> Result<Vec<Vec<u8>>, CompressorError> {
+        shard::split(&self.compress(value)?, max_shard)
+    }
+
+    /// Reassembles and decompresses shards from `compress_sharded`, given in any order.
+    /// Missing, duplicated or mixed-up shards fail with `IntegrityError`.
+    fn decompress_sharded(&self, shards: &[Vec<u8>]) -> Result<Vec<u8>, CompressorError> {
+        self.decompress(&shard::reassemble(shards)?)
+    }
+
+    /// Splits `value` with `chunk_content_defined` and compresses every chunk on its own,
+    /// so chunks unchanged between versions of the data compress to identical bytes and
+    /// can be deduplicated, e.g. keyed by their hash.
+    ///
+    /// Unlike `compress_sharded`, chunks carry no index header, which would make their
+    /// bytes depend on their position; callers keep the order themselves.
+    fn compress_content_defined(
+        &self,
+        value: &[u8],
+        avg_chunk: usize,
+    ) -> Result<Vec<Vec<u8>>, CompressorError> {
+        chunk_content_defined(value, avg_chunk)
+            .into_iter()
+            .map(|chunk| self.compress(chunk))
+            .collect()
+    }
+
+    /// Decompresses chunks from `compress_content_defined`, in order, and joins them
+    fn decompress_content_defined(&self, chunks: &[Vec<u8>]) -> Result<Vec<u8>, CompressorError> {
+        let mut decompressed = Vec::new();
+        for chunk in chunks {
+            self.decompress_into(chunk, &mut decompressed)?;
+        }
+        Ok(decompressed)
+    }
+
+    /// Approximate fixed number of bytes the format adds on top of the payload
+    /// (headers, trailers, checksums), useful for computing break-even sizes
+    fn overhead_bytes(&self) -> usize {
+        0
+    }
+
+    /// Compressed-to-original size ratio for `sample`, or for only its first
+    /// `sample_prefix_len` bytes to bound the work. Values below 1.0 mean the data
+    /// compresses; an empty sample reports 1.0.
+    fn estimate_ratio(
+        &self,
+        sample: &[u8],
+        sample_prefix_len: Option<usize>,
+    ) -> Result<f64, CompressorError> {
+        let sample = match sample_prefix_len {
+            Some(len) => &sample[..len.min(sample.len())],
+            None => sample,
+        };
+        if sample.is_empty() {
+            return Ok(1.0);
+        }
+
+        Ok(self.compress(sample)?.len() as f64 / sample.len() as f64)
+    }
+
+    /// Natural block or frame size of the algorithm, a good read size for streaming loops
+    fn preferred_chunk_size(&self) -> usize {
+        DEFAULT_CHUNK_SIZE
+    }
+
+    /// Wraps `reader` in a decoder that yields the decompressed bytes.
+    /// The default implementation reads and decompresses the whole input up front;
+    /// compressors with a streaming format should override it.
+    #[cfg(feature = "std")]
+    fn decoder<'a>(
+        &self,
+        mut reader: Box<dyn Read + 'a>,
+    ) -> Result<Box<dyn Read + 'a>, CompressorError> {
+        let mut compressed = Vec::new();
+        reader
+            .read_to_end(&mut compressed)
+            .map_err(|err| CompressorError::DecompressionError(err.to_string()))?;
+
+        Ok(Box::new(io::Cursor::new(self.decompress(&compressed)?)))
+    }
+
+    /// Wraps `writer` in an encoder that compresses everything written to it.
+    /// The default implementation buffers all input and compresses it on `finish`;
+    /// compressors with a streaming format should override it.
+    #[cfg(feature = "std")]
+    fn encoder<'a>(
+        &'a self,
+        writer: Box<dyn Write + 'a>,
+    ) -> Result<Box<dyn TEncoder + 'a>, CompressorError> {
+        Ok(Box::new(BufferedEncoder {
+            compressor: self,
+            writer,
+            buffer: Vec::new(),
+        }))
+    }
+
+    /// Returns `len` bytes of the decompressed output starting at offset `start`.
+    ///
+    /// Decoding goes through `decoder` and stops at the end of the range; the bytes before
+    /// `start` are decoded and discarded, never collected. A range reaching past the end of
+    /// the output fails with `DecompressionError`.
+    #[cfg(feature = "std")]
+    fn decompress_range(
+        &self,
+        compressed: &[u8],
+        start: usize,
+        len: usize,
+    ) -> Result<Vec<u8>, CompressorError> {
+        let end = start.checked_add(len).ok_or_else(|| {
+            CompressorError::DecompressionError("range end overflows usize".to_string())
+        })?;
+        let mut reader = self.decoder(Box::new(compressed))?;
+
+        let skipped = io::copy(&mut (&mut reader).take(start as u64), &mut io::sink())
+            .map_err(|err| CompressorError::DecompressionError(err.to_string()))?;
+
+        let mut range = Vec::new();
+        if skipped == start as u64 {
+            reader
+                .take(len as u64)
+                .read_to_end(&mut range)
+                .map_err(|err| CompressorError::DecompressionError(err.to_string()))?;
+        }
+
+        if skipped < start as u64 || range.len() < len {
+            return Err(CompressorError::DecompressionError(format!(
+                "range {}..{} exceeds the decompressed length of {} bytes",
+                start,
+                end,
+                skipped as usize + range.len()
+            )));
+        }
+
+        Ok(range)
+    }
+
+    /// Best-effort `decompress` for damaged data: returns every byte decoded before the
+    /// first error, along with that error, e.g. to recover the intact records of a
+    /// truncated log. Decoding goes through `decoder`, so compressors relying on the
+    /// default one recover nothing from a damaged input.
+    #[cfg(feature = "std")]
+    fn decompress_lossy(&self, compressed: &[u8]) -> (Vec<u8>, Option<CompressorError>) {
+        let mut decompressed = Vec::new();
+        let mut decoder = match self.decoder(Box::new(compressed)) {
+            Ok(decoder) => decoder,
+            Err(err) => return (decompressed, Some(err)),
+        };
+
+        // `read_to_end` keeps whatever it appended before failing
+        let error = decoder.read_to_end(&mut decompressed).err().map(|err| {
+            CompressorError::DecompressionError(format!(
+                "stopped after {} bytes: {}",
+                decompressed.len(),
+                err
+            ))
+        });
+        (decompressed, error)
+    }
+
+    /// Compresses `value` in chunks, calling `on_progress` with the number of input
+    /// bytes processed so far after each chunk
+    #[cfg(feature = "std")]
+    fn compress_with_progress(
+        &self,
+        value: &[u8],
+        mut on_progress: impl FnMut(usize),
+    ) -> Result<Vec<u8>, CompressorError>
+    where
+        Self: Sized,
+    {
+        let mut compressed = Vec::new();
+        let mut encoder = self.encoder(Box::new(&mut compressed))?;
+        let mut processed = 0;
+
+        for chunk in value.chunks(self.preferred_chunk_size()) {
+            encoder
+                .write_all(chunk)
+                .map_err(|err| CompressorError::CompressionError(err.to_string()))?;
+            processed += chunk.len();
+            on_progress(processed);
+        }
+
+        encoder.finish()?;
+        Ok(compressed)
+    }
+
+    /// Compresses `value` chunk by chunk, failing with `DeadlineExceeded` as soon as a
+    /// chunk boundary is reached after `deadline`.
+    ///
+    /// Only streaming encoders (Snappy, gzip, zstd, LZ4, identity) do their work chunk by
+    /// chunk. Compressors on the buffered default `encoder`, e.g. `SnappyRawCompressor`
+    /// or the `PaddedCompressor`, `CheckedCompressor` and `ChainCompressor` wrappers,
+    /// compress everything in one call at the end, which the deadline cannot interrupt.
+    /// The deadline is checked once more afterwards, so their late result is still
+    /// reported as `DeadlineExceeded`.
+    #[cfg(feature = "std")]
+    fn compress_until(&self, value: &[u8], deadline: Instant) -> Result<Vec<u8>, CompressorError> {
+        let mut compressed = Vec::new();
+        let mut encoder = self.encoder(Box::new(&mut compressed))?;
+
+        for chunk in value.chunks(self.preferred_chunk_size()) {
+            if Instant::now() >= deadline {
+                return Err(CompressorError::DeadlineExceeded);
+            }
+            encoder
+                .write_all(chunk)
+                .map_err(|err| CompressorError::CompressionError(err.to_string()))?;
+        }
+
+        if Instant::now() >= deadline {
+            return Err(CompressorError::DeadlineExceeded);
+        }
+        encoder.finish()?;
+
+        if Instant::now() >= deadline {
+            return Err(CompressorError::DeadlineExceeded);
+        }
+        Ok(compressed)
+    }
+
+    /// Compresses `value` and returns the SHA-256 digest of the uncompressed input with
+    /// it, e.g. as a content address. Each chunk is hashed as it is fed to the encoder,
+    /// so the input is traversed once.
+    #[cfg(feature = "sha2")]
+    fn compress_with_digest(&self, value: &[u8]) -> Result<(Vec<u8>, [u8; 32]), CompressorError> {
+        use sha2::{Digest, Sha256};
+
+        let mut hasher = Sha256::new();
+        let mut compressed = Vec::new();
+        let mut encoder = self.encoder(Box::new(&mut compressed))?;
+
+        for chunk in value.chunks(self.preferred_chunk_size()) {
+            hasher.update(chunk);
+            encoder
+                .write_all(chunk)
+                .map_err(|err| CompressorError::CompressionError(err.to_string()))?;
+        }
+
+        encoder.finish()?;
+        Ok((compressed, hasher.finalize().into()))
+    }
+
+    /// `compress` for `bytes` buffers; the result takes ownership of the compressed
+    /// allocation instead of copying it
+    #[cfg(feature = "bytes")]
+    fn compress_bytes(&self, value: bytes::Bytes) -> Result<bytes::Bytes, CompressorError> {
+        self.compress(&value).map(bytes::Bytes::from)
+    }
+
+    /// `decompress` for `bytes` buffers; the result takes ownership of the decompressed
+    /// allocation instead of copying it
+    #[cfg(feature = "bytes")]
+    fn decompress_bytes(&self, compressed: bytes::Bytes) -> Result<bytes::Bytes, CompressorError> {
+        self.decompress(&compressed).map(bytes::Bytes::from)
+    }
+
+    /// Splits `value` into `chunk_size` blocks, compresses them in parallel and concatenates
+    /// the resulting frames. The output is only readable by formats that accept concatenated
+    /// frames, which `decompress_concatenated` handles for every built-in algorithm.
+    #[cfg(feature = "rayon")]
+    fn compress_parallel_chunked(
+        &self,
+        value: &[u8],
+        chunk_size: usize,
+    ) -> Result<Vec<u8>, CompressorError>
+    where
+        Self: Sync + Sized,
+    {
+        use rayon::prelude::*;
+
+        if chunk_size == 0 {
+            return Err(CompressorError::ConfigError(
+                "chunk size must be greater than zero".to_string(),
+            ));
+        }
+
+        let frames = value
+            .par_chunks(chunk_size)
+            .map(|chunk| self.compress(chunk))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(frames.concat())
+    }
+
+    /// Compresses every payload in `items`, keeping their keys. The first failure is
+    /// returned as a `KeyedError` naming the payload's key.
+    #[cfg(feature = "std")]
+    fn compress_map<K>(
+        &self,
+        items: &HashMap<K, Vec<u8>>,
+    ) -> Result<HashMap<K, Vec<u8>>, CompressorError>
+    where
+        K: fmt::Display + Eq + Hash + Clone,
+        Self: Sized,
+    {
+        items
+            .iter()
+            .map(|(key, payload)| match self.compress(payload) {
+                Ok(compressed) => Ok((key.clone(), compressed)),
+                Err(err) => Err(err.with_key(key)),
+            })
+            .collect()
+    }
+
+    /// Compresses `value` unless it already looks compressed (see `is_likely_compressed`),
+    /// in which case it fails with `AlreadyCompressed` instead of wasting CPU and space
+    #[cfg(feature = "std")]
+    fn compress_guarded(&self, value: &[u8]) -> Result<Vec<u8>, CompressorError> {
+        if let Some(format) = detect_compressed(value) {
+            return Err(CompressorError::AlreadyCompressed(format.to_string()));
+        }
+
+        self.compress(value)
+    }
+
+    /// Checks that `compressed` decodes completely without keeping the decompressed output.
+    /// Streaming compressors decode into a sink in bounded memory; those relying on the
+    /// default `decoder` still materialize the output once.
+    #[cfg(feature = "std")]
+    fn validate(&self, compressed: &[u8]) -> Result<(), CompressorError> {
+        let mut decoder = self.decoder(Box::new(compressed))?;
+
+        io::copy(&mut decoder, &mut io::sink())
+            .map(drop)
+            .map_err(|err| CompressorError::DecompressionError(err.to_string()))
+    }
+
+    /// Compresses `value` and reports whether the output expanded, along with the framing
+    /// overhead and the input's entropy, to tell framing cost from incompressible content
+    #[cfg(feature = "std")]
+    fn compress_diagnostic(&self, value: &[u8]) -> Result<CompressDiagnostic, CompressorError> {
+        let compressed = self.compress(value)?;
+
+        Ok(CompressDiagnostic {
+            expanded: compressed.len() > value.len(),
+            overhead_bytes: self.overhead_bytes(),
+            estimated_entropy: estimate_entropy(value),
+            compressed,
+        })
+    }
+}
+
+/// The first `len` bytes of a buffer returned by a `decompress_with_allocator` allocator
+fn allocated(buffer: &mut [u8], len: usize) -> Result<&mut [u8], CompressorError> {
+    let available = buffer.len();
+    buffer.get_mut(..len).ok_or_else(|| {
+        CompressorError::DecompressionError(format!(
+            "allocator returned {} bytes, {} needed",
+            available, len
+        ))
+    })
+}
+
+/// Fallback encoder that compresses the whole input at once when finished
+#[cfg(feature = "std")]
+struct BufferedEncoder<'a, C: ?Sized> {
+    compressor: &'a C,
+    writer: Box<dyn Write + 'a>,
+    buffer: Vec<u8>,
+}
+
+#[cfg(feature = "std")]
+impl<C: ?Sized> Write for BufferedEncoder<'_, C> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.buffer.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(feature = "std")]
+impl<C: TCompressor + ?Sized> TEncoder for BufferedEncoder<'_, C> {
+    fn finish(mut self: Box<Self>) -> Result<(), CompressorError> {
+        let compressed = self.compressor.compress(&self.buffer)?;
+
+        self.writer
+            .write_all(&compressed)
+            .and_then(|_| self.writer.flush())
+            .map_err(|err| CompressorError::CompressionError(err.to_string()))
+    }
 }
 
 /// Compression algorithm types
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum CompressionAlgorithm {
+    /// No compression, data is stored as-is
+    Identity,
     Snappy,
-    // Gzip or something
+    #[cfg(feature = "gzip")]
+    Gzip,
+    #[cfg(feature = "zstd")]
+    Zstd,
+    #[cfg(feature = "lz4")]
+    Lz4,
 }
 
+impl CompressionAlgorithm {
+    /// Every algorithm compiled in, in tag order
+    pub fn all() -> &'static [CompressionAlgorithm] {
+        &[
+            CompressionAlgorithm::Identity,
+            CompressionAlgorithm::Snappy,
+            #[cfg(feature = "gzip")]
+            CompressionAlgorithm::Gzip,
+            #[cfg(feature = "zstd")]
+            CompressionAlgorithm::Zstd,
+            #[cfg(feature = "lz4")]
+            CompressionAlgorithm::Lz4,
+        ]
+    }
+
+    /// Stable byte identifying the algorithm in self-describing formats.
+    /// Tags never change meaning, whichever features are enabled.
+    pub fn tag(&self) -> u8 {
+        match self {
+            CompressionAlgorithm::Identity => 0,
+            CompressionAlgorithm::Snappy => 1,
+            #[cfg(feature = "gzip")]
+            CompressionAlgorithm::Gzip => 2,
+            #[cfg(feature = "zstd")]
+            CompressionAlgorithm::Zstd => 3,
+            #[cfg(feature = "lz4")]
+            CompressionAlgorithm::Lz4 => 4,
+        }
+    }
+
+    /// Fails with `UnsupportedAlgorithm` for the tag of an algorithm whose feature is
+    /// disabled in this build, and with `UnknownAlgorithm` for tags never assigned
+    pub fn from_tag(tag: u8) -> Result<Self, CompressorError> {
+        match tag {
+            0 => Ok(CompressionAlgorithm::Identity),
+            1 => Ok(CompressionAlgorithm::Snappy),
+            #[cfg(feature = "gzip")]
+            2 => Ok(CompressionAlgorithm::Gzip),
+            #[cfg(feature = "zstd")]
+            3 => Ok(CompressionAlgorithm::Zstd),
+            #[cfg(feature = "lz4")]
+            4 => Ok(CompressionAlgorithm::Lz4),
+            #[cfg(not(feature = "gzip"))]
+            2 => Err(CompressorError::UnsupportedAlgorithm("gzip")),
+            #[cfg(not(feature = "zstd"))]
+            3 => Err(CompressorError::UnsupportedAlgorithm("zstd")),
+            #[cfg(not(feature = "lz4"))]
+            4 => Err(CompressorError::UnsupportedAlgorithm("lz4")),
+            _ => Err(CompressorError::UnknownAlgorithm(tag)),
+        }
+    }
+
+    /// Level of the compressor `CompressorFactory` builds for the algorithm, or `None`
+    /// for algorithms without levels (identity and snappy). LZ4's 0 is its fast mode.
+    pub fn default_level(&self) -> Option<u32> {
+        match self {
+            CompressionAlgorithm::Identity | CompressionAlgorithm::Snappy => None,
+            #[cfg(feature = "gzip")]
+            CompressionAlgorithm::Gzip => Some(6),
+            #[cfg(feature = "zstd")]
+            CompressionAlgorithm::Zstd => Some(3),
+            #[cfg(feature = "lz4")]
+            CompressionAlgorithm::Lz4 => Some(0),
+        }
+    }
+
+    /// Lowercase name used in configuration strings
+    pub fn name(&self) -> &'static str {
+        match self {
+            CompressionAlgorithm::Identity => "identity",
+            CompressionAlgorithm::Snappy => "snappy",
+            #[cfg(feature = "gzip")]
+            CompressionAlgorithm::Gzip => "gzip",
+            #[cfg(feature = "zstd")]
+            CompressionAlgorithm::Zstd => "zstd",
+            #[cfg(feature = "lz4")]
+            CompressionAlgorithm::Lz4 => "lz4",
+        }
+    }
+}
+
+impl fmt::Display for CompressionAlgorithm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl TryFrom<u8> for CompressionAlgorithm {
+    type Error = CompressorError;
+
+    fn try_from(tag: u8) -> Result<Self, Self::Error> {
+        CompressionAlgorithm::from_tag(tag)
+    }
+}
+
+impl From<CompressionAlgorithm> for u8 {
+    fn from(algorithm: CompressionAlgorithm) -> u8 {
+        algorithm.tag()
+    }
+}
+
+/// Parses an algorithm name, case-insensitively. Names of algorithms whose feature is
+/// disabled in this build fail with `UnsupportedAlgorithm`, other names with `ConfigError`.
+impl FromStr for CompressionAlgorithm {
+    type Err = CompressorError;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name.trim().to_ascii_lowercase().as_str() {
+            "identity" | "none" => Ok(CompressionAlgorithm::Identity),
+            "snappy" => Ok(CompressionAlgorithm::Snappy),
+            #[cfg(feature = "gzip")]
+            "gzip" => Ok(CompressionAlgorithm::Gzip),
+            #[cfg(feature = "zstd")]
+            "zstd" => Ok(CompressionAlgorithm::Zstd),
+            #[cfg(feature = "lz4")]
+            "lz4" => Ok(CompressionAlgorithm::Lz4),
+            #[cfg(not(feature = "gzip"))]
+            "gzip" => Err(CompressorError::UnsupportedAlgorithm("gzip")),
+            #[cfg(not(feature = "zstd"))]
+            "zstd" => Err(CompressorError::UnsupportedAlgorithm("zstd")),
+            #[cfg(not(feature = "lz4"))]
+            "lz4" => Err(CompressorError::UnsupportedAlgorithm("lz4")),
+            _ => Err(CompressorError::ConfigError(format!(
+                "unknown compression algorithm '{}'",
+                name
+            ))),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
 pub struct CompressorFactory;
 
+#[cfg(feature = "std")]
 impl CompressorFactory {
-    pub fn get_compressor(algorithm: CompressionAlgorithm) -> Box<dyn TCompressor> {
-        match algorithm {
+    /// Returns the default compressor for `algorithm`. With the `tracing` feature it
+    /// reports its compress and decompress calls as spans.
+    pub fn get_compressor(algorithm: CompressionAlgorithm) -> Box<dyn TCompressor + Send + Sync> {
+        let compressor: Box<dyn TCompressor + Send + Sync> = match algorithm {
+            CompressionAlgorithm::Identity => Box::new(IdentityCompressor),
             CompressionAlgorithm::Snappy => Box::new(SnappyCompressor),
+            #[cfg(feature = "gzip")]
+            CompressionAlgorithm::Gzip => Box::new(GzipCompressor::default()),
+            #[cfg(feature = "zstd")]
+            CompressionAlgorithm::Zstd => Box::new(ZstdCompressor::default()),
+            #[cfg(feature = "lz4")]
+            CompressionAlgorithm::Lz4 => Box::new(Lz4Compressor::default()),
+        };
+
+        #[cfg(feature = "tracing")]
+        let compressor = Box::new(crate::trace::TracedCompressor::new(algorithm, compressor));
+
+        compressor
+    }
+
+    /// Decompresses `data` with the first of `candidates` that succeeds, for stores holding
+    /// untagged blobs in mixed algorithms. Candidates whose magic prefix `data` starts with
+    /// are tried first, the rest in the given order. `Identity` accepts any input, so it
+    /// only ever wins when no other candidate does.
+    ///
+    /// If every candidate fails, the error of the first one tried is returned.
+    #[cfg(feature = "std")]
+    pub fn decompress_any(
+        data: &[u8],
+        candidates: &[CompressionAlgorithm],
+    ) -> Result<Vec<u8>, CompressorError> {
+        let detected = detect_compressed(data);
+        let mut ordered: Vec<CompressionAlgorithm> = candidates
+            .iter()
+            .copied()
+            .filter(|algorithm| Some(algorithm.name()) == detected)
+            .collect();
+        ordered.extend(candidates.iter().copied().filter(|algorithm| {
+            Some(algorithm.name()) != detected && *algorithm != CompressionAlgorithm::Identity
+        }));
+        if candidates.contains(&CompressionAlgorithm::Identity) {
+            ordered.push(CompressionAlgorithm::Identity);
         }
+
+        let mut first_error = None;
+        for algorithm in ordered {
+            match Self::get_compressor(algorithm).decompress(data) {
+                Ok(decompressed) => return Ok(decompressed),
+                Err(err) => {
+                    first_error.get_or_insert(err.with_algorithm(algorithm));
+                }
+            }
+        }
+
+        Err(first_error.unwrap_or_else(|| {
+            CompressorError::ConfigError("no candidate algorithms given".to_string())
+        }))
+    }
+
+    /// Compresses `value` with the algorithm named `preferred`, or with `fallback` if that
+    /// algorithm is not enabled in this build, and returns the algorithm actually used.
+    /// Names of no known algorithm still fail with `ConfigError`.
+    pub fn compress_or_fallback(
+        value: &[u8],
+        preferred: &str,
+        fallback: CompressionAlgorithm,
+    ) -> Result<(Vec<u8>, CompressionAlgorithm), CompressorError> {
+        let algorithm = match preferred.parse() {
+            Ok(algorithm) => algorithm,
+            Err(CompressorError::UnsupportedAlgorithm(_)) => fallback,
+            Err(err) => return Err(err),
+        };
+
+        let compressed = Self::get_compressor(algorithm)
+            .compress(value)
+            .map_err(|err| err.with_algorithm(algorithm))?;
+        Ok((compressed, algorithm))
+    }
+
+    /// Whether `data` starts with the magic prefix of the crate's tagged format
+    /// (see `compress_tagged`). Only the prefix is checked, not the tags after it.
+    pub fn is_tagged(data: &[u8]) -> bool {
+        data.starts_with(crate::tagged::TAGGED_MAGIC)
     }
 }
 
+#[cfg(feature = "std")]
 pub type DefaultCompressor = SnappyCompressor;
+
+/// Reversible test compressor that reverses the bytes, relying on the buffered
+/// `encoder`/`decoder` defaults
+#[cfg(test)]
+pub(crate) struct ReverseCompressor;
+
+#[cfg(test)]
+impl TCompressor for ReverseCompressor {
+    fn compress(&self, value: &[u8]) -> Result<Vec<u8>, CompressorError> {
+        Ok(value.iter().rev().copied().collect())
+    }
+
+    fn decompress(&self, compressed: &[u8]) -> Result<Vec<u8>, CompressorError> {
+        Ok(compressed.iter().rev().copied().collect())
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_builtin_compressors_are_send_sync() {
+        fn assert_send_sync<T: Send + Sync>() {}
+
+        assert_send_sync::<IdentityCompressor>();
+        assert_send_sync::<SnappyCompressor>();
+        assert_send_sync::<SnappyBodyCompressor>();
+        assert_send_sync::<ChunkedSnappyCompressor>();
+        assert_send_sync::<SnappyRawCompressor>();
+        assert_send_sync::<CheckedCompressor<SnappyCompressor>>();
+        assert_send_sync::<InstrumentedCompressor<SnappyCompressor>>();
+        assert_send_sync::<PaddedCompressor<SnappyCompressor>>();
+        assert_send_sync::<PooledCompressor<SnappyCompressor>>();
+        assert_send_sync::<ResilientCompressor<SnappyCompressor>>();
+        #[cfg(feature = "gzip")]
+        assert_send_sync::<GzipCompressor>();
+        #[cfg(feature = "zstd")]
+        assert_send_sync::<ZstdCompressor>();
+        #[cfg(feature = "zstd")]
+        assert_send_sync::<ZstdDictionaryCompressor>();
+        #[cfg(feature = "lz4")]
+        assert_send_sync::<Lz4Compressor>();
+        #[cfg(feature = "aead")]
+        assert_send_sync::<EncryptedCompressor<SnappyCompressor>>();
+    }
+
+    #[test]
+    fn test_concurrent_use_of_shared_instance() {
+        fn stress(compressor: Arc<dyn TCompressor + Send + Sync>) {
+            let handles: Vec<_> = (0..16)
+                .map(|thread| {
+                    let compressor = Arc::clone(&compressor);
+                    std::thread::spawn(move || {
+                        for i in 0..100 {
+                            let data =
+                                format!("thread {} payload {} ", thread, i).repeat(i * 7 + 1);
+                            let compressed = compressor.compress(data.as_bytes()).unwrap();
+                            let decompressed = compressor.decompress(&compressed).unwrap();
+                            assert_eq!(decompressed, data.as_bytes());
+                        }
+                    })
+                })
+                .collect();
+
+            for handle in handles {
+                handle.join().unwrap();
+            }
+        }
+
+        stress(Arc::new(SnappyCompressor));
+        for &algorithm in CompressionAlgorithm::all() {
+            stress(Arc::from(CompressorFactory::get_compressor(algorithm)));
+        }
+    }
+
+    #[test]
+    fn test_all_algorithms() {
+        let all = CompressionAlgorithm::all();
+        assert!(all.contains(&CompressionAlgorithm::Snappy));
+        assert!(all.windows(2).all(|pair| pair[0].tag() < pair[1].tag()));
+
+        let data = b"every algorithm ".repeat(50);
+        for &algorithm in all {
+            let compressor = CompressorFactory::get_compressor(algorithm);
+            let compressed = compressor.compress(&data).unwrap();
+            assert_eq!(compressor.decompress(&compressed).unwrap(), data);
+            assert_eq!(
+                algorithm.name().parse::<CompressionAlgorithm>().unwrap(),
+                algorithm
+            );
+        }
+    }
+
+    #[test]
+    fn test_compress_or_fallback() {
+        let data = b"fallback payload ".repeat(20);
+
+        let (compressed, used) = CompressorFactory::compress_or_fallback(
+            &data,
+            "snappy",
+            CompressionAlgorithm::Identity,
+        )
+        .unwrap();
+        assert_eq!(used, CompressionAlgorithm::Snappy);
+        assert_eq!(SnappyCompressor.decompress(&compressed).unwrap(), data);
+
+        assert!(matches!(
+            CompressorFactory::compress_or_fallback(&data, "brotli", CompressionAlgorithm::Snappy),
+            Err(CompressorError::ConfigError(_))
+        ));
+    }
+
+    #[cfg(not(feature = "zstd"))]
+    #[test]
+    fn test_unsupported_algorithm() {
+        assert!(matches!(
+            "zstd".parse::<CompressionAlgorithm>(),
+            Err(CompressorError::UnsupportedAlgorithm("zstd"))
+        ));
+        assert!(matches!(
+            CompressionAlgorithm::from_tag(3),
+            Err(CompressorError::UnsupportedAlgorithm("zstd"))
+        ));
+
+        let (compressed, used) = CompressorFactory::compress_or_fallback(
+            b"fallback",
+            "zstd",
+            CompressionAlgorithm::Snappy,
+        )
+        .unwrap();
+        assert_eq!(used, CompressionAlgorithm::Snappy);
+        assert_eq!(
+            SnappyCompressor.decompress(&compressed).unwrap(),
+            b"fallback"
+        );
+    }
+
+    #[test]
+    fn test_tag_conversions() {
+        for &algorithm in CompressionAlgorithm::all() {
+            let tag = u8::from(algorithm);
+            assert_eq!(tag, algorithm.tag());
+            assert_eq!(CompressionAlgorithm::try_from(tag).unwrap(), algorithm);
+        }
+
+        assert!(matches!(
+            CompressionAlgorithm::try_from(200),
+            Err(CompressorError::UnknownAlgorithm(200))
+        ));
+    }
+
+    #[test]
+    fn test_default_level() {
+        assert_eq!(CompressionAlgorithm::Identity.default_level(), None);
+        assert_eq!(CompressionAlgorithm::Snappy.default_level(), None);
+
+        #[cfg(any(feature = "gzip", feature = "zstd", feature = "lz4"))]
+        let data = b"default level ".repeat(500);
+        #[cfg(any(feature = "gzip", feature = "zstd", feature = "lz4"))]
+        let factory_output = |algorithm| {
+            CompressorFactory::get_compressor(algorithm)
+                .compress(&data)
+                .unwrap()
+        };
+
+        #[cfg(feature = "gzip")]
+        {
+            let level = CompressionAlgorithm::Gzip.default_level().unwrap();
+            assert_eq!(level, GzipCompressor::default().level());
+            assert_eq!(
+                factory_output(CompressionAlgorithm::Gzip),
+                GzipCompressor::new(level).compress(&data).unwrap()
+            );
+        }
+        #[cfg(feature = "zstd")]
+        {
+            let level = CompressionAlgorithm::Zstd.default_level().unwrap();
+            assert_eq!(level as i32, ZstdCompressor::default().level());
+            assert_eq!(
+                factory_output(CompressionAlgorithm::Zstd),
+                ZstdCompressor::new(level as i32).compress(&data).unwrap()
+            );
+        }
+        #[cfg(feature = "lz4")]
+        {
+            let level = CompressionAlgorithm::Lz4.default_level().unwrap();
+            assert_eq!(level, Lz4Compressor::default().level());
+            assert_eq!(
+                factory_output(CompressionAlgorithm::Lz4),
+                Lz4Compressor::new().compress(&data).unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn test_decompress_any() {
+        let data = b"migrated blob ".repeat(100);
+        let compressed = SnappyCompressor.compress(&data).unwrap();
+
+        // Identity would accept the bytes as they are, but detection tries snappy first
+        assert_eq!(
+            CompressorFactory::decompress_any(
+                &compressed,
+                &[CompressionAlgorithm::Identity, CompressionAlgorithm::Snappy]
+            )
+            .unwrap(),
+            data
+        );
+        assert_eq!(
+            CompressorFactory::decompress_any(
+                b"plain",
+                &[CompressionAlgorithm::Snappy, CompressionAlgorithm::Identity]
+            )
+            .unwrap(),
+            b"plain"
+        );
+
+        assert!(matches!(
+            CompressorFactory::decompress_any(b"plain", &[CompressionAlgorithm::Snappy]),
+            Err(CompressorError::WithAlgorithm {
+                algorithm: CompressionAlgorithm::Snappy,
+                ..
+            })
+        ));
+        assert!(matches!(
+            CompressorFactory::decompress_any(&compressed, &[]),
+            Err(CompressorError::ConfigError(_))
+        ));
+    }
+
+    #[cfg(all(feature = "gzip", feature = "zstd", feature = "lz4"))]
+    #[test]
+    fn test_decompress_any_wrong_candidates() {
+        let data = b"zstd blob among others ".repeat(100);
+        let compressed = ZstdCompressor::default().compress(&data).unwrap();
+
+        assert_eq!(
+            CompressorFactory::decompress_any(
+                &compressed,
+                &[
+                    CompressionAlgorithm::Snappy,
+                    CompressionAlgorithm::Gzip,
+                    CompressionAlgorithm::Lz4,
+                    CompressionAlgorithm::Zstd,
+                ]
+            )
+            .unwrap(),
+            data
+        );
+
+        // Without a magic prefix every candidate is tried in order
+        let raw = crate::snappy_raw_compress(&data).unwrap();
+        assert!(CompressorFactory::decompress_any(
+            &raw,
+            &[CompressionAlgorithm::Gzip, CompressionAlgorithm::Zstd]
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_compress_until_buffered_encoder() {
+        use std::time::Duration;
+
+        /// Buffered compressor whose single `compress` call outlasts short deadlines
+        struct SlowCompressor;
+
+        impl TCompressor for SlowCompressor {
+            fn compress(&self, value: &[u8]) -> Result<Vec<u8>, CompressorError> {
+                std::thread::sleep(Duration::from_millis(50));
+                Ok(value.to_vec())
+            }
+
+            fn decompress(&self, compressed: &[u8]) -> Result<Vec<u8>, CompressorError> {
+                Ok(compressed.to_vec())
+            }
+        }
+
+        let data = b"buffered until the end ".repeat(1_000);
+        assert!(matches!(
+            SlowCompressor.compress_until(&data, Instant::now() + Duration::from_millis(10)),
+            Err(CompressorError::DeadlineExceeded)
+        ));
+        assert_eq!(
+            SlowCompressor
+                .compress_until(&data, Instant::now() + Duration::from_secs(60))
+                .unwrap(),
+            data
+        );
+
+        let raw = SnappyRawCompressor
+            .compress_until(&data, Instant::now() + Duration::from_secs(60))
+            .unwrap();
+        assert_eq!(SnappyRawCompressor.decompress(&raw).unwrap(), data);
+    }
+
+    #[test]
+    fn test_buffered_stream_defaults() {
+        let data = [1u8, 2, 3, 4, 5].repeat(50_000);
+
+        let mut reported = Vec::new();
+        let compressed = ReverseCompressor
+            .compress_with_progress(&data, |processed| reported.push(processed))
+            .unwrap();
+        assert_eq!(*reported.last().unwrap(), data.len());
+
+        let mut decompressed = Vec::new();
+        ReverseCompressor
+            .decoder(Box::new(&compressed[..]))
+            .unwrap()
+            .read_to_end(&mut decompressed)
+            .unwrap();
+
+        assert_eq!(data, decompressed);
+    }
+
+    /// Rejects payloads marked as bad, standing in for a backend failure
+    struct PickyCompressor;
+
+    impl TCompressor for PickyCompressor {
+        fn compress(&self, value: &[u8]) -> Result<Vec<u8>, CompressorError> {
+            if value.starts_with(b"bad") {
+                return Err(CompressorError::CompressionError(
+                    "payload rejected".to_string(),
+                ));
+            }
+            Ok(value.to_vec())
+        }
+
+        fn decompress(&self, compressed: &[u8]) -> Result<Vec<u8>, CompressorError> {
+            Ok(compressed.to_vec())
+        }
+    }
+
+    #[test]
+    fn test_counted() {
+        let compressor = CompressorFactory::get_compressor(CompressionAlgorithm::Snappy);
+        let payloads = [
+            Vec::new(),
+            b"x".to_vec(),
+            b"tenant payload ".repeat(100),
+            (0..200_000).map(|i| (i % 253) as u8).collect(),
+        ];
+
+        for payload in payloads {
+            let (compressed, consumed) = compressor.compress_counted(&payload).unwrap();
+            assert_eq!(consumed, payload.len() as u64);
+            assert_eq!(compressed, compressor.compress(&payload).unwrap());
+
+            let (decompressed, consumed) = compressor.decompress_counted(&compressed).unwrap();
+            assert_eq!(consumed, compressed.len() as u64);
+            assert_eq!(decompressed, payload);
+        }
+    }
+
+    #[cfg(feature = "sha2")]
+    #[test]
+    fn test_compress_with_digest() {
+        use sha2::{Digest, Sha256};
+
+        let data = b"content addressed ".repeat(10_000);
+        let (compressed, digest) = SnappyCompressor.compress_with_digest(&data).unwrap();
+
+        assert_eq!(digest, <[u8; 32]>::from(Sha256::digest(&data)));
+        assert_eq!(SnappyCompressor.decompress(&compressed).unwrap(), data);
+
+        let (_, empty) = SnappyCompressor.compress_with_digest(b"").unwrap();
+        assert_eq!(empty, <[u8; 32]>::from(Sha256::digest(b"")));
+    }
+
+    #[test]
+    fn test_decompress_range() {
+        let data: Vec<u8> = (0..300_000).map(|i| (i % 251) as u8).collect();
+        let compressed = SnappyCompressor.compress(&data).unwrap();
+        let full = SnappyCompressor.decompress(&compressed).unwrap();
+
+        assert_eq!(
+            SnappyCompressor
+                .decompress_range(&compressed, 100_000, 70_000)
+                .unwrap(),
+            &full[100_000..170_000]
+        );
+        assert_eq!(
+            SnappyCompressor
+                .decompress_range(&compressed, 299_990, 10)
+                .unwrap(),
+            &full[299_990..]
+        );
+        assert!(SnappyCompressor
+            .decompress_range(&compressed, 300_000, 0)
+            .unwrap()
+            .is_empty());
+
+        assert!(matches!(
+            SnappyCompressor.decompress_range(&compressed, 299_990, 11),
+            Err(CompressorError::DecompressionError(_))
+        ));
+        assert!(SnappyCompressor
+            .decompress_range(&compressed, 400_000, 1)
+            .is_err());
+        assert!(matches!(
+            SnappyCompressor.decompress_range(&compressed, 300_001, 0),
+            Err(CompressorError::DecompressionError(_))
+        ));
+        assert!(SnappyCompressor
+            .decompress_range(&compressed, 1, usize::MAX)
+            .is_err());
+    }
+
+    #[test]
+    fn test_decompress_verify() {
+        let data = b"checksummed upstream ".repeat(100);
+        let crc = crc32fast::hash(&data);
+        let compressed = SnappyCompressor.compress(&data).unwrap();
+
+        assert_eq!(
+            SnappyCompressor
+                .decompress_verify(&compressed, crc)
+                .unwrap(),
+            data
+        );
+        assert!(matches!(
+            SnappyCompressor.decompress_verify(&compressed, crc ^ 1),
+            Err(CompressorError::IntegrityError(_))
+        ));
+    }
+
+    #[test]
+    fn test_compress_map() {
+        let mut items = HashMap::new();
+        items.insert("alpha", b"first payload".to_vec());
+        items.insert("beta", b"second payload".to_vec());
+
+        let compressed = SnappyCompressor.compress_map(&items).unwrap();
+        assert_eq!(compressed.len(), 2);
+        assert_eq!(
+            SnappyCompressor.decompress(&compressed["beta"]).unwrap(),
+            b"second payload"
+        );
+
+        items.insert("gamma", b"bad payload".to_vec());
+        let err = PickyCompressor.compress_map(&items).unwrap_err();
+        assert!(matches!(&err, CompressorError::KeyedError { key, .. } if key == "gamma"));
+        assert!(err.to_string().contains("gamma"));
+    }
+
+    #[test]
+    fn test_preferred_chunk_size() {
+        #[allow(unused_mut)]
+        let mut compressors = vec![
+            CompressorFactory::get_compressor(CompressionAlgorithm::Identity),
+            CompressorFactory::get_compressor(CompressionAlgorithm::Snappy),
+            Box::new(SnappyRawCompressor),
+            Box::new(ReverseCompressor),
+        ];
+        #[cfg(feature = "gzip")]
+        compressors.push(CompressorFactory::get_compressor(
+            CompressionAlgorithm::Gzip,
+        ));
+        #[cfg(feature = "zstd")]
+        compressors.push(CompressorFactory::get_compressor(
+            CompressionAlgorithm::Zstd,
+        ));
+        #[cfg(feature = "lz4")]
+        compressors.push(CompressorFactory::get_compressor(CompressionAlgorithm::Lz4));
+
+        for compressor in compressors {
+            let chunk_size = compressor.preferred_chunk_size();
+            assert!(chunk_size >= 4096);
+            assert!(chunk_size.is_power_of_two());
+        }
+    }
+}
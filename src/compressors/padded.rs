@@ -0,0 +1,100 @@
+use super::TCompressor;
+use crate::error::CompressorError;
+use alloc::{format, string::ToString, vec::Vec};
+
+/// Length of the little-endian compressed-length prefix
+const LENGTH_PREFIX_LEN: usize = 8;
+
+/// Wraps a compressor and zero-pads its output to a multiple of `block_size` bytes.
+///
+/// The output is the true compressed length (u64 little-endian), the compressed bytes,
+/// then padding. Decompression reads the length and ignores the padding.
+pub struct PaddedCompressor<C> {
+    inner: C,
+    block_size: usize,
+}
+
+impl<C: TCompressor> PaddedCompressor<C> {
+    /// # Panics
+    ///
+    /// Panics if `block_size` is zero.
+    pub fn new(inner: C, block_size: usize) -> Self {
+        assert!(block_size > 0, "block size must be non-zero");
+        PaddedCompressor { inner, block_size }
+    }
+
+    pub fn block_size(&self) -> usize {
+        self.block_size
+    }
+}
+
+impl<C: TCompressor> TCompressor for PaddedCompressor<C> {
+    fn compress(&self, value: &[u8]) -> Result<Vec<u8>, CompressorError> {
+        let compressed = self.inner.compress(value)?;
+
+        let unpadded_len = LENGTH_PREFIX_LEN + compressed.len();
+        let mut padded = Vec::with_capacity(unpadded_len.next_multiple_of(self.block_size));
+        padded.extend_from_slice(&(compressed.len() as u64).to_le_bytes());
+        padded.extend_from_slice(&compressed);
+        padded.resize(unpadded_len.next_multiple_of(self.block_size), 0);
+
+        Ok(padded)
+    }
+
+    fn decompress(&self, compressed: &[u8]) -> Result<Vec<u8>, CompressorError> {
+        let (prefix, rest) = compressed
+            .split_first_chunk::<LENGTH_PREFIX_LEN>()
+            .ok_or_else(|| {
+                CompressorError::DecompressionError("missing padded length prefix".to_string())
+            })?;
+
+        let len = usize::try_from(u64::from_le_bytes(*prefix))
+            .ok()
+            .filter(|&len| len <= rest.len())
+            .ok_or_else(|| {
+                CompressorError::DecompressionError(format!(
+                    "padded length {} exceeds the {} available bytes",
+                    u64::from_le_bytes(*prefix),
+                    rest.len()
+                ))
+            })?;
+
+        self.inner.decompress(&rest[..len])
+    }
+
+    fn overhead_bytes(&self) -> usize {
+        self.inner.overhead_bytes() + LENGTH_PREFIX_LEN
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compressors::SnappyRawCompressor;
+
+    #[test]
+    fn test_padded_round_trip() {
+        for block_size in [1, 16, 512, 4096] {
+            let compressor = PaddedCompressor::new(SnappyRawCompressor, block_size);
+
+            for len in [0, 1, 100, 10_000] {
+                let data = b"padding".repeat(len);
+                let padded = compressor.compress(&data).unwrap();
+
+                assert_eq!(padded.len() % block_size, 0);
+                assert_eq!(compressor.decompress(&padded).unwrap(), data);
+            }
+        }
+    }
+
+    #[test]
+    fn test_padded_corrupt_length() {
+        let compressor = PaddedCompressor::new(SnappyRawCompressor, 64);
+
+        let mut padded = compressor.compress(b"payload").unwrap();
+        padded[..LENGTH_PREFIX_LEN].copy_from_slice(&1000u64.to_le_bytes());
+
+        assert!(compressor.decompress(&padded).is_err());
+        assert!(compressor.decompress(&[0; 4]).is_err());
+    }
+}
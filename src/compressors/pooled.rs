@@ -0,0 +1,148 @@
+use super::TCompressor;
+use crate::error::CompressorError;
+use std::sync::{Mutex, PoisonError};
+
+/// Number of scratch buffers kept by default
+const DEFAULT_MAX_BUFFERS: usize = 8;
+
+/// Capacity an idle buffer is shrunk to by default, so one huge call does not pin its
+/// peak allocation in the pool
+const DEFAULT_MAX_BUFFER_CAPACITY: usize = 1024 * 1024;
+
+/// Wraps a compressor and runs `compress_into`/`decompress_into` on scratch buffers
+/// drawn from a shared free list instead of freshly allocated ones.
+///
+/// A fresh output buffer is reallocated every time it outgrows its capacity, about
+/// log2(output size) times per call. A pooled buffer has already grown to the size of
+/// earlier outputs, up to 1 MiB by default, so a call only allocates the exact-size
+/// result it returns. The saving
+/// depends on the inner compressor writing into the buffer it is given, as
+/// `SnappyCompressor` does; the default `compress_into` allocates internally anyway.
+pub struct PooledCompressor<C> {
+    inner: C,
+    max_buffers: usize,
+    max_buffer_capacity: usize,
+    buffers: Mutex<Vec<Vec<u8>>>,
+}
+
+impl<C: TCompressor> PooledCompressor<C> {
+    pub fn new(inner: C) -> Self {
+        PooledCompressor {
+            inner,
+            max_buffers: DEFAULT_MAX_BUFFERS,
+            max_buffer_capacity: DEFAULT_MAX_BUFFER_CAPACITY,
+            buffers: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Keeps at most `max_buffers` idle buffers; more are freed when returned
+    pub fn with_max_buffers(mut self, max_buffers: usize) -> Self {
+        self.max_buffers = max_buffers;
+        self
+    }
+
+    /// Shrinks buffers to at most `max_buffer_capacity` bytes when they are returned
+    pub fn with_max_buffer_capacity(mut self, max_buffer_capacity: usize) -> Self {
+        self.max_buffer_capacity = max_buffer_capacity;
+        self
+    }
+
+    /// Number of idle buffers currently in the pool
+    pub fn pooled_buffers(&self) -> usize {
+        self.lock_buffers().len()
+    }
+
+    pub fn into_inner(self) -> C {
+        self.inner
+    }
+
+    fn lock_buffers(&self) -> std::sync::MutexGuard<'_, Vec<Vec<u8>>> {
+        // The free list stays consistent even if a holder panicked
+        self.buffers.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn with_buffer(
+        &self,
+        run: impl FnOnce(&mut Vec<u8>) -> Result<(), CompressorError>,
+    ) -> Result<Vec<u8>, CompressorError> {
+        let mut buffer = self.lock_buffers().pop().unwrap_or_default();
+
+        let result = run(&mut buffer).map(|()| buffer.as_slice().to_vec());
+        buffer.clear();
+        buffer.shrink_to(self.max_buffer_capacity);
+
+        let mut buffers = self.lock_buffers();
+        if buffers.len() < self.max_buffers {
+            buffers.push(buffer);
+        }
+
+        result
+    }
+}
+
+impl<C: TCompressor> TCompressor for PooledCompressor<C> {
+    fn compress(&self, value: &[u8]) -> Result<Vec<u8>, CompressorError> {
+        self.with_buffer(|buffer| self.inner.compress_into(value, buffer))
+    }
+
+    fn decompress(&self, compressed: &[u8]) -> Result<Vec<u8>, CompressorError> {
+        self.with_buffer(|buffer| self.inner.decompress_into(compressed, buffer))
+    }
+
+    fn decompress_strict(&self, compressed: &[u8]) -> Result<Vec<u8>, CompressorError> {
+        self.inner.decompress_strict(compressed)
+    }
+
+    fn overhead_bytes(&self) -> usize {
+        self.inner.overhead_bytes()
+    }
+
+    fn preferred_chunk_size(&self) -> usize {
+        self.inner.preferred_chunk_size()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compressors::SnappyCompressor;
+
+    #[test]
+    fn test_pooled_round_trips() {
+        let compressor = PooledCompressor::new(SnappyCompressor).with_max_buffers(4);
+
+        std::thread::scope(|scope| {
+            for thread in 0..8 {
+                let compressor = &compressor;
+                scope.spawn(move || {
+                    for i in 0..200 {
+                        let data = format!("thread {} request {} ", thread, i).repeat(i + 1);
+                        let compressed = compressor.compress(data.as_bytes()).unwrap();
+                        assert_eq!(
+                            compressed,
+                            SnappyCompressor.compress(data.as_bytes()).unwrap()
+                        );
+                        assert_eq!(compressor.decompress(&compressed).unwrap(), data.as_bytes());
+                    }
+                });
+            }
+        });
+
+        assert!(compressor.pooled_buffers() >= 1);
+        assert!(compressor.pooled_buffers() <= 4);
+        assert!(compressor.decompress(b"not snappy").is_err());
+    }
+
+    #[test]
+    fn test_pooled_buffers_are_capped() {
+        let compressor = PooledCompressor::new(SnappyCompressor).with_max_buffer_capacity(4096);
+        let data = crate::noise(7, 64 * 1024);
+
+        let compressed = compressor.compress(&data).unwrap();
+        assert_eq!(compressor.decompress(&compressed).unwrap(), data);
+
+        let buffers = compressor.lock_buffers();
+        assert_eq!(buffers.len(), 1);
+        assert!(buffers[0].capacity() <= 4096);
+    }
+}
@@ -0,0 +1,169 @@
+use super::{TCompressor, DEFAULT_CHUNK_SIZE};
+use crate::error::CompressorError;
+use alloc::{format, string::ToString, vec::Vec};
+
+/// Length of a record header: u32 LE compressed length followed by its u32 LE CRC32
+const RECORD_HEADER_LEN: usize = 8;
+
+/// Wraps a compressor and splits its input into independently compressed chunks, each
+/// stored as a record of compressed length, CRC32 of the compressed bytes and the bytes.
+///
+/// A corrupt chunk only loses that chunk: `decompress_chunks` reports every chunk on its
+/// own. Corruption of a length field cannot be resynchronized past, so it ends the
+/// sequence with a single error.
+pub struct ResilientCompressor<C> {
+    inner: C,
+    chunk_size: usize,
+}
+
+impl<C: TCompressor> ResilientCompressor<C> {
+    /// Creates a compressor that chunks its input every 64 KiB
+    pub fn new(inner: C) -> Self {
+        ResilientCompressor {
+            inner,
+            chunk_size: DEFAULT_CHUNK_SIZE,
+        }
+    }
+
+    /// Sets the number of uncompressed bytes per chunk, at least 1
+    pub fn with_chunk_size(mut self, chunk_size: usize) -> Self {
+        self.chunk_size = chunk_size.max(1);
+        self
+    }
+
+    pub fn chunk_size(&self) -> usize {
+        self.chunk_size
+    }
+
+    pub fn into_inner(self) -> C {
+        self.inner
+    }
+
+    /// Decompresses every chunk separately, in order, so that callers can keep the chunks
+    /// that survived. Only a checksum or decompression failure of a chunk is reported for
+    /// that chunk alone.
+    pub fn decompress_chunks(&self, compressed: &[u8]) -> Vec<Result<Vec<u8>, CompressorError>> {
+        let mut chunks = Vec::new();
+        let mut remaining = compressed;
+
+        while !remaining.is_empty() {
+            let Some((len, expected, rest)) = read_record_header(remaining) else {
+                chunks.push(Err(CompressorError::IntegrityError(
+                    "truncated chunk record".to_string(),
+                )));
+                break;
+            };
+            let (chunk, rest) = rest.split_at(len);
+            remaining = rest;
+
+            let actual = crc32fast::hash(chunk);
+            chunks.push(if actual != expected {
+                Err(CompressorError::IntegrityError(format!(
+                    "chunk checksum mismatch: expected {:08x}, got {:08x}",
+                    expected, actual
+                )))
+            } else {
+                self.inner.decompress(chunk)
+            });
+        }
+
+        chunks
+    }
+}
+
+/// Splits off a record header, returning the chunk length, its checksum and the rest of
+/// the input, or `None` if the header or the chunk it announces is cut short
+fn read_record_header(data: &[u8]) -> Option<(usize, u32, &[u8])> {
+    if data.len() < RECORD_HEADER_LEN {
+        return None;
+    }
+
+    let (header, rest) = data.split_at(RECORD_HEADER_LEN);
+    let len = u32::from_le_bytes([header[0], header[1], header[2], header[3]]) as usize;
+    let checksum = u32::from_le_bytes([header[4], header[5], header[6], header[7]]);
+
+    (rest.len() >= len).then_some((len, checksum, rest))
+}
+
+impl<C: TCompressor> TCompressor for ResilientCompressor<C> {
+    fn compress(&self, value: &[u8]) -> Result<Vec<u8>, CompressorError> {
+        let mut compressed = Vec::new();
+
+        for chunk in value.chunks(self.chunk_size) {
+            let chunk = self.inner.compress(chunk)?;
+            let len = u32::try_from(chunk.len()).map_err(|_| {
+                CompressorError::CompressionError("compressed chunk too large".to_string())
+            })?;
+
+            compressed.extend_from_slice(&len.to_le_bytes());
+            compressed.extend_from_slice(&crc32fast::hash(&chunk).to_le_bytes());
+            compressed.extend_from_slice(&chunk);
+        }
+
+        Ok(compressed)
+    }
+
+    /// Decompresses all chunks, failing on the first one that does not decode
+    fn decompress(&self, compressed: &[u8]) -> Result<Vec<u8>, CompressorError> {
+        let mut decompressed = Vec::new();
+
+        for chunk in self.decompress_chunks(compressed) {
+            decompressed.extend_from_slice(&chunk?);
+        }
+
+        Ok(decompressed)
+    }
+
+    fn overhead_bytes(&self) -> usize {
+        RECORD_HEADER_LEN + self.inner.overhead_bytes()
+    }
+
+    fn preferred_chunk_size(&self) -> usize {
+        self.chunk_size
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compressors::IdentityCompressor;
+
+    #[test]
+    fn test_round_trip() {
+        let compressor = ResilientCompressor::new(IdentityCompressor).with_chunk_size(100);
+        let data: Vec<u8> = (0..1050).map(|i| (i % 256) as u8).collect();
+
+        let compressed = compressor.compress(&data).unwrap();
+        assert_eq!(compressed.len(), data.len() + 11 * RECORD_HEADER_LEN);
+        assert_eq!(compressor.decompress(&compressed).unwrap(), data);
+        assert!(compressor.compress(b"").unwrap().is_empty());
+        assert!(compressor.decompress_chunks(b"").is_empty());
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_corrupt_chunk_is_isolated() {
+        let compressor =
+            ResilientCompressor::new(crate::compressors::SnappyCompressor).with_chunk_size(1000);
+        let data = b"resilient archival ".repeat(150);
+
+        let mut compressed = compressor.compress(&data).unwrap();
+        let first_len = u32::from_le_bytes(compressed[..4].try_into().unwrap()) as usize;
+        // Flip a byte inside the second chunk's compressed bytes
+        let second = RECORD_HEADER_LEN + first_len + RECORD_HEADER_LEN;
+        compressed[second + 5] ^= 0xff;
+
+        let chunks = compressor.decompress_chunks(&compressed);
+        assert_eq!(chunks.len(), 3);
+        assert_eq!(chunks[0].as_ref().unwrap(), &data[..1000]);
+        assert!(matches!(chunks[1], Err(CompressorError::IntegrityError(_))));
+        assert_eq!(chunks[2].as_ref().unwrap(), &data[2000..]);
+
+        assert!(compressor.decompress(&compressed).is_err());
+
+        // A cut-short record ends the sequence
+        let chunks = compressor.decompress_chunks(&compressed[..compressed.len() - 1]);
+        assert_eq!(chunks.len(), 3);
+        assert!(chunks[2].is_err());
+    }
+}
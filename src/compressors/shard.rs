@@ -0,0 +1,88 @@
+//! Shard framing for `TCompressor::compress_sharded`.
+//!
+//! Every shard starts with its index and the total shard count, both u32 little-endian,
+//! so shards can be stored separately and reassembled in any order.
+
+use crate::error::CompressorError;
+use alloc::{format, string::ToString, vec::Vec};
+
+pub const SHARD_HEADER_LEN: usize = 8;
+
+/// Splits `compressed` into shards of at most `max_shard` bytes, headers included
+pub(crate) fn split(compressed: &[u8], max_shard: usize) -> Result<Vec<Vec<u8>>, CompressorError> {
+    if max_shard <= SHARD_HEADER_LEN {
+        return Err(CompressorError::ConfigError(format!(
+            "shards must be larger than their {}-byte header",
+            SHARD_HEADER_LEN
+        )));
+    }
+
+    let payload_len = max_shard - SHARD_HEADER_LEN;
+    // An empty payload still needs one shard to be reassembled from
+    let total = compressed.len().div_ceil(payload_len).max(1);
+    let total = u32::try_from(total).map_err(|_| {
+        CompressorError::ConfigError(format!("{} shards exceed the u32 shard count", total))
+    })?;
+
+    let shards = (0..total)
+        .map(|index| {
+            let start = index as usize * payload_len;
+            let end = (start + payload_len).min(compressed.len());
+
+            let mut shard = Vec::with_capacity(SHARD_HEADER_LEN + end - start);
+            shard.extend_from_slice(&index.to_le_bytes());
+            shard.extend_from_slice(&total.to_le_bytes());
+            shard.extend_from_slice(&compressed[start..end]);
+            shard
+        })
+        .collect();
+
+    Ok(shards)
+}
+
+/// Orders `shards` by their index and joins their payloads, checking that every shard
+/// of the same set is present exactly once
+pub(crate) fn reassemble(shards: &[Vec<u8>]) -> Result<Vec<u8>, CompressorError> {
+    let mut parsed = shards
+        .iter()
+        .map(|shard| {
+            if shard.len() < SHARD_HEADER_LEN {
+                return Err(CompressorError::IntegrityError(
+                    "shard is shorter than its header".to_string(),
+                ));
+            }
+            let index = u32::from_le_bytes([shard[0], shard[1], shard[2], shard[3]]);
+            let total = u32::from_le_bytes([shard[4], shard[5], shard[6], shard[7]]);
+            Ok((index, total, &shard[SHARD_HEADER_LEN..]))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    parsed.sort_unstable_by_key(|&(index, _, _)| index);
+
+    let total = parsed.first().map_or(0, |&(_, total, _)| total);
+    if parsed.len() != total as usize {
+        return Err(CompressorError::IntegrityError(format!(
+            "expected {} shards, got {}",
+            total,
+            parsed.len()
+        )));
+    }
+
+    let mut compressed =
+        Vec::with_capacity(parsed.iter().map(|(_, _, payload)| payload.len()).sum());
+    for (position, (index, shard_total, payload)) in parsed.into_iter().enumerate() {
+        if shard_total != total {
+            return Err(CompressorError::IntegrityError(
+                "shards belong to different sets".to_string(),
+            ));
+        }
+        if index as usize != position {
+            return Err(CompressorError::IntegrityError(format!(
+                "shard {} is missing or duplicated",
+                position
+            )));
+        }
+        compressed.extend_from_slice(payload);
+    }
+
+    Ok(compressed)
+}
@@ -1,28 +1,1086 @@
-use super::TCompressor;
+use super::{allocated, TCompressor, TEncoder};
 use crate::error::CompressorError;
-use std::io;
+use std::io::{self, Read, Write};
 
+/// Stream identifier chunk that starts every Snappy frame: type 0xff, length 6, then `sNaPpY`
+const STREAM_IDENTIFIER: &[u8] = b"\xff\x06\x00\x00sNaPpY";
+const STREAM_IDENTIFIER_LEN: usize = STREAM_IDENTIFIER.len();
+/// Size of a chunk header: type, 3-byte length and masked CRC32C
+const CHUNK_HEADER_LEN: usize = 8;
+/// Maximum uncompressed size of a single frame chunk
+const MAX_CHUNK_LEN: usize = 1 << 16;
+/// Most bytes one byte of a Snappy raw block can decode to: the densest element, a
+/// 3-byte copy, expands to 64 bytes
+const MAX_RAW_EXPANSION: usize = 22;
+
+/// Snappy frame format compressor.
+///
+/// Frames are split into chunks of at most 64 KiB, so inputs of any size, including
+/// beyond 4 GiB, compress into as many chunks as needed. Raw blocks (`body_only`,
+/// `SnappyRawCompressor`, `snappy_raw_compress`) hold a single length and fail with
+/// `CompressionError` for inputs of more than `u32::MAX` bytes instead of truncating.
 pub struct SnappyCompressor;
 
+impl SnappyCompressor {
+    /// Returns a compressor that ends frame chunks every `chunk_size` uncompressed bytes,
+    /// clamped to `1..=65536`.
+    ///
+    /// `snap` always fills chunks up to the 65536-byte maximum of the frame format and does
+    /// not expose a chunk size, so smaller chunks are produced by flushing the encoder at
+    /// each boundary. A chunk size of 65536 produces exactly the output of `SnappyCompressor`.
+    pub fn with_chunk_size(chunk_size: usize) -> ChunkedSnappyCompressor {
+        ChunkedSnappyCompressor {
+            chunk_size: chunk_size.clamp(1, MAX_CHUNK_LEN),
+        }
+    }
+
+    /// Returns a compressor that emits a bare Snappy raw block: the compressed body
+    /// without the frame's stream identifier, chunk headers and CRC32C checksums.
+    ///
+    /// Use it when an outer format already provides framing and integrity checks. Raw
+    /// blocks and frames are not interchangeable: `SnappyCompressor` rejects a raw block
+    /// because it lacks the stream identifier, and `SnappyBodyCompressor` rejects anything
+    /// that starts with one.
+    pub fn body_only() -> SnappyBodyCompressor {
+        SnappyBodyCompressor
+    }
+
+    /// Returns the byte length of the Snappy frame at the start of `data` without decompressing it.
+    ///
+    /// The frame ends at the next stream identifier (a concatenated frame), at the end of
+    /// `data`, or at the first bytes that cannot form a chunk of this frame. A chunk
+    /// header whose declared length runs past the end of `data` is a truncated frame and
+    /// fails with `DecompressionError`, so a returned length always covers whole chunks.
+    /// Trailing data that happens to look like a valid chunk header cannot be told apart
+    /// from the frame.
+    pub fn frame_len(&self, data: &[u8]) -> Result<usize, CompressorError> {
+        if !data.starts_with(STREAM_IDENTIFIER) {
+            return Err(CompressorError::DecompressionError(
+                "data does not start with a Snappy stream identifier".to_string(),
+            ));
+        }
+
+        let mut offset = STREAM_IDENTIFIER_LEN;
+        while let Some(header) = data.get(offset..offset + 4) {
+            let chunk_type = header[0];
+            let chunk_len =
+                usize::from(header[1]) | usize::from(header[2]) << 8 | usize::from(header[3]) << 16;
+
+            let belongs_to_frame = match chunk_type {
+                // Compressed and uncompressed data chunks carry at least a CRC32C
+                0x00 | 0x01 => chunk_len >= 4,
+                // Padding and reserved skippable chunks
+                0x80..=0xfe => true,
+                // Reserved unskippable chunks and the next frame's stream identifier
+                _ => false,
+            };
+
+            if !belongs_to_frame {
+                break;
+            }
+            if offset + 4 + chunk_len > data.len() {
+                return Err(CompressorError::DecompressionError(format!(
+                    "truncated Snappy frame: the chunk at offset {} declares {} bytes but {} remain",
+                    offset,
+                    chunk_len,
+                    data.len() - offset - 4
+                )));
+            }
+
+            offset += 4 + chunk_len;
+        }
+
+        Ok(offset)
+    }
+}
+
 impl TCompressor for SnappyCompressor {
-    fn compress(&self, mut value: &[u8]) -> Result<Vec<u8>, CompressorError> {
+    fn compress(&self, value: &[u8]) -> Result<Vec<u8>, CompressorError> {
         let mut compressed = Vec::new();
-        let mut encoder = snap::write::FrameEncoder::new(&mut compressed);
+        self.compress_into(value, &mut compressed)?;
+        Ok(compressed)
+    }
+
+    fn decompress(&self, compressed: &[u8]) -> Result<Vec<u8>, CompressorError> {
+        let mut decompressed = Vec::new();
+        self.decompress_into(compressed, &mut decompressed)?;
+        Ok(decompressed)
+    }
+
+    fn compress_into(&self, mut value: &[u8], output: &mut Vec<u8>) -> Result<(), CompressorError> {
+        let mut encoder = snap::write::FrameEncoder::new(output);
 
         io::copy(&mut value, &mut encoder)
             .map_err(|err| CompressorError::CompressionError(err.to_string()))?;
 
-        drop(encoder);
-        Ok(compressed)
+        encoder
+            .flush()
+            .map_err(|err| CompressorError::CompressionError(err.to_string()))
     }
 
-    fn decompress(&self, compressed: &[u8]) -> Result<Vec<u8>, CompressorError> {
+    fn decompress_into(
+        &self,
+        compressed: &[u8],
+        output: &mut Vec<u8>,
+    ) -> Result<(), CompressorError> {
         let mut reader = snap::read::FrameDecoder::new(compressed);
-        let mut decompressed = Vec::new();
 
-        io::copy(&mut reader, &mut decompressed)
+        io::copy(&mut reader, output)
             .map_err(|err| CompressorError::DecompressionError(err.to_string()))?;
 
-        Ok(decompressed)
+        Ok(())
+    }
+
+    fn decompress_strict(&self, compressed: &[u8]) -> Result<Vec<u8>, CompressorError> {
+        // Empty input is what compressing empty input produces
+        if compressed.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let frame_len = self.frame_len(compressed)?;
+        if frame_len < compressed.len() {
+            return Err(CompressorError::TrailingData(compressed.len() - frame_len));
+        }
+
+        self.decompress(compressed)
+    }
+
+    fn overhead_bytes(&self) -> usize {
+        STREAM_IDENTIFIER_LEN + CHUNK_HEADER_LEN
+    }
+
+    fn preferred_chunk_size(&self) -> usize {
+        MAX_CHUNK_LEN
+    }
+
+    fn decoder<'a>(
+        &self,
+        reader: Box<dyn Read + 'a>,
+    ) -> Result<Box<dyn Read + 'a>, CompressorError> {
+        Ok(Box::new(snap::read::FrameDecoder::new(reader)))
+    }
+
+    fn encoder<'a>(
+        &'a self,
+        writer: Box<dyn Write + 'a>,
+    ) -> Result<Box<dyn TEncoder + 'a>, CompressorError> {
+        Ok(Box::new(snap::write::FrameEncoder::new(writer)))
+    }
+}
+
+impl<W: Write> TEncoder for snap::write::FrameEncoder<W> {
+    fn finish(mut self: Box<Self>) -> Result<(), CompressorError> {
+        self.flush()
+            .map_err(|err| CompressorError::CompressionError(err.to_string()))
+    }
+}
+
+/// Decodes a series of Snappy frames, e.g. from one streaming source, reusing the frame
+/// decoder's chunk buffers and the output buffer across `decompress_next` calls instead of
+/// setting them up for every frame like `SnappyCompressor::decompress` does.
+pub struct SnappyDecoder {
+    decoder: snap::read::FrameDecoder<io::Cursor<Vec<u8>>>,
+    output: Vec<u8>,
+}
+
+impl SnappyDecoder {
+    pub fn new() -> Self {
+        SnappyDecoder {
+            decoder: snap::read::FrameDecoder::new(io::Cursor::new(Vec::new())),
+            output: Vec::new(),
+        }
+    }
+
+    /// Decompresses one frame, accepting the same input as `SnappyCompressor::decompress`.
+    /// The result borrows the decoder's buffer and is overwritten by the next call.
+    pub fn decompress_next(&mut self, frame: &[u8]) -> Result<&[u8], CompressorError> {
+        self.output.clear();
+        // The inner decoder only checks the stream identifier of the first frame it reads
+        if !frame.is_empty() && !frame.starts_with(STREAM_IDENTIFIER) {
+            return Err(CompressorError::DecompressionError(
+                "data does not start with a Snappy stream identifier".to_string(),
+            ));
+        }
+
+        let source = self.decoder.get_mut();
+        source.get_mut().clear();
+        source.get_mut().extend_from_slice(frame);
+        source.set_position(0);
+
+        if let Err(err) = self.decoder.read_to_end(&mut self.output) {
+            // A failed frame can leave part of a chunk buffered for the next one
+            self.decoder = snap::read::FrameDecoder::new(io::Cursor::new(Vec::new()));
+            return Err(CompressorError::DecompressionError(err.to_string()));
+        }
+        Ok(&self.output)
+    }
+}
+
+impl Default for SnappyDecoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Snappy frame compressor with a custom chunk size, see `SnappyCompressor::with_chunk_size`.
+/// Its output decodes with any Snappy frame decoder, whatever the chunk size.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChunkedSnappyCompressor {
+    chunk_size: usize,
+}
+
+impl ChunkedSnappyCompressor {
+    pub fn chunk_size(&self) -> usize {
+        self.chunk_size
+    }
+}
+
+impl TCompressor for ChunkedSnappyCompressor {
+    fn compress(&self, value: &[u8]) -> Result<Vec<u8>, CompressorError> {
+        let mut compressed = Vec::new();
+        let mut encoder = self.encoder(Box::new(&mut compressed))?;
+
+        encoder
+            .write_all(value)
+            .map_err(|err| CompressorError::CompressionError(err.to_string()))?;
+
+        encoder.finish()?;
+        Ok(compressed)
+    }
+
+    fn decompress(&self, compressed: &[u8]) -> Result<Vec<u8>, CompressorError> {
+        SnappyCompressor.decompress(compressed)
+    }
+
+    fn decompress_strict(&self, compressed: &[u8]) -> Result<Vec<u8>, CompressorError> {
+        SnappyCompressor.decompress_strict(compressed)
+    }
+
+    fn overhead_bytes(&self) -> usize {
+        SnappyCompressor.overhead_bytes()
+    }
+
+    fn preferred_chunk_size(&self) -> usize {
+        self.chunk_size
+    }
+
+    fn decoder<'a>(
+        &self,
+        reader: Box<dyn Read + 'a>,
+    ) -> Result<Box<dyn Read + 'a>, CompressorError> {
+        SnappyCompressor.decoder(reader)
+    }
+
+    fn encoder<'a>(
+        &'a self,
+        writer: Box<dyn Write + 'a>,
+    ) -> Result<Box<dyn TEncoder + 'a>, CompressorError> {
+        Ok(Box::new(ChunkingEncoder {
+            inner: snap::write::FrameEncoder::new(writer),
+            chunk_size: self.chunk_size,
+            pending: 0,
+        }))
+    }
+}
+
+/// Flushes the frame encoder every `chunk_size` bytes so each flush ends a chunk
+struct ChunkingEncoder<W: Write> {
+    inner: snap::write::FrameEncoder<W>,
+    chunk_size: usize,
+    /// Bytes written since the last chunk boundary
+    pending: usize,
+}
+
+impl<W: Write> Write for ChunkingEncoder<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let len = buf.len().min(self.chunk_size - self.pending);
+        let written = self.inner.write(&buf[..len])?;
+
+        self.pending += written;
+        if self.pending == self.chunk_size {
+            self.inner.flush()?;
+            self.pending = 0;
+        }
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+impl<W: Write> TEncoder for ChunkingEncoder<W> {
+    fn finish(self: Box<Self>) -> Result<(), CompressorError> {
+        Box::new(self.inner).finish()
+    }
+}
+
+/// Snappy compressor producing body-only raw blocks, see `SnappyCompressor::body_only`.
+///
+/// The block holds the whole input, so streaming through `encoder`/`decoder` buffers it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SnappyBodyCompressor;
+
+impl TCompressor for SnappyBodyCompressor {
+    fn compress(&self, value: &[u8]) -> Result<Vec<u8>, CompressorError> {
+        snappy_raw_compress(value)
+    }
+
+    fn decompress(&self, compressed: &[u8]) -> Result<Vec<u8>, CompressorError> {
+        if compressed.starts_with(STREAM_IDENTIFIER) {
+            return Err(CompressorError::DecompressionError(
+                "expected a Snappy raw block, found a Snappy frame".to_string(),
+            ));
+        }
+
+        snappy_raw_decompress_auto(compressed)
+    }
+
+    fn decompressed_len_hint(&self, compressed: &[u8]) -> Option<usize> {
+        if compressed.starts_with(STREAM_IDENTIFIER) {
+            return None;
+        }
+
+        raw_block_len(compressed).ok()
+    }
+
+    fn decompress_with_allocator<'a>(
+        &self,
+        compressed: &[u8],
+        mut alloc: impl FnMut(usize) -> &'a mut [u8],
+    ) -> Result<usize, CompressorError> {
+        if compressed.starts_with(STREAM_IDENTIFIER) {
+            return Err(CompressorError::DecompressionError(
+                "expected a Snappy raw block, found a Snappy frame".to_string(),
+            ));
+        }
+        let len = raw_block_len(compressed)?;
+
+        snap::raw::Decoder::new()
+            .decompress(compressed, allocated(alloc(len), len)?)
+            .map_err(|err| CompressorError::DecompressionError(err.to_string()))
+    }
+
+    fn overhead_bytes(&self) -> usize {
+        // Only the varint length prefix, one byte for inputs under 128 bytes
+        1
+    }
+
+    fn preferred_chunk_size(&self) -> usize {
+        MAX_CHUNK_LEN
+    }
+}
+
+/// Compresses `value` into a single Snappy raw block (no frame format)
+pub fn snappy_raw_compress(value: &[u8]) -> Result<Vec<u8>, CompressorError> {
+    snap::raw::Encoder::new()
+        .compress_vec(value)
+        .map_err(|err| CompressorError::CompressionError(err.to_string()))
+}
+
+/// Decompresses a Snappy raw block whose uncompressed size is `expected_len`.
+///
+/// A length more than `compressed` can decode to fails with `DecompressionError` before
+/// anything is allocated.
+pub fn snappy_raw_decompress(
+    compressed: &[u8],
+    expected_len: usize,
+) -> Result<Vec<u8>, CompressorError> {
+    check_raw_expansion(compressed, expected_len)?;
+    let mut decompressed = vec![0; expected_len];
+    let written = snap::raw::Decoder::new()
+        .decompress(compressed, &mut decompressed)
+        .map_err(|err| CompressorError::DecompressionError(err.to_string()))?;
+
+    if written != expected_len {
+        return Err(CompressorError::DecompressionError(format!(
+            "expected {} decompressed bytes, got {}",
+            expected_len, written
+        )));
+    }
+
+    Ok(decompressed)
+}
+
+/// Decompresses a Snappy raw block using the uncompressed length stored in its varint
+/// header, which is untrusted and checked like `snappy_raw_decompress` does
+pub fn snappy_raw_decompress_auto(compressed: &[u8]) -> Result<Vec<u8>, CompressorError> {
+    snappy_raw_decompress(compressed, raw_block_len(compressed)?)
+}
+
+/// Uncompressed length declared in a raw block's varint header, rejected if the block
+/// cannot decode to that many bytes
+fn raw_block_len(compressed: &[u8]) -> Result<usize, CompressorError> {
+    let len = snap::raw::decompress_len(compressed)
+        .map_err(|err| CompressorError::DecompressionError(err.to_string()))?;
+    check_raw_expansion(compressed, len)?;
+    Ok(len)
+}
+
+fn check_raw_expansion(compressed: &[u8], len: usize) -> Result<(), CompressorError> {
+    if len > compressed.len().saturating_mul(MAX_RAW_EXPANSION) {
+        return Err(CompressorError::DecompressionError(format!(
+            "Snappy raw block of {} bytes cannot decode to the {} bytes expected",
+            compressed.len(),
+            len
+        )));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_overhead_bytes() {
+        let compressor = SnappyCompressor;
+
+        // An empty input emits no chunks at all, so it never exceeds the overhead
+        assert!(compressor.compress(b"").unwrap().len() <= compressor.overhead_bytes());
+
+        // A single byte is stored as one uncompressed chunk: overhead + payload
+        let compressed = compressor.compress(b"a").unwrap();
+        assert_eq!(compressed.len(), compressor.overhead_bytes() + 1);
+    }
+
+    #[test]
+    fn test_framed_round_trip() {
+        let data = b"framed snappy body ".repeat(500);
+        let compressed = SnappyCompressor.compress(&data).unwrap();
+
+        assert!(compressed.starts_with(STREAM_IDENTIFIER));
+        assert_eq!(SnappyCompressor.decompress(&compressed).unwrap(), data);
+    }
+
+    #[test]
+    fn test_body_only_round_trip() {
+        let compressor = SnappyCompressor::body_only();
+        let data = b"body-only snappy block ".repeat(500);
+
+        let compressed = compressor.compress(&data).unwrap();
+        assert!(!compressed.starts_with(STREAM_IDENTIFIER));
+        assert_eq!(compressed, snappy_raw_compress(&data).unwrap());
+        assert_eq!(compressor.decompress(&compressed).unwrap(), data);
+
+        let empty = compressor.compress(b"").unwrap();
+        assert_eq!(empty.len(), compressor.overhead_bytes());
+        assert!(compressor.decompress(&empty).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_framing_mismatch_is_an_error() {
+        let data = b"mixed up framing ".repeat(100);
+        let body_only = SnappyCompressor::body_only();
+
+        let framed = SnappyCompressor.compress(&data).unwrap();
+        assert!(matches!(
+            body_only.decompress(&framed),
+            Err(CompressorError::DecompressionError(_))
+        ));
+
+        let raw = body_only.compress(&data).unwrap();
+        assert!(matches!(
+            SnappyCompressor.decompress(&raw),
+            Err(CompressorError::DecompressionError(_))
+        ));
+        assert!(SnappyCompressor
+            .decompress(&body_only.compress(b"").unwrap())
+            .is_err());
+    }
+
+    #[test]
+    fn test_frame_len_single_frame() {
+        let compressor = SnappyCompressor;
+        let compressed = compressor.compress(&vec![7u8; 200_000]).unwrap();
+
+        assert_eq!(compressor.frame_len(&compressed).unwrap(), compressed.len());
+    }
+
+    #[test]
+    fn test_frame_len_with_trailing_data() {
+        let compressor = SnappyCompressor;
+        let frame = compressor.compress(b"first frame").unwrap();
+
+        let mut concatenated = frame.clone();
+        concatenated.extend_from_slice(&compressor.compress(b"second frame").unwrap());
+        assert_eq!(compressor.frame_len(&concatenated).unwrap(), frame.len());
+
+        let mut trailing = frame.clone();
+        trailing.extend_from_slice(b"trailing bytes");
+        assert_eq!(compressor.frame_len(&trailing).unwrap(), frame.len());
+
+        assert!(compressor.frame_len(b"not a frame").is_err());
+    }
+
+    #[test]
+    fn test_frame_len_truncated() {
+        let compressor = SnappyCompressor;
+        let frame = compressor
+            .compress(&b"truncated frame ".repeat(10))
+            .unwrap();
+
+        assert!(matches!(
+            compressor.frame_len(&frame[..frame.len() - 1]),
+            Err(CompressorError::DecompressionError(message)) if message.starts_with("truncated")
+        ));
+        // Cut between chunks, the frame is complete
+        assert_eq!(
+            compressor
+                .frame_len(&frame[..STREAM_IDENTIFIER_LEN])
+                .unwrap(),
+            STREAM_IDENTIFIER_LEN
+        );
+    }
+
+    #[test]
+    fn test_compress_with_progress() {
+        let compressor = SnappyCompressor;
+        let data = vec![42u8; 300_000];
+
+        let mut reported = Vec::new();
+        let compressed = compressor
+            .compress_with_progress(&data, |processed| reported.push(processed))
+            .unwrap();
+
+        assert!(reported.len() > 1);
+        assert!(reported.windows(2).all(|pair| pair[0] < pair[1]));
+        assert_eq!(*reported.last().unwrap(), data.len());
+        assert_eq!(compressor.decompress(&compressed).unwrap(), data);
+    }
+
+    #[test]
+    fn test_compress_diagnostic() {
+        let compressor = SnappyCompressor;
+
+        let random = crate::noise(0x2545_f491_4f6c_dd1d, 65_536);
+
+        let diagnostic = compressor.compress_diagnostic(&random).unwrap();
+        assert!(diagnostic.expanded);
+        assert!(diagnostic.estimated_entropy > 7.9);
+        assert_eq!(diagnostic.overhead_bytes, compressor.overhead_bytes());
+
+        let diagnostic = compressor.compress_diagnostic(&[0u8; 65_536]).unwrap();
+        assert!(!diagnostic.expanded);
+        assert_eq!(diagnostic.estimated_entropy, 0.0);
+        assert_eq!(
+            compressor.decompress(&diagnostic.compressed).unwrap(),
+            vec![0u8; 65_536]
+        );
+    }
+
+    #[test]
+    fn test_estimate_ratio() {
+        let compressor = SnappyCompressor;
+        let text = b"routing decisions depend on compressibility ".repeat(1000);
+        let random = crate::noise(0x2545_f491_4f6c_dd1d, 65_536);
+
+        assert!(compressor.estimate_ratio(&text, None).unwrap() < 1.0);
+        assert!(compressor.estimate_ratio(&text, Some(4096)).unwrap() < 1.0);
+        assert!(compressor.estimate_ratio(&random, None).unwrap() >= 1.0);
+        assert!(compressor.estimate_ratio(&random, Some(4096)).unwrap() >= 1.0);
+        assert_eq!(compressor.estimate_ratio(&text, Some(0)).unwrap(), 1.0);
+    }
+
+    #[test]
+    fn test_reused_decoder() {
+        let frames = [
+            b"first frame ".repeat(10_000),
+            Vec::new(),
+            b"third".to_vec(),
+        ];
+
+        let mut decoder = SnappyDecoder::new();
+        for frame in &frames {
+            let compressed = SnappyCompressor.compress(frame).unwrap();
+            assert_eq!(decoder.decompress_next(&compressed).unwrap(), &frame[..]);
+        }
+
+        // A corrupt frame fails without affecting the next one
+        let compressed = SnappyCompressor.compress(&frames[0]).unwrap();
+        assert!(decoder
+            .decompress_next(&compressed[..compressed.len() / 2])
+            .is_err());
+        assert!(decoder
+            .decompress_next(&compressed[STREAM_IDENTIFIER_LEN..])
+            .is_err());
+        assert_eq!(
+            decoder.decompress_next(&compressed).unwrap(),
+            &frames[0][..]
+        );
+    }
+
+    #[test]
+    fn test_compress_cow() {
+        use crate::compressors::IdentityCompressor;
+        use std::borrow::Cow;
+
+        let text = "borrowed from the request buffer ".repeat(20);
+
+        let skipped = IdentityCompressor
+            .compress_cow_str(Cow::Borrowed(&text))
+            .unwrap();
+        assert!(matches!(&skipped, Cow::Borrowed(bytes) if bytes.as_ptr() == text.as_ptr()));
+        assert!(matches!(
+            IdentityCompressor.compress_cow(Cow::Borrowed(b"")).unwrap(),
+            Cow::Borrowed(b"")
+        ));
+
+        let compressed = SnappyCompressor
+            .compress_cow_str(Cow::Borrowed(&text))
+            .unwrap();
+        assert!(matches!(compressed, Cow::Owned(_)));
+        assert_eq!(
+            SnappyCompressor.decompress(&compressed).unwrap(),
+            text.as_bytes()
+        );
+        assert_eq!(
+            SnappyCompressor
+                .compress_cow_str(Cow::Owned(text.clone()))
+                .unwrap(),
+            compressed
+        );
+    }
+
+    #[test]
+    fn test_decompress_with_allocator() {
+        let records = [
+            br#"{"frame":1,"entities":[]}"#.repeat(40),
+            Vec::new(),
+            br#"{"frame":2,"entities":[7,8,9]}"#.repeat(25),
+        ];
+        let mut arena = vec![0u8; 4096];
+
+        fn check(compressor: &impl TCompressor, records: &[Vec<u8>], arena: &mut [u8]) {
+            let mut free: &mut [u8] = arena;
+            let mut ranges = Vec::new();
+            let mut used = 0;
+
+            for record in records {
+                let compressed = compressor.compress(record).unwrap();
+                let written = compressor
+                    .decompress_with_allocator(&compressed, |len| {
+                        let (chunk, rest) = std::mem::take(&mut free).split_at_mut(len);
+                        free = rest;
+                        chunk
+                    })
+                    .unwrap();
+                ranges.push(used..used + written);
+                used += written;
+            }
+
+            for (record, range) in records.iter().zip(ranges) {
+                assert_eq!(&arena[range], record.as_slice());
+            }
+        }
+
+        check(&SnappyCompressor::body_only(), &records, &mut arena);
+        check(&SnappyCompressor, &records, &mut arena);
+        check(&crate::IdentityCompressor, &records, &mut arena);
+
+        let compressed = SnappyCompressor::body_only().compress(&records[0]).unwrap();
+        assert_eq!(
+            SnappyCompressor::body_only().decompressed_len_hint(&compressed),
+            Some(records[0].len())
+        );
+        let mut short = Some(&mut arena[..8]);
+        assert!(matches!(
+            SnappyCompressor::body_only()
+                .decompress_with_allocator(&compressed, |_| short.take().unwrap()),
+            Err(CompressorError::DecompressionError(_))
+        ));
+    }
+
+    #[test]
+    fn test_decompress_lossy() {
+        let data: Vec<u8> = (0..20_000)
+            .flat_map(|line| format!("log line {}\n", line).into_bytes())
+            .collect();
+        let compressed = SnappyCompressor.compress(&data).unwrap();
+
+        let (recovered, error) = SnappyCompressor.decompress_lossy(&compressed);
+        assert_eq!(recovered, data);
+        assert!(error.is_none());
+
+        let (recovered, error) =
+            SnappyCompressor.decompress_lossy(&compressed[..compressed.len() / 2]);
+        assert!(!recovered.is_empty());
+        assert!(recovered.len() < data.len());
+        assert!(data.starts_with(&recovered));
+        assert!(matches!(
+            error,
+            Some(CompressorError::DecompressionError(_))
+        ));
+    }
+
+    #[test]
+    fn test_decompress_stream_checked() {
+        let data = b"checked stream ".repeat(20_000);
+        let compressed = SnappyCompressor.compress(&data).unwrap();
+        let crc = crc32fast::hash(&data);
+
+        let mut out = Vec::new();
+        SnappyCompressor
+            .decompress_stream_checked(&compressed, &mut out, crc)
+            .unwrap();
+        assert_eq!(out, data);
+
+        // The mismatch is only detected after the whole output was written
+        let mut out = Vec::new();
+        assert!(matches!(
+            SnappyCompressor.decompress_stream_checked(&compressed, &mut out, crc ^ 1),
+            Err(CompressorError::IntegrityError(_))
+        ));
+        assert_eq!(out, data);
+
+        assert!(matches!(
+            SnappyCompressor.decompress_stream_checked(b"not snappy", io::sink(), crc),
+            Err(CompressorError::DecompressionError(_))
+        ));
+    }
+
+    #[test]
+    fn test_decompress_arc() {
+        let data = b"shared cache entry ".repeat(100);
+        let compressed = SnappyCompressor.compress(&data).unwrap();
+
+        let shared = SnappyCompressor.decompress_arc(&compressed).unwrap();
+        assert_eq!(&shared[..], &data[..]);
+
+        let reader = std::sync::Arc::clone(&shared);
+        assert!(std::ptr::eq(shared.as_ptr(), reader.as_ptr()));
+    }
+
+    /// Reader yielding `remaining` bytes of a repeating pattern without holding them
+    struct PatternReader {
+        offset: u64,
+        remaining: u64,
+    }
+
+    fn pattern_byte(offset: u64) -> u8 {
+        (offset % 251) as u8
+    }
+
+    impl Read for PatternReader {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let len = buf
+                .len()
+                .min(self.remaining.try_into().unwrap_or(usize::MAX));
+            for byte in &mut buf[..len] {
+                *byte = pattern_byte(self.offset);
+                self.offset += 1;
+            }
+            self.remaining -= len as u64;
+            Ok(len)
+        }
+    }
+
+    /// Writer checking that it receives exactly the `PatternReader` bytes
+    #[derive(Default)]
+    struct PatternChecker {
+        offset: u64,
+    }
+
+    impl Write for PatternChecker {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            for &byte in buf {
+                assert_eq!(byte, pattern_byte(self.offset), "at offset {}", self.offset);
+                self.offset += 1;
+            }
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn check_large_round_trip(len: u64) {
+        use crate::compressors::CompressionAlgorithm;
+        use crate::stream::{compress_stream_with_stats, pipe_decompress};
+
+        let mut compressed = Vec::new();
+        let stats = compress_stream_with_stats(
+            PatternReader {
+                offset: 0,
+                remaining: len,
+            },
+            &mut compressed,
+            CompressionAlgorithm::Snappy,
+            |_| {},
+        )
+        .unwrap();
+        assert_eq!(stats.bytes_in, len);
+        assert!(stats.calls >= len.div_ceil(MAX_CHUNK_LEN as u64));
+
+        let mut checker = PatternChecker::default();
+        let decompressed =
+            pipe_decompress(&compressed[..], &mut checker, CompressionAlgorithm::Snappy).unwrap();
+        assert_eq!(decompressed, len);
+        assert_eq!(checker.offset, len);
+    }
+
+    #[test]
+    fn test_large_input_spans_chunks() {
+        check_large_round_trip(16 * MAX_CHUNK_LEN as u64 + 1);
+    }
+
+    #[test]
+    #[ignore] // Streams over 4 GiB, run with `cargo test --release -- --ignored`
+    fn test_input_beyond_u32_spans_chunks() {
+        check_large_round_trip(u64::from(u32::MAX) + 1);
+    }
+
+    #[test]
+    fn test_content_defined_round_trip() {
+        let compressor = SnappyCompressor;
+        let original: Vec<u8> = crate::noise(0x2545_f491_4f6c_dd1d, 50_000)
+            .into_iter()
+            .flat_map(|byte| [byte, byte, byte])
+            .collect();
+        let mut edited = original.clone();
+        edited.splice(10..10, *b"v2 header");
+
+        let before = compressor
+            .compress_content_defined(&original, 4096)
+            .unwrap();
+        let after = compressor.compress_content_defined(&edited, 4096).unwrap();
+        assert_eq!(
+            compressor.decompress_content_defined(&before).unwrap(),
+            original
+        );
+        assert_eq!(
+            compressor.decompress_content_defined(&after).unwrap(),
+            edited
+        );
+
+        let reused = after.iter().filter(|chunk| before.contains(chunk)).count();
+        assert!(reused + 2 >= after.len(), "{} of {}", reused, after.len());
+        assert!(compressor
+            .decompress_content_defined(&[])
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn test_sharded_round_trip() {
+        let compressor = SnappyCompressor;
+        let data = crate::noise(0x2545_f491_4f6c_dd1d, 100_000);
+
+        let shards = compressor.compress_sharded(&data, 4096).unwrap();
+        assert!(shards.len() > 20);
+        assert!(shards.iter().all(|shard| shard.len() <= 4096));
+
+        let mut shuffled = shards.clone();
+        shuffled.reverse();
+        shuffled.rotate_left(shards.len() / 3);
+        assert_eq!(compressor.decompress_sharded(&shuffled).unwrap(), data);
+
+        let missing = &shuffled[1..];
+        assert!(matches!(
+            compressor.decompress_sharded(missing),
+            Err(CompressorError::IntegrityError(_))
+        ));
+
+        let mut duplicated = shuffled[1..].to_vec();
+        duplicated.push(shuffled[2].clone());
+        assert!(matches!(
+            compressor.decompress_sharded(&duplicated),
+            Err(CompressorError::IntegrityError(_))
+        ));
+
+        assert!(matches!(
+            compressor.compress_sharded(&data, 8),
+            Err(CompressorError::ConfigError(_))
+        ));
+        let empty = compressor.compress_sharded(b"", 64).unwrap();
+        assert_eq!(compressor.decompress_sharded(&empty).unwrap(), b"");
+    }
+
+    #[test]
+    fn test_compress_until() {
+        use std::time::{Duration, Instant};
+
+        let compressor = SnappyCompressor;
+        let data = b"deadline bound ".repeat(10_000);
+
+        assert!(matches!(
+            compressor.compress_until(&data, Instant::now() - Duration::from_millis(1)),
+            Err(CompressorError::DeadlineExceeded)
+        ));
+
+        let compressed = compressor
+            .compress_until(&data, Instant::now() + Duration::from_secs(60))
+            .unwrap();
+        assert_eq!(compressor.decompress(&compressed).unwrap(), data);
+    }
+
+    #[test]
+    fn test_chunk_size() {
+        let data = b"medium sized message ".repeat(10_000);
+
+        let default = SnappyCompressor::with_chunk_size(MAX_CHUNK_LEN);
+        assert_eq!(
+            default.compress(&data).unwrap(),
+            SnappyCompressor.compress(&data).unwrap()
+        );
+
+        let small = SnappyCompressor::with_chunk_size(4096);
+        let compressed = small.compress(&data).unwrap();
+        assert_ne!(compressed, SnappyCompressor.compress(&data).unwrap());
+        assert_eq!(small.decompress(&compressed).unwrap(), data);
+        assert_eq!(SnappyCompressor.decompress(&compressed).unwrap(), data);
+
+        let mut chunks = 0;
+        let mut offset = STREAM_IDENTIFIER_LEN;
+        while offset < compressed.len() {
+            let header = &compressed[offset..offset + 4];
+            offset += 4
+                + (usize::from(header[1])
+                    | usize::from(header[2]) << 8
+                    | usize::from(header[3]) << 16);
+            chunks += 1;
+        }
+        assert_eq!(chunks, data.len().div_ceil(4096));
+
+        assert_eq!(SnappyCompressor::with_chunk_size(0).chunk_size(), 1);
+        assert_eq!(
+            SnappyCompressor::with_chunk_size(1 << 20).chunk_size(),
+            MAX_CHUNK_LEN
+        );
+    }
+
+    #[test]
+    fn test_validate() {
+        let compressor = SnappyCompressor;
+        let compressed = compressor
+            .compress(&b"validate me ".repeat(50_000))
+            .unwrap();
+
+        assert!(compressor.validate(&compressed).is_ok());
+        assert!(matches!(
+            compressor.validate(&compressed[..compressed.len() / 2]),
+            Err(CompressorError::DecompressionError(_))
+        ));
+    }
+
+    #[test]
+    fn test_decompress_strict() {
+        let compressor = SnappyCompressor;
+        let frame = compressor.compress(b"strict payload").unwrap();
+        assert_eq!(
+            compressor.decompress_strict(&frame).unwrap(),
+            b"strict payload"
+        );
+        assert!(compressor.decompress_strict(b"").unwrap().is_empty());
+
+        // A smuggled second frame is accepted by the lenient decoder only
+        let mut smuggled = frame.clone();
+        smuggled.extend_from_slice(&compressor.compress(b"smuggled").unwrap());
+        assert_eq!(
+            compressor.decompress(&smuggled).unwrap(),
+            b"strict payloadsmuggled"
+        );
+        assert!(matches!(
+            compressor.decompress_strict(&smuggled),
+            Err(CompressorError::TrailingData(len)) if len == smuggled.len() - frame.len()
+        ));
+
+        // A single stray byte is reported precisely instead of as a truncated chunk
+        let mut extra_byte = frame.clone();
+        extra_byte.push(0);
+        assert!(matches!(
+            compressor.decompress_strict(&extra_byte),
+            Err(CompressorError::TrailingData(1))
+        ));
+
+        // A frame cut inside its last chunk is truncated, not followed by trailing data
+        assert!(compressor.decompress(&frame[..frame.len() - 1]).is_err());
+        assert!(matches!(
+            compressor.decompress_strict(&frame[..frame.len() - 1]),
+            Err(CompressorError::DecompressionError(message)) if message.starts_with("truncated")
+        ));
+    }
+
+    #[cfg(feature = "bytes")]
+    #[test]
+    fn test_bytes_round_trip() {
+        let data = b"zero-copy network buffer ".repeat(100);
+        let compressor = SnappyCompressor;
+
+        let compressed = compressor
+            .compress_bytes(bytes::Bytes::from(data.clone()))
+            .unwrap();
+        assert_eq!(compressed, compressor.compress(&data).unwrap());
+
+        let decompressed = compressor.decompress_bytes(compressed).unwrap();
+        assert_eq!(decompressed, data);
+    }
+
+    #[test]
+    fn test_raw_round_trip() {
+        let data = b"raw snappy block raw snappy block raw snappy block".to_vec();
+
+        let compressed = snappy_raw_compress(&data).unwrap();
+        let decompressed = snappy_raw_decompress(&compressed, data.len()).unwrap();
+
+        assert_eq!(data, decompressed);
+    }
+
+    #[test]
+    fn test_raw_wrong_expected_len() {
+        let data = b"raw snappy block".to_vec();
+        let compressed = snappy_raw_compress(&data).unwrap();
+
+        assert!(matches!(
+            snappy_raw_decompress(&compressed, data.len() - 1),
+            Err(CompressorError::DecompressionError(_))
+        ));
+        assert!(matches!(
+            snappy_raw_decompress(&compressed, data.len() + 1),
+            Err(CompressorError::DecompressionError(_))
+        ));
+    }
+
+    #[test]
+    fn test_raw_oversized_declared_len() {
+        // A varint header declaring about 4 GiB followed by nothing
+        let bomb = [0xff, 0xff, 0xff, 0xff, 0x0f];
+
+        for result in [
+            snappy_raw_decompress_auto(&bomb),
+            snappy_raw_decompress(&bomb, u32::MAX as usize),
+            SnappyBodyCompressor.decompress(&bomb),
+        ] {
+            assert!(matches!(
+                result,
+                Err(CompressorError::DecompressionError(_))
+            ));
+        }
+        assert_eq!(SnappyBodyCompressor.decompressed_len_hint(&bomb), None);
+        assert!(SnappyBodyCompressor
+            .decompress_with_allocator(&bomb, |len| panic!("allocated {} bytes", len))
+            .is_err());
+
+        // The densest real blocks stay within the bound
+        let zeros = vec![0u8; 1 << 20];
+        let compressed = snappy_raw_compress(&zeros).unwrap();
+        assert_eq!(snappy_raw_decompress_auto(&compressed).unwrap(), zeros);
+    }
+
+    #[test]
+    fn test_raw_auto_len_round_trip() {
+        let data = b"raw snappy block without a known length ".repeat(64);
+        let compressed = snappy_raw_compress(&data).unwrap();
+
+        assert_eq!(snappy_raw_decompress_auto(&compressed).unwrap(), data);
+        assert_eq!(
+            snappy_raw_decompress_auto(&snappy_raw_compress(b"").unwrap()).unwrap(),
+            b""
+        );
+        assert!(matches!(
+            snappy_raw_decompress_auto(&[0xff; 6]),
+            Err(CompressorError::DecompressionError(_))
+        ));
     }
 }
@@ -0,0 +1,291 @@
+//! Snappy raw block format implemented on `core` + `alloc` only, so the byte-level
+//! API stays available in `no_std` builds.
+//!
+//! The output is a standard Snappy raw block (varint uncompressed length followed by
+//! literal and copy elements) and interoperates with any other Snappy implementation.
+
+use super::TCompressor;
+use crate::error::CompressorError;
+use alloc::{format, string::ToString, vec, vec::Vec};
+
+/// Shortest match worth encoding as a copy
+const MIN_MATCH_LEN: usize = 4;
+/// Longest copy a single element can express
+const MAX_COPY_LEN: usize = 64;
+/// Largest offset the encoder emits (2-byte offset copies)
+const MAX_OFFSET: usize = u16::MAX as usize;
+const HASH_TABLE_BITS: u32 = 14;
+
+const TAG_LITERAL: u8 = 0;
+const TAG_COPY_1: u8 = 1;
+const TAG_COPY_2: u8 = 2;
+
+pub struct SnappyRawCompressor;
+
+impl TCompressor for SnappyRawCompressor {
+    fn compress(&self, value: &[u8]) -> Result<Vec<u8>, CompressorError> {
+        let len = u32::try_from(value.len()).map_err(|_| {
+            CompressorError::CompressionError(format!(
+                "input of {} bytes exceeds the Snappy raw block limit",
+                value.len()
+            ))
+        })?;
+
+        let mut compressed = Vec::with_capacity(value.len() + value.len() / 6 + 32);
+        write_varint(&mut compressed, len);
+        encode(value, &mut compressed);
+
+        Ok(compressed)
+    }
+
+    fn decompress(&self, compressed: &[u8]) -> Result<Vec<u8>, CompressorError> {
+        decode(compressed)
+    }
+
+    fn decompressed_len_hint(&self, compressed: &[u8]) -> Option<usize> {
+        read_varint(compressed).ok().map(|(len, _)| len as usize)
+    }
+
+    fn overhead_bytes(&self) -> usize {
+        // Only the varint length prefix, one byte for inputs under 128 bytes
+        1
+    }
+}
+
+fn write_varint(output: &mut Vec<u8>, mut value: u32) {
+    while value >= 0x80 {
+        output.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    output.push(value as u8);
+}
+
+fn read_varint(input: &[u8]) -> Result<(u32, usize), CompressorError> {
+    let mut value = 0u32;
+    for (index, &byte) in input.iter().take(5).enumerate() {
+        value |= u32::from(byte & 0x7f) << (7 * index);
+        if byte & 0x80 == 0 {
+            return Ok((value, index + 1));
+        }
+    }
+
+    Err(CompressorError::DecompressionError(
+        "invalid Snappy length header".to_string(),
+    ))
+}
+
+fn hash(bytes: &[u8]) -> usize {
+    let key = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+    (key.wrapping_mul(0x1e35_a7bd) >> (32 - HASH_TABLE_BITS)) as usize
+}
+
+/// Greedy single-pass encoder using a hash table of the last position of each 4-byte sequence
+fn encode(input: &[u8], output: &mut Vec<u8>) {
+    // Positions are stored off by one so that zero means "empty"
+    let mut table = vec![0usize; 1 << HASH_TABLE_BITS];
+    let mut literal_start = 0;
+    let mut pos = 0;
+
+    while pos + MIN_MATCH_LEN <= input.len() {
+        let slot = hash(&input[pos..]);
+        let candidate = table[slot];
+        table[slot] = pos + 1;
+
+        if let Some(candidate) = candidate.checked_sub(1) {
+            if pos - candidate <= MAX_OFFSET
+                && input[candidate..candidate + MIN_MATCH_LEN] == input[pos..pos + MIN_MATCH_LEN]
+            {
+                let mut len = MIN_MATCH_LEN;
+                while pos + len < input.len() && input[candidate + len] == input[pos + len] {
+                    len += 1;
+                }
+
+                emit_literal(output, &input[literal_start..pos]);
+                emit_copy(output, pos - candidate, len);
+                pos += len;
+                literal_start = pos;
+                continue;
+            }
+        }
+
+        pos += 1;
+    }
+
+    emit_literal(output, &input[literal_start..]);
+}
+
+fn emit_literal(output: &mut Vec<u8>, literal: &[u8]) {
+    if literal.is_empty() {
+        return;
+    }
+
+    let n = literal.len() - 1;
+    if n < 60 {
+        output.push((n as u8) << 2 | TAG_LITERAL);
+    } else {
+        let extra = (usize::BITS - n.leading_zeros()).div_ceil(8) as usize;
+        output.push((59 + extra as u8) << 2 | TAG_LITERAL);
+        output.extend_from_slice(&n.to_le_bytes()[..extra]);
+    }
+    output.extend_from_slice(literal);
+}
+
+fn emit_copy(output: &mut Vec<u8>, offset: usize, mut len: usize) {
+    while len > 0 {
+        // Never leave a remainder shorter than the minimum match
+        let chunk = match len {
+            len if len >= MAX_COPY_LEN + MIN_MATCH_LEN => MAX_COPY_LEN,
+            len if len > MAX_COPY_LEN => len - MIN_MATCH_LEN,
+            len => len,
+        };
+
+        output.push(((chunk - 1) as u8) << 2 | TAG_COPY_2);
+        output.extend_from_slice(&(offset as u16).to_le_bytes());
+        len -= chunk;
+    }
+}
+
+fn truncated() -> CompressorError {
+    CompressorError::DecompressionError("truncated Snappy raw block".to_string())
+}
+
+fn decode(input: &[u8]) -> Result<Vec<u8>, CompressorError> {
+    let (expected_len, mut pos) = read_varint(input)?;
+    let expected_len = expected_len as usize;
+    // The declared length is untrusted, so only preallocate what the input could plausibly produce
+    let mut output = Vec::with_capacity(expected_len.min(input.len().saturating_mul(32)));
+
+    while pos < input.len() {
+        let tag = input[pos];
+        pos += 1;
+
+        let (len, offset) = match tag & 0b11 {
+            TAG_LITERAL => {
+                let mut len = usize::from(tag >> 2);
+                if len >= 60 {
+                    let extra = len - 59;
+                    let bytes = input.get(pos..pos + extra).ok_or_else(truncated)?;
+                    len = bytes
+                        .iter()
+                        .rev()
+                        .fold(0, |acc, &byte| acc << 8 | usize::from(byte));
+                    pos += extra;
+                }
+                let literal = input.get(pos..pos + len + 1).ok_or_else(truncated)?;
+                if output.len() + literal.len() > expected_len {
+                    return Err(CompressorError::DecompressionError(
+                        "Snappy raw block exceeds its declared length".to_string(),
+                    ));
+                }
+                output.extend_from_slice(literal);
+                pos += literal.len();
+                continue;
+            }
+            TAG_COPY_1 => {
+                let byte = *input.get(pos).ok_or_else(truncated)?;
+                pos += 1;
+                (
+                    usize::from(tag >> 2 & 0b111) + 4,
+                    usize::from(tag >> 5) << 8 | usize::from(byte),
+                )
+            }
+            TAG_COPY_2 => {
+                let bytes = input.get(pos..pos + 2).ok_or_else(truncated)?;
+                pos += 2;
+                (
+                    usize::from(tag >> 2) + 1,
+                    usize::from(u16::from_le_bytes([bytes[0], bytes[1]])),
+                )
+            }
+            _ => {
+                let bytes = input.get(pos..pos + 4).ok_or_else(truncated)?;
+                pos += 4;
+                (
+                    usize::from(tag >> 2) + 1,
+                    u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as usize,
+                )
+            }
+        };
+
+        if offset == 0 || offset > output.len() {
+            return Err(CompressorError::DecompressionError(format!(
+                "invalid Snappy copy offset {} at output position {}",
+                offset,
+                output.len()
+            )));
+        }
+        if output.len() + len > expected_len {
+            return Err(CompressorError::DecompressionError(
+                "Snappy raw block exceeds its declared length".to_string(),
+            ));
+        }
+
+        // Copies may overlap their own output, so they are applied byte by byte
+        let start = output.len() - offset;
+        for index in start..start + len {
+            output.push(output[index]);
+        }
+    }
+
+    if output.len() != expected_len {
+        return Err(CompressorError::DecompressionError(format!(
+            "expected {} decompressed bytes, got {}",
+            expected_len,
+            output.len()
+        )));
+    }
+
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn samples() -> Vec<Vec<u8>> {
+        vec![
+            Vec::new(),
+            b"a".to_vec(),
+            b"abcdabcdabcdabcdabcd".to_vec(),
+            vec![0u8; 100_000],
+            (0..70_000u32).map(|i| (i * 7 % 251) as u8).collect(),
+            b"The quick brown fox jumps over the lazy dog. ".repeat(500),
+        ]
+    }
+
+    #[test]
+    fn test_round_trip() {
+        for sample in samples() {
+            let compressed = SnappyRawCompressor.compress(&sample).unwrap();
+            assert_eq!(SnappyRawCompressor.decompress(&compressed).unwrap(), sample);
+        }
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_interop_with_snap() {
+        for sample in samples() {
+            let ours = SnappyRawCompressor.compress(&sample).unwrap();
+            assert_eq!(
+                snap::raw::Decoder::new().decompress_vec(&ours).unwrap(),
+                sample
+            );
+
+            let theirs = snap::raw::Encoder::new().compress_vec(&sample).unwrap();
+            assert_eq!(SnappyRawCompressor.decompress(&theirs).unwrap(), sample);
+        }
+    }
+
+    #[test]
+    fn test_corrupt_input() {
+        let compressed = SnappyRawCompressor
+            .compress(&b"hello hello hello hello".repeat(4))
+            .unwrap();
+
+        assert!(SnappyRawCompressor
+            .decompress(&compressed[..compressed.len() - 1])
+            .is_err());
+        assert!(SnappyRawCompressor.decompress(&[0x05, 0x09, 0x00]).is_err());
+        assert!(SnappyRawCompressor.decompress(&[0xff; 6]).is_err());
+    }
+}
@@ -0,0 +1,432 @@
+use super::{TCompressor, TEncoder};
+use crate::error::CompressorError;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::path::Path;
+use std::sync::Arc;
+use zstd::dict::{DecoderDictionary, EncoderDictionary};
+
+/// Magic number starting every dictionary produced by zstd's trainer (0xEC30A437, LE)
+const DICTIONARY_MAGIC: &[u8; 4] = b"\x37\xa4\x30\xec";
+
+/// Zstandard compressor.
+///
+/// Compression can run on `workers` background threads (zstd's `nbWorkers`); the output
+/// is a regular zstd frame and decompresses single-threaded like any other.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ZstdCompressor {
+    level: i32,
+    workers: u32,
+}
+
+impl ZstdCompressor {
+    pub fn new(level: i32) -> Self {
+        ZstdCompressor { level, workers: 0 }
+    }
+
+    /// Compresses on `workers` background threads; 0 compresses on the calling thread
+    pub fn with_workers(mut self, workers: u32) -> Self {
+        self.workers = workers;
+        self
+    }
+
+    pub fn level(&self) -> i32 {
+        self.level
+    }
+
+    pub fn workers(&self) -> u32 {
+        self.workers
+    }
+
+    fn new_encoder<W: Write>(&self, writer: W) -> io::Result<zstd::stream::Encoder<'static, W>> {
+        let mut encoder = zstd::stream::Encoder::new(writer, self.level)?;
+        if self.workers > 0 {
+            encoder.multithread(self.workers)?;
+        }
+
+        Ok(encoder)
+    }
+}
+
+impl ZstdCompressor {
+    /// Loads the zstd dictionary at `path` and returns a compressor using it at the
+    /// default level, see `ZstdDictionaryCompressor`
+    pub fn from_dictionary_file(path: &Path) -> Result<ZstdDictionaryCompressor, CompressorError> {
+        let dictionary = std::fs::read(path)
+            .map_err(|err| CompressorError::IoError(format!("{}: {}", path.display(), err)))?;
+
+        ZstdDictionaryCompressor::new(&dictionary, zstd::DEFAULT_COMPRESSION_LEVEL).map_err(|err| {
+            match err {
+                CompressorError::ConfigError(message) => {
+                    CompressorError::ConfigError(format!("{}: {}", path.display(), message))
+                }
+                err => err,
+            }
+        })
+    }
+}
+
+impl Default for ZstdCompressor {
+    fn default() -> Self {
+        ZstdCompressor::new(zstd::DEFAULT_COMPRESSION_LEVEL)
+    }
+}
+
+impl TCompressor for ZstdCompressor {
+    fn compress(&self, mut value: &[u8]) -> Result<Vec<u8>, CompressorError> {
+        let mut encoder = self
+            .new_encoder(Vec::new())
+            .map_err(|err| CompressorError::CompressionError(err.to_string()))?;
+
+        io::copy(&mut value, &mut encoder)
+            .and_then(|_| encoder.finish())
+            .map_err(|err| CompressorError::CompressionError(err.to_string()))
+    }
+
+    fn decompress(&self, compressed: &[u8]) -> Result<Vec<u8>, CompressorError> {
+        zstd::stream::decode_all(compressed)
+            .map_err(|err| CompressorError::DecompressionError(err.to_string()))
+    }
+
+    fn decompress_strict(&self, compressed: &[u8]) -> Result<Vec<u8>, CompressorError> {
+        let frame_len =
+            zstd::zstd_safe::find_frame_compressed_size(compressed).map_err(|code| {
+                CompressorError::DecompressionError(
+                    zstd::zstd_safe::get_error_name(code).to_string(),
+                )
+            })?;
+
+        if frame_len < compressed.len() {
+            return Err(CompressorError::TrailingData(compressed.len() - frame_len));
+        }
+
+        self.decompress(compressed)
+    }
+
+    fn overhead_bytes(&self) -> usize {
+        // Magic number, frame header with content size, and one block header
+        4 + 2 + 3
+    }
+
+    fn preferred_chunk_size(&self) -> usize {
+        // ZSTD_CStreamInSize, one full block
+        zstd::zstd_safe::CCtx::in_size()
+    }
+
+    fn decoder<'a>(
+        &self,
+        reader: Box<dyn Read + 'a>,
+    ) -> Result<Box<dyn Read + 'a>, CompressorError> {
+        let decoder = zstd::stream::Decoder::new(reader)
+            .map_err(|err| CompressorError::DecompressionError(err.to_string()))?;
+
+        Ok(Box::new(decoder))
+    }
+
+    fn encoder<'a>(
+        &'a self,
+        writer: Box<dyn Write + 'a>,
+    ) -> Result<Box<dyn TEncoder + 'a>, CompressorError> {
+        let encoder = self
+            .new_encoder(writer)
+            .map_err(|err| CompressorError::CompressionError(err.to_string()))?;
+
+        Ok(Box::new(encoder))
+    }
+}
+
+/// Zstandard compressor using a pre-trained dictionary, e.g. one produced by
+/// `DictionaryTrainer`.
+///
+/// The dictionary is digested once when the compressor is created and reused by every
+/// call. Its output can only be decompressed with the same dictionary; decompressing it
+/// without, or with another dictionary, fails.
+///
+/// The compressor is `Send + Sync` and clones share the digested dictionaries through an
+/// `Arc`, so threads can use one instance, or a clone each, without preparing the
+/// dictionary again.
+#[derive(Clone)]
+pub struct ZstdDictionaryCompressor {
+    dictionary: Arc<[u8]>,
+    level: i32,
+    encoder_dictionary: Arc<EncoderDictionary<'static>>,
+    decoder_dictionary: Arc<DecoderDictionary<'static>>,
+}
+
+impl ZstdDictionaryCompressor {
+    /// Creates a compressor from a trained dictionary, failing with `ConfigError` if
+    /// `dictionary` does not start with zstd's dictionary magic number
+    pub fn new(dictionary: &[u8], level: i32) -> Result<Self, CompressorError> {
+        if !dictionary.starts_with(DICTIONARY_MAGIC) {
+            return Err(CompressorError::ConfigError(
+                "not a zstd dictionary".to_string(),
+            ));
+        }
+
+        Ok(ZstdDictionaryCompressor {
+            dictionary: Arc::from(dictionary),
+            level,
+            encoder_dictionary: Arc::new(EncoderDictionary::copy(dictionary, level)),
+            decoder_dictionary: Arc::new(DecoderDictionary::copy(dictionary)),
+        })
+    }
+
+    pub fn dictionary(&self) -> &[u8] {
+        &self.dictionary
+    }
+
+    pub fn level(&self) -> i32 {
+        self.level
+    }
+}
+
+/// Decoder that keeps its digested dictionary alive. zstd only ties a decoder to the
+/// dictionary's data, not to the `DecoderDictionary` itself, so the decoder returned by
+/// `ZstdDictionaryCompressor::decoder` holds its own reference; fields drop in order,
+/// the decoder first.
+struct PreparedDecoder<R: BufRead> {
+    decoder: zstd::stream::Decoder<'static, R>,
+    _dictionary: Arc<DecoderDictionary<'static>>,
+}
+
+impl<R: BufRead> Read for PreparedDecoder<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.decoder.read(buf)
+    }
+}
+
+impl TCompressor for ZstdDictionaryCompressor {
+    fn compress(&self, mut value: &[u8]) -> Result<Vec<u8>, CompressorError> {
+        let mut encoder =
+            zstd::stream::Encoder::with_prepared_dictionary(Vec::new(), &self.encoder_dictionary)
+                .map_err(|err| CompressorError::CompressionError(err.to_string()))?;
+
+        io::copy(&mut value, &mut encoder)
+            .and_then(|_| encoder.finish())
+            .map_err(|err| CompressorError::CompressionError(err.to_string()))
+    }
+
+    fn decompress(&self, compressed: &[u8]) -> Result<Vec<u8>, CompressorError> {
+        let mut decoder =
+            zstd::stream::Decoder::with_prepared_dictionary(compressed, &self.decoder_dictionary)
+                .map_err(|err| CompressorError::DecompressionError(err.to_string()))?;
+        let mut decompressed = Vec::new();
+
+        decoder
+            .read_to_end(&mut decompressed)
+            .map_err(|err| CompressorError::DecompressionError(err.to_string()))?;
+
+        Ok(decompressed)
+    }
+
+    fn preferred_chunk_size(&self) -> usize {
+        zstd::zstd_safe::CCtx::in_size()
+    }
+
+    fn decoder<'a>(
+        &self,
+        reader: Box<dyn Read + 'a>,
+    ) -> Result<Box<dyn Read + 'a>, CompressorError> {
+        let decoder = zstd::stream::Decoder::with_prepared_dictionary(
+            BufReader::new(reader),
+            &self.decoder_dictionary,
+        )
+        .map_err(|err| CompressorError::DecompressionError(err.to_string()))?;
+
+        Ok(Box::new(PreparedDecoder {
+            decoder,
+            _dictionary: Arc::clone(&self.decoder_dictionary),
+        }))
+    }
+
+    fn encoder<'a>(
+        &'a self,
+        writer: Box<dyn Write + 'a>,
+    ) -> Result<Box<dyn TEncoder + 'a>, CompressorError> {
+        let encoder =
+            zstd::stream::Encoder::with_prepared_dictionary(writer, &self.encoder_dictionary)
+                .map_err(|err| CompressorError::CompressionError(err.to_string()))?;
+
+        Ok(Box::new(encoder))
+    }
+}
+
+impl<W: Write> TEncoder for zstd::stream::Encoder<'_, W> {
+    fn finish(self: Box<Self>) -> Result<(), CompressorError> {
+        (*self)
+            .finish()
+            .map(drop)
+            .map_err(|err| CompressorError::CompressionError(err.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let compressor = ZstdCompressor::default();
+        let data = b"zstd round trip ".repeat(1000);
+
+        let compressed = compressor.compress(&data).unwrap();
+        assert!(compressed.len() < data.len());
+        assert_eq!(compressor.decompress(&compressed).unwrap(), data);
+    }
+
+    #[test]
+    fn test_overhead_bytes() {
+        let compressor = ZstdCompressor::default();
+
+        assert_eq!(
+            compressor.compress(b"").unwrap().len(),
+            compressor.overhead_bytes()
+        );
+    }
+
+    #[test]
+    fn test_decompress_strict() {
+        let compressor = ZstdCompressor::default();
+        let frame = compressor.compress(b"strict payload").unwrap();
+        assert_eq!(
+            compressor.decompress_strict(&frame).unwrap(),
+            b"strict payload"
+        );
+
+        let mut smuggled = frame.clone();
+        smuggled.extend_from_slice(&compressor.compress(b"smuggled").unwrap());
+        assert!(compressor.decompress(&smuggled).is_ok());
+        assert!(matches!(
+            compressor.decompress_strict(&smuggled),
+            Err(CompressorError::TrailingData(len)) if len == smuggled.len() - frame.len()
+        ));
+
+        let mut extra_byte = frame;
+        extra_byte.push(0);
+        assert!(matches!(
+            compressor.decompress_strict(&extra_byte),
+            Err(CompressorError::TrailingData(1))
+        ));
+    }
+
+    fn record(id: usize) -> Vec<u8> {
+        format!(
+            r#"{{"id":{},"kind":"event","source":"service-{}","payload":{{"status":"ok","attempt":{}}}}}"#,
+            id,
+            id % 13,
+            id % 5
+        )
+        .into_bytes()
+    }
+
+    #[test]
+    fn test_dictionary_file_round_trip() {
+        let samples: Vec<Vec<u8>> = (0..500).map(record).collect();
+        let dictionary = zstd::dict::from_samples(&samples, 2048).unwrap();
+        let file = tempfile::NamedTempFile::new().unwrap();
+        std::fs::write(file.path(), &dictionary).unwrap();
+
+        let compressor = ZstdCompressor::from_dictionary_file(file.path()).unwrap();
+        assert_eq!(compressor.dictionary(), &dictionary[..]);
+
+        let data = record(1234);
+        let compressed = compressor.compress(&data).unwrap();
+        assert!(compressed.len() < ZstdCompressor::default().compress(&data).unwrap().len());
+        assert_eq!(compressor.decompress(&compressed).unwrap(), data);
+
+        let mut streamed = Vec::new();
+        compressor
+            .decoder(Box::new(&compressed[..]))
+            .unwrap()
+            .read_to_end(&mut streamed)
+            .unwrap();
+        assert_eq!(streamed, data);
+
+        // The dictionary is required to decompress
+        assert!(ZstdCompressor::default().decompress(&compressed).is_err());
+    }
+
+    #[test]
+    fn test_dictionary_decoder_outlives_compressor() {
+        let samples: Vec<Vec<u8>> = (0..500).map(record).collect();
+        let dictionary = zstd::dict::from_samples(&samples, 2048).unwrap();
+        let data: Vec<u8> = (0..50).flat_map(record).collect();
+
+        let compressor = ZstdDictionaryCompressor::new(&dictionary, 3).unwrap();
+        let compressed = compressor.compress(&data).unwrap();
+        let mut decoder = compressor.decoder(Box::new(&compressed[..])).unwrap();
+        drop(compressor);
+
+        let mut streamed = Vec::new();
+        decoder.read_to_end(&mut streamed).unwrap();
+        assert_eq!(streamed, data);
+    }
+
+    #[test]
+    fn test_shared_dictionary_across_threads() {
+        fn assert_send_sync<T: Send + Sync>(_: &T) {}
+
+        let samples: Vec<Vec<u8>> = (0..500).map(record).collect();
+        let dictionary = zstd::dict::from_samples(&samples, 2048).unwrap();
+        let compressor = ZstdDictionaryCompressor::new(&dictionary, 3).unwrap();
+        assert_send_sync(&compressor);
+
+        let data: Vec<u8> = (0..50).flat_map(record).collect();
+        let compressed = compressor.compress(&data).unwrap();
+
+        let shared = &compressor;
+        let results: Vec<Vec<u8>> = std::thread::scope(|scope| {
+            let threads: Vec<_> = (0..8)
+                .map(|thread| {
+                    let clone = compressor.clone();
+                    let compressed = &compressed;
+                    scope.spawn(move || {
+                        // Clones and the shared instance use the same prepared dictionary
+                        if thread % 2 == 0 {
+                            clone.decompress(compressed).unwrap()
+                        } else {
+                            shared.decompress(compressed).unwrap()
+                        }
+                    })
+                })
+                .collect();
+            threads.into_iter().map(|t| t.join().unwrap()).collect()
+        });
+
+        assert_eq!(results.len(), 8);
+        assert!(results.iter().all(|decompressed| *decompressed == data));
+    }
+
+    #[test]
+    fn test_dictionary_file_errors() {
+        let dir = tempfile::tempdir().unwrap();
+        assert!(matches!(
+            ZstdCompressor::from_dictionary_file(&dir.path().join("missing.dict")),
+            Err(CompressorError::IoError(_))
+        ));
+
+        let malformed = dir.path().join("malformed.dict");
+        std::fs::write(&malformed, b"not a dictionary").unwrap();
+        assert!(matches!(
+            ZstdCompressor::from_dictionary_file(&malformed),
+            Err(CompressorError::ConfigError(_))
+        ));
+    }
+
+    #[test]
+    fn test_multithreaded_compression() {
+        let data: Vec<u8> = (0..8_000_000u32)
+            .map(|i| (i % 251) as u8 ^ (i / 4096) as u8)
+            .collect();
+
+        let single = ZstdCompressor::default().compress(&data).unwrap();
+        let multi = ZstdCompressor::default()
+            .with_workers(4)
+            .compress(&data)
+            .unwrap();
+
+        // Decompression is always single-threaded and agnostic of how the frame was produced
+        let decompressor = ZstdCompressor::default();
+        assert_eq!(decompressor.decompress(&single).unwrap(), data);
+        assert_eq!(decompressor.decompress(&multi).unwrap(), data);
+    }
+}
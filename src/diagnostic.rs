@@ -0,0 +1,510 @@
+use crate::compressors::{CompressionAlgorithm, CompressorFactory};
+use crate::error::CompressorError;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+/// Timed runs per candidate in `select_fastest_meeting_ratio`; the fastest run counts
+const BENCHMARK_RUNS: usize = 3;
+
+/// Outcome of `TCompressor::compress_diagnostic`
+#[derive(Debug, Clone, PartialEq)]
+pub struct CompressDiagnostic {
+    pub compressed: Vec<u8>,
+    /// The compressed output is larger than the input
+    pub expanded: bool,
+    /// Fixed framing overhead reported by the compressor
+    pub overhead_bytes: usize,
+    /// Shannon entropy of the input in bits per byte (0.0 to 8.0)
+    pub estimated_entropy: f64,
+}
+
+/// Magic prefixes of common compressed formats
+const COMPRESSED_MAGICS: &[(&[u8], &str)] = &[
+    (b"\xff\x06\x00\x00sNaPpY", "snappy"),
+    (b"\x1f\x8b", "gzip"),
+    (b"\x28\xb5\x2f\xfd", "zstd"),
+    (b"\x04\x22\x4d\x18", "lz4"),
+    (b"\xfd7zXZ\x00", "xz"),
+    (b"BZh", "bzip2"),
+];
+
+/// Inputs shorter than this are too small for a meaningful entropy estimate
+const MIN_ENTROPY_SAMPLE: usize = 1024;
+/// Entropy in bits per byte above which data is considered already compressed
+const COMPRESSED_ENTROPY: f64 = 7.5;
+
+/// Names the compressed format `data` appears to be in, if any: a known magic prefix,
+/// or "high-entropy data" for large inputs that look random
+pub fn detect_compressed(data: &[u8]) -> Option<&'static str> {
+    COMPRESSED_MAGICS
+        .iter()
+        .find(|(magic, _)| data.starts_with(magic))
+        .map(|&(_, name)| name)
+        .or_else(|| {
+            (data.len() >= MIN_ENTROPY_SAMPLE && estimate_entropy(data) > COMPRESSED_ENTROPY)
+                .then_some("high-entropy data")
+        })
+}
+
+/// Heuristically checks whether `data` is already compressed, to avoid compressing it twice
+pub fn is_likely_compressed(data: &[u8]) -> bool {
+    detect_compressed(data).is_some()
+}
+
+/// Estimates the Shannon entropy of `data` in bits per byte from its byte histogram.
+///
+/// Values close to 8.0 mean the data looks random (already compressed or encrypted),
+/// while low values mean it is highly repetitive. Empty input has zero entropy.
+pub fn estimate_entropy(data: &[u8]) -> f64 {
+    if data.is_empty() {
+        return 0.0;
+    }
+
+    let histogram = byte_histogram(data);
+    let len = data.len() as f64;
+    histogram
+        .iter()
+        .filter(|&&count| count > 0)
+        .map(|&count| {
+            let probability = count as f64 / len;
+            -probability * probability.log2()
+        })
+        .sum()
+}
+
+/// Counts of each byte value in `data`, via the multi-lane path with the `simd` feature
+#[cfg(feature = "simd")]
+fn byte_histogram(data: &[u8]) -> [usize; 256] {
+    byte_histogram_lanes(data)
+}
+
+/// Counts of each byte value in `data`, one byte at a time
+#[cfg(not(feature = "simd"))]
+fn byte_histogram(data: &[u8]) -> [usize; 256] {
+    byte_histogram_scalar(data)
+}
+
+#[cfg_attr(feature = "simd", allow(dead_code))]
+fn byte_histogram_scalar(data: &[u8]) -> [usize; 256] {
+    let mut histogram = [0usize; 256];
+    for &byte in data {
+        histogram[byte as usize] += 1;
+    }
+    histogram
+}
+
+/// Reads 8 bytes per load and spreads them over 4 separate tables, so runs of the same
+/// byte don't serialize on one counter and the loop pipelines; the tables are summed at
+/// the end
+#[cfg_attr(not(feature = "simd"), allow(dead_code))]
+fn byte_histogram_lanes(data: &[u8]) -> [usize; 256] {
+    let mut lanes = [[0usize; 256]; 4];
+    let mut words = data.chunks_exact(8);
+
+    for word in &mut words {
+        let word = u64::from_le_bytes(word.try_into().unwrap());
+        for (i, lane) in [0, 1, 2, 3, 0, 1, 2, 3].into_iter().enumerate() {
+            lanes[lane][(word >> (i * 8)) as u8 as usize] += 1;
+        }
+    }
+    for &byte in words.remainder() {
+        lanes[0][byte as usize] += 1;
+    }
+
+    let mut histogram = [0usize; 256];
+    for lane in &lanes {
+        for (total, count) in histogram.iter_mut().zip(lane) {
+            *total += count;
+        }
+    }
+    histogram
+}
+
+/// Text part of the `self_test` payload, repeated so every algorithm finds matches
+const SELF_TEST_TEXT: &[u8] = b"bering-compression self-test vector: {\"id\":42,\"ok\":true}\n";
+
+/// Round-trips a built-in payload through every compiled-in algorithm, e.g. as a startup
+/// health check that the binary's codecs work. Fails with the first algorithm's error, or
+/// `IntegrityError` if its output does not match the payload.
+pub fn self_test() -> Result<(), CompressorError> {
+    let mut payload = SELF_TEST_TEXT.repeat(64);
+    payload.extend(0..=u8::MAX);
+
+    for &algorithm in CompressionAlgorithm::all() {
+        let compressor = CompressorFactory::get_compressor(algorithm);
+        let round_trip = compressor
+            .compress(&payload)
+            .and_then(|compressed| compressor.decompress(&compressed))
+            .map_err(|err| err.with_algorithm(algorithm))?;
+        if round_trip != payload {
+            return Err(CompressorError::IntegrityError(format!(
+                "{} self-test round trip returned {} bytes that differ from the {} byte payload",
+                algorithm,
+                round_trip.len(),
+                payload.len()
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Decompresses `a` with `algo_a` and `b` with `algo_b` and checks that both hold the same
+/// bytes, e.g. to verify a recompression before the old copy is dropped.
+///
+/// A decompression failure is returned tagged with its algorithm; differing content fails
+/// with `IntegrityError` naming the first differing offset.
+pub fn assert_equivalent(
+    a: &[u8],
+    algo_a: CompressionAlgorithm,
+    b: &[u8],
+    algo_b: CompressionAlgorithm,
+) -> Result<(), CompressorError> {
+    let decompress = |data, algorithm| {
+        CompressorFactory::get_compressor(algorithm)
+            .decompress(data)
+            .map_err(|err| err.with_algorithm(algorithm))
+    };
+    let (a, b) = (decompress(a, algo_a)?, decompress(b, algo_b)?);
+
+    let Some(offset) = a
+        .iter()
+        .zip(&b)
+        .position(|(x, y)| x != y)
+        .or_else(|| (a.len() != b.len()).then(|| a.len().min(b.len())))
+    else {
+        return Ok(());
+    };
+
+    Err(CompressorError::IntegrityError(format!(
+        "{} content ({} bytes) and {} content ({} bytes) differ at offset {}",
+        algo_a,
+        a.len(),
+        algo_b,
+        b.len(),
+        offset
+    )))
+}
+
+/// Benchmarks each candidate on `sample` and returns the fastest one whose compression
+/// ratio, `sample.len() / compressed.len()` (2.0 means half the size), is at least
+/// `min_ratio`.
+///
+/// Each candidate compresses the sample a few times and its fastest run counts, which
+/// damps scheduling noise. Candidates that fail to compress the sample are skipped;
+/// `None` means no candidate met the floor.
+pub fn select_fastest_meeting_ratio(
+    sample: &[u8],
+    min_ratio: f64,
+    candidates: &[CompressionAlgorithm],
+) -> Option<CompressionAlgorithm> {
+    let mut fastest: Option<(CompressionAlgorithm, Duration)> = None;
+
+    for &algorithm in candidates {
+        let Some((elapsed, compressed_len)) = benchmark(algorithm, sample) else {
+            continue;
+        };
+
+        let ratio = sample.len() as f64 / compressed_len.max(1) as f64;
+        if ratio >= min_ratio && fastest.is_none_or(|(_, fastest)| elapsed < fastest) {
+            fastest = Some((algorithm, elapsed));
+        }
+    }
+
+    fastest.map(|(algorithm, _)| algorithm)
+}
+
+/// Compressed-to-original size ratio of each of `samples` under `algorithm`'s default
+/// compressor, as `TCompressor::estimate_ratio` reports it: below 1.0 means the sample
+/// compresses, and an empty sample reports 1.0. Samples that fail to compress report NaN,
+/// which fails every threshold comparison.
+pub fn ratio_report(samples: &[&[u8]], algorithm: CompressionAlgorithm) -> Vec<f64> {
+    let compressor = CompressorFactory::get_compressor(algorithm);
+
+    samples
+        .iter()
+        .map(|sample| compressor.estimate_ratio(sample, None).unwrap_or(f64::NAN))
+        .collect()
+}
+
+/// Times every algorithm enabled in this build on `sample`, as a relative CPU cost for
+/// scheduling: the fastest of a few runs, at least 1ns so ratios between entries stay
+/// finite. Algorithms that fail to compress the sample are left out.
+pub fn calibrate_costs(sample: &[u8]) -> HashMap<CompressionAlgorithm, Duration> {
+    CompressionAlgorithm::all()
+        .iter()
+        .filter_map(|&algorithm| {
+            let (elapsed, _) = benchmark(algorithm, sample)?;
+            Some((algorithm, elapsed.max(Duration::from_nanos(1))))
+        })
+        .collect()
+}
+
+/// Fastest of `BENCHMARK_RUNS` compressions of `sample` and the compressed length, or
+/// `None` if compression fails
+fn benchmark(algorithm: CompressionAlgorithm, sample: &[u8]) -> Option<(Duration, usize)> {
+    let compressor = CompressorFactory::get_compressor(algorithm);
+    let mut best = Duration::MAX;
+    let mut compressed_len = 0;
+
+    for _ in 0..BENCHMARK_RUNS {
+        let started = Instant::now();
+        compressed_len = compressor.compress(sample).ok()?.len();
+        best = best.min(started.elapsed());
+    }
+
+    Some((best, compressed_len))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compressors::{IdentityCompressor, SnappyCompressor, TCompressor};
+
+    #[test]
+    fn test_estimate_entropy_bounds() {
+        assert_eq!(estimate_entropy(&[]), 0.0);
+        assert_eq!(estimate_entropy(&[9; 1000]), 0.0);
+        assert_eq!(estimate_entropy(&[0, 1].repeat(500)), 1.0);
+
+        let all_bytes: Vec<u8> = (0..=255).collect();
+        assert_eq!(estimate_entropy(&all_bytes), 8.0);
+    }
+
+    /// Bitwise reflected CRC-32 (IEEE), the reference for `crc32fast`'s accelerated paths
+    fn crc32_bitwise(data: &[u8]) -> u32 {
+        let mut crc = !0u32;
+        for &byte in data {
+            crc ^= byte as u32;
+            for _ in 0..8 {
+                crc = (crc >> 1) ^ (0xEDB8_8320 & (crc & 1).wrapping_neg());
+            }
+        }
+        !crc
+    }
+
+    #[test]
+    fn test_lane_and_scalar_histograms_agree() {
+        let inputs: Vec<Vec<u8>> = vec![
+            Vec::new(),
+            vec![7; 13],
+            (0..=255).collect(),
+            br#"{"lane":"histogram","n":[1,2,3]}"#.repeat(300),
+            // A length that leaves a partial 8-byte word
+            crate::noise(0x2545_f491_4f6c_dd1d, 100_003),
+        ];
+
+        for input in &inputs {
+            assert_eq!(byte_histogram_lanes(input), byte_histogram_scalar(input));
+            assert_eq!(byte_histogram(input), byte_histogram_scalar(input));
+            assert_eq!(crc32fast::hash(input), crc32_bitwise(input));
+        }
+    }
+
+    #[test]
+    fn test_is_likely_compressed() {
+        let json =
+            br#"{"name":"double compression","values":[1,2,3],"nested":{"ok":true}}"#.repeat(100);
+        assert!(!is_likely_compressed(&json));
+        assert!(!is_likely_compressed(b""));
+
+        let snappy = SnappyCompressor.compress(&json).unwrap();
+        assert!(is_likely_compressed(&snappy));
+
+        #[cfg(feature = "gzip")]
+        assert!(is_likely_compressed(
+            &crate::compressors::GzipCompressor::default()
+                .compress(&json)
+                .unwrap()
+        ));
+
+        // A gzip header alone is enough, even without the gzip feature
+        assert_eq!(detect_compressed(b"\x1f\x8b\x08\x00"), Some("gzip"));
+    }
+
+    #[test]
+    fn test_select_fastest_meeting_ratio() {
+        let sample = br#"{"event":"deploy","region":"eu-west","ok":true}"#.repeat(2000);
+        let candidates = CompressionAlgorithm::all();
+
+        // Identity never compresses, so a ratio floor of 2 rules it out
+        let selected = select_fastest_meeting_ratio(&sample, 2.0, candidates).unwrap();
+        assert_ne!(selected, CompressionAlgorithm::Identity);
+        let compressed = CompressorFactory::get_compressor(selected)
+            .compress(&sample)
+            .unwrap();
+        assert!(sample.len() as f64 / compressed.len() as f64 >= 2.0);
+
+        assert_eq!(
+            select_fastest_meeting_ratio(&sample, 0.5, &[CompressionAlgorithm::Identity]),
+            Some(CompressionAlgorithm::Identity)
+        );
+        assert_eq!(select_fastest_meeting_ratio(&sample, 1e9, candidates), None);
+        assert_eq!(select_fastest_meeting_ratio(&sample, 1.0, &[]), None);
+    }
+
+    #[test]
+    fn test_self_test() {
+        self_test().unwrap();
+    }
+
+    #[test]
+    fn test_assert_equivalent() {
+        let content = br#"{"migrated":true,"rows":[1,2,3]}"#.repeat(50);
+        let snappy = SnappyCompressor.compress(&content).unwrap();
+        let identity = IdentityCompressor.compress(&content).unwrap();
+        assert_equivalent(
+            &snappy,
+            CompressionAlgorithm::Snappy,
+            &identity,
+            CompressionAlgorithm::Identity,
+        )
+        .unwrap();
+
+        let mut changed = content.clone();
+        changed[700] ^= 1;
+        let err = assert_equivalent(
+            &snappy,
+            CompressionAlgorithm::Snappy,
+            &changed,
+            CompressionAlgorithm::Identity,
+        )
+        .unwrap_err();
+        assert!(
+            matches!(&err, CompressorError::IntegrityError(message) if message.contains("offset 700"))
+        );
+
+        let truncated = &content[..content.len() - 1];
+        let err = assert_equivalent(
+            &snappy,
+            CompressionAlgorithm::Snappy,
+            truncated,
+            CompressionAlgorithm::Identity,
+        )
+        .unwrap_err();
+        assert!(err
+            .to_string()
+            .contains(&format!("offset {}", truncated.len())));
+
+        assert!(matches!(
+            assert_equivalent(
+                b"not snappy",
+                CompressionAlgorithm::Snappy,
+                &identity,
+                CompressionAlgorithm::Identity,
+            ),
+            Err(CompressorError::WithAlgorithm {
+                algorithm: CompressionAlgorithm::Snappy,
+                ..
+            })
+        ));
+    }
+
+    /// Ratios may exceed their committed baseline by at most this fraction (2%) before the
+    /// regression test fails; improvements always pass, update the baseline to keep them
+    const RATIO_TOLERANCE: f64 = 0.02;
+
+    /// Deterministic fixtures: English-like text, JSON records and xorshift binary noise
+    fn ratio_fixtures() -> [Vec<u8>; 3] {
+        const WORDS: [&str; 8] = [
+            "the",
+            "compressed",
+            "archive",
+            "stores",
+            "records",
+            "quickly",
+            "and",
+            "safely",
+        ];
+        let mut bytes = crate::noise(0x2545_f491_4f6c_dd1d, 16_384)
+            .into_iter()
+            .cycle();
+        let mut next = move || u64::from(bytes.next().unwrap());
+
+        let text = (0..4000)
+            .map(|_| WORDS[next() as usize % WORDS.len()])
+            .collect::<Vec<_>>()
+            .join(" ")
+            .into_bytes();
+        let json = (0..500)
+            .map(|id| {
+                format!(
+                    r#"{{"id":{},"user":"user{}","score":{},"active":{}}}"#,
+                    id,
+                    next() % 100,
+                    next() << 8 | next(),
+                    next() % 2 == 0
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(",")
+            .into_bytes();
+        let binary = crate::noise(0x9e37_79b9_7f4a_7c15, 16_384);
+
+        [text, json, binary]
+    }
+
+    fn check_ratios(algorithm: CompressionAlgorithm, baseline: [f64; 3]) {
+        let fixtures = ratio_fixtures();
+        let samples: Vec<&[u8]> = fixtures.iter().map(Vec::as_slice).collect();
+        let ratios = ratio_report(&samples, algorithm);
+
+        for ((ratio, baseline), name) in ratios.iter().zip(baseline).zip(["text", "json", "binary"])
+        {
+            assert!(
+                *ratio <= baseline * (1.0 + RATIO_TOLERANCE),
+                "{} ratio on {} regressed: {:.4} vs baseline {:.4}",
+                algorithm,
+                name,
+                ratio,
+                baseline
+            );
+        }
+    }
+
+    #[test]
+    fn test_ratio_report() {
+        let ratios = ratio_report(&[b"", &b"abcd".repeat(1000)], CompressionAlgorithm::Snappy);
+        assert_eq!(ratios[0], 1.0);
+        assert!(ratios[1] < 0.1);
+        assert_eq!(
+            ratio_report(&[b"raw"], CompressionAlgorithm::Identity),
+            [1.0]
+        );
+        assert!(ratio_report(&[], CompressionAlgorithm::Snappy).is_empty());
+    }
+
+    #[test]
+    fn test_ratio_baselines() {
+        check_ratios(CompressionAlgorithm::Identity, [1.0, 1.0, 1.0]);
+        check_ratios(CompressionAlgorithm::Snappy, [0.2603, 0.2884, 1.0011]);
+        #[cfg(feature = "gzip")]
+        check_ratios(CompressionAlgorithm::Gzip, [0.1079, 0.1501, 1.0014]);
+        #[cfg(feature = "zstd")]
+        check_ratios(CompressionAlgorithm::Zstd, [0.1472, 0.1581, 1.0005]);
+        #[cfg(feature = "lz4")]
+        check_ratios(CompressionAlgorithm::Lz4, [0.3222, 0.2711, 1.0014]);
+    }
+
+    #[test]
+    fn test_calibrate_costs() {
+        let sample = b"scheduler calibration sample ".repeat(4000);
+        let costs = calibrate_costs(&sample);
+
+        assert_eq!(costs.len(), CompressionAlgorithm::all().len());
+        for algorithm in CompressionAlgorithm::all() {
+            assert!(costs[algorithm] > Duration::ZERO, "{:?}", algorithm);
+        }
+        assert!(calibrate_costs(b"").values().all(|cost| !cost.is_zero()));
+    }
+
+    #[test]
+    fn test_compress_guarded() {
+        let json = br#"{"guarded":true}"#.repeat(100);
+        let compressed = SnappyCompressor.compress_guarded(&json).unwrap();
+
+        assert!(matches!(
+            SnappyCompressor.compress_guarded(&compressed),
+            Err(CompressorError::AlreadyCompressed(format)) if format == "snappy"
+        ));
+    }
+}
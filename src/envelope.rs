@@ -0,0 +1,173 @@
+//! Versioned envelope for durable storage, recording both lengths of the payload.
+//!
+//! Layout, all integers little-endian:
+//!
+//! | bytes | field                    |
+//! |-------|--------------------------|
+//! | 4     | magic `BENV`             |
+//! | 1     | format version (1)       |
+//! | 1     | algorithm tag            |
+//! | 8     | original length (u64)    |
+//! | 8     | compressed length (u64)  |
+//! | ...   | compressed payload       |
+
+use crate::compressors::{CompressionAlgorithm, CompressorFactory};
+use crate::error::CompressorError;
+
+pub const ENVELOPE_MAGIC: &[u8; 4] = b"BENV";
+pub const ENVELOPE_VERSION: u8 = 1;
+pub const ENVELOPE_HEADER_LEN: usize = ENVELOPE_MAGIC.len() + 2 + 8 + 8;
+
+/// The fixed-size fields at the start of an envelope
+pub(crate) struct EnvelopeHeader {
+    pub(crate) algorithm: CompressionAlgorithm,
+    pub(crate) original_len: u64,
+    pub(crate) compressed_len: u64,
+}
+
+impl EnvelopeHeader {
+    /// Validates the magic, version and algorithm tag at the start of `data`
+    pub(crate) fn parse(data: &[u8]) -> Result<Self, CompressorError> {
+        if data.len() < ENVELOPE_HEADER_LEN || !data.starts_with(ENVELOPE_MAGIC) {
+            return Err(CompressorError::DecompressionError(
+                "missing envelope header".to_string(),
+            ));
+        }
+        if data[4] != ENVELOPE_VERSION {
+            return Err(CompressorError::DecompressionError(format!(
+                "unsupported envelope version {}",
+                data[4]
+            )));
+        }
+
+        let read_u64 = |offset: usize| {
+            let mut bytes = [0u8; 8];
+            bytes.copy_from_slice(&data[offset..offset + 8]);
+            u64::from_le_bytes(bytes)
+        };
+
+        Ok(EnvelopeHeader {
+            algorithm: CompressionAlgorithm::from_tag(data[5])?,
+            original_len: read_u64(6),
+            compressed_len: read_u64(14),
+        })
+    }
+}
+
+/// Compresses `data` with `algorithm` into an envelope
+pub fn encode_envelope(
+    data: &[u8],
+    algorithm: CompressionAlgorithm,
+) -> Result<Vec<u8>, CompressorError> {
+    let payload = CompressorFactory::get_compressor(algorithm)
+        .compress(data)
+        .map_err(|err| err.with_algorithm(algorithm))?;
+
+    let mut envelope = Vec::with_capacity(ENVELOPE_HEADER_LEN + payload.len());
+    envelope.extend_from_slice(ENVELOPE_MAGIC);
+    envelope.push(ENVELOPE_VERSION);
+    envelope.push(algorithm.tag());
+    envelope.extend_from_slice(&(data.len() as u64).to_le_bytes());
+    envelope.extend_from_slice(&(payload.len() as u64).to_le_bytes());
+    envelope.extend_from_slice(&payload);
+    Ok(envelope)
+}
+
+/// Validates and decompresses an envelope, returning the algorithm it records and the
+/// original data.
+///
+/// A payload shorter than the recorded compressed length, or data that does not
+/// decompress to the recorded original length, fails with `IntegrityError`; bytes after
+/// the payload fail with `TrailingData`.
+pub fn decode_envelope(data: &[u8]) -> Result<(CompressionAlgorithm, Vec<u8>), CompressorError> {
+    let EnvelopeHeader {
+        algorithm,
+        original_len,
+        compressed_len,
+    } = EnvelopeHeader::parse(data)?;
+
+    let payload = &data[ENVELOPE_HEADER_LEN..];
+    match usize::try_from(compressed_len) {
+        Ok(len) if len == payload.len() => {}
+        Ok(len) if len < payload.len() => {
+            return Err(CompressorError::TrailingData(payload.len() - len));
+        }
+        _ => {
+            return Err(CompressorError::IntegrityError(format!(
+                "envelope records {} compressed bytes but holds {}",
+                compressed_len,
+                payload.len()
+            )));
+        }
+    }
+
+    let decompressed = CompressorFactory::get_compressor(algorithm)
+        .decompress_strict(payload)
+        .map_err(|err| err.with_algorithm(algorithm))?;
+    if decompressed.len() as u64 != original_len {
+        return Err(CompressorError::IntegrityError(format!(
+            "envelope records {} original bytes but decompressed to {}",
+            original_len,
+            decompressed.len()
+        )));
+    }
+
+    Ok((algorithm, decompressed))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_envelope_round_trip() {
+        let data = b"durable envelope ".repeat(100);
+        let envelope = encode_envelope(&data, CompressionAlgorithm::Snappy).unwrap();
+
+        assert!(envelope.starts_with(ENVELOPE_MAGIC));
+        assert_eq!(envelope[4], ENVELOPE_VERSION);
+        assert_eq!(envelope[5], CompressionAlgorithm::Snappy.tag());
+        assert_eq!(
+            decode_envelope(&envelope).unwrap(),
+            (CompressionAlgorithm::Snappy, data)
+        );
+    }
+
+    #[test]
+    fn test_envelope_wrong_magic() {
+        let mut envelope = encode_envelope(b"payload", CompressionAlgorithm::Snappy).unwrap();
+        envelope[0] = b'X';
+
+        assert!(matches!(
+            decode_envelope(&envelope),
+            Err(CompressorError::DecompressionError(_))
+        ));
+        assert!(decode_envelope(b"BENV").is_err());
+    }
+
+    #[test]
+    fn test_envelope_tampered_lengths() {
+        let envelope = encode_envelope(b"payload", CompressionAlgorithm::Snappy).unwrap();
+
+        let mut original = envelope.clone();
+        original[6] += 1;
+        assert!(matches!(
+            decode_envelope(&original),
+            Err(CompressorError::IntegrityError(_))
+        ));
+
+        let mut compressed = envelope.clone();
+        compressed[14] += 1;
+        assert!(matches!(
+            decode_envelope(&compressed),
+            Err(CompressorError::IntegrityError(_))
+        ));
+
+        let mut trailing = envelope.clone();
+        trailing.push(0);
+        assert!(matches!(
+            decode_envelope(&trailing),
+            Err(CompressorError::TrailingData(1))
+        ));
+    }
+}
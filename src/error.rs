@@ -1,3 +1,9 @@
+use crate::compressors::CompressionAlgorithm;
+use alloc::{
+    boxed::Box,
+    string::{String, ToString},
+};
+use core::fmt;
 use thiserror::Error;
 
 #[derive(Debug, Error)]
@@ -10,4 +16,166 @@ pub enum CompressorError {
     SerializationError(String),
     #[error("Deserialization error: {0}")]
     DeserializationError(String),
+    #[error("Decoding error: {0}")]
+    DecodingError(String),
+    #[error("I/O error: {0}")]
+    IoError(String),
+    #[error("Integrity error: {0}")]
+    IntegrityError(String),
+    #[error("Configuration error: {0}")]
+    ConfigError(String),
+    #[error("Trailing data: {0} unexpected bytes after the compressed stream")]
+    TrailingData(usize),
+    #[error("Input is already compressed: {0}")]
+    AlreadyCompressed(String),
+    #[error("Deadline exceeded before compression finished")]
+    DeadlineExceeded,
+    #[error("Unknown algorithm tag: {0}")]
+    UnknownAlgorithm(u8),
+    #[error("Compression algorithm '{0}' is not enabled in this build")]
+    UnsupportedAlgorithm(&'static str),
+    #[error("Authentication failed: the ciphertext or its key does not match")]
+    AuthenticationFailed,
+    #[error("{key}: {source}")]
+    KeyedError {
+        key: String,
+        source: Box<CompressorError>,
+    },
+    #[error("{algorithm:?}: {source}")]
+    WithAlgorithm {
+        algorithm: CompressionAlgorithm,
+        source: Box<CompressorError>,
+    },
+}
+
+/// Machine-readable category of a `CompressorError`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ErrorKind {
+    Compression,
+    Decompression,
+    Serialization,
+    Deserialization,
+    Decoding,
+    Io,
+    Integrity,
+    Config,
+    TrailingData,
+    AlreadyCompressed,
+    DeadlineExceeded,
+    UnknownAlgorithm,
+    UnsupportedAlgorithm,
+    Authentication,
+}
+
+impl ErrorKind {
+    /// Stable identifier, safe to match on across releases
+    pub fn code(&self) -> &'static str {
+        match self {
+            ErrorKind::Compression => "compression",
+            ErrorKind::Decompression => "decompression",
+            ErrorKind::Serialization => "serialization",
+            ErrorKind::Deserialization => "deserialization",
+            ErrorKind::Decoding => "decoding",
+            ErrorKind::Io => "io",
+            ErrorKind::Integrity => "integrity",
+            ErrorKind::Config => "config",
+            ErrorKind::TrailingData => "trailing_data",
+            ErrorKind::AlreadyCompressed => "already_compressed",
+            ErrorKind::DeadlineExceeded => "deadline_exceeded",
+            ErrorKind::UnknownAlgorithm => "unknown_algorithm",
+            ErrorKind::UnsupportedAlgorithm => "unsupported_algorithm",
+            ErrorKind::Authentication => "authentication",
+        }
+    }
+}
+
+impl CompressorError {
+    /// The category of the error. Context wrappers (`WithAlgorithm`, `KeyedError`)
+    /// report the kind of the error they wrap.
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            CompressorError::CompressionError(_) => ErrorKind::Compression,
+            CompressorError::DecompressionError(_) => ErrorKind::Decompression,
+            CompressorError::SerializationError(_) => ErrorKind::Serialization,
+            CompressorError::DeserializationError(_) => ErrorKind::Deserialization,
+            CompressorError::DecodingError(_) => ErrorKind::Decoding,
+            CompressorError::IoError(_) => ErrorKind::Io,
+            CompressorError::IntegrityError(_) => ErrorKind::Integrity,
+            CompressorError::ConfigError(_) => ErrorKind::Config,
+            CompressorError::TrailingData(_) => ErrorKind::TrailingData,
+            CompressorError::AlreadyCompressed(_) => ErrorKind::AlreadyCompressed,
+            CompressorError::DeadlineExceeded => ErrorKind::DeadlineExceeded,
+            CompressorError::UnknownAlgorithm(_) => ErrorKind::UnknownAlgorithm,
+            CompressorError::UnsupportedAlgorithm(_) => ErrorKind::UnsupportedAlgorithm,
+            CompressorError::AuthenticationFailed => ErrorKind::Authentication,
+            CompressorError::KeyedError { source, .. }
+            | CompressorError::WithAlgorithm { source, .. } => source.kind(),
+        }
+    }
+
+    /// Stable identifier of the error's kind, e.g. `"compression"` or `"integrity"`
+    pub fn code(&self) -> &'static str {
+        self.kind().code()
+    }
+
+    /// Wraps the error with the algorithm that was in use when it occurred
+    pub fn with_algorithm(self, algorithm: CompressionAlgorithm) -> Self {
+        CompressorError::WithAlgorithm {
+            algorithm,
+            source: Box::new(self),
+        }
+    }
+
+    /// Wraps the error with the key of the payload being processed when it occurred
+    pub fn with_key(self, key: impl fmt::Display) -> Self {
+        CompressorError::KeyedError {
+            key: key.to_string(),
+            source: Box::new(self),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::{string::ToString, vec, vec::Vec};
+
+    #[test]
+    fn test_codes_are_distinct() {
+        let message = || "message".to_string();
+        let errors = vec![
+            CompressorError::CompressionError(message()),
+            CompressorError::DecompressionError(message()),
+            CompressorError::SerializationError(message()),
+            CompressorError::DeserializationError(message()),
+            CompressorError::DecodingError(message()),
+            CompressorError::IoError(message()),
+            CompressorError::IntegrityError(message()),
+            CompressorError::ConfigError(message()),
+            CompressorError::TrailingData(1),
+            CompressorError::AlreadyCompressed(message()),
+            CompressorError::DeadlineExceeded,
+            CompressorError::UnknownAlgorithm(9),
+            CompressorError::UnsupportedAlgorithm("zstd"),
+            CompressorError::AuthenticationFailed,
+        ];
+
+        let mut codes: Vec<&str> = errors.iter().map(CompressorError::code).collect();
+        assert_eq!(codes[0], "compression");
+        assert_eq!(codes[3], "deserialization");
+        assert_eq!(codes[6], "integrity");
+        codes.sort_unstable();
+        codes.dedup();
+        assert_eq!(codes.len(), errors.len());
+    }
+
+    #[test]
+    fn test_wrappers_report_source_code() {
+        let err = CompressorError::IntegrityError("checksum mismatch".to_string())
+            .with_algorithm(CompressionAlgorithm::Snappy)
+            .with_key("user:42");
+
+        assert_eq!(err.kind(), ErrorKind::Integrity);
+        assert_eq!(err.code(), "integrity");
+    }
 }
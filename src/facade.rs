@@ -0,0 +1,125 @@
+use crate::compressors::TCompressor;
+use crate::error::CompressorError;
+use crate::options::CompressionOptions;
+use crate::serializer::Serializer;
+use crate::tagged::{decompress_tagged_to, TaggedHeader};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+/// A serializer and configured algorithm bundled into one object, so call sites only
+/// pick what to encode.
+///
+/// `encode` produces tagged blobs (see `tagged`) recording the algorithm and serializer,
+/// so `decode` needs neither: it reads them from the header and also accepts blobs from
+/// differently configured `Compressor`s or `to_vec_with_header`.
+pub struct Compressor {
+    options: CompressionOptions,
+    serializer: Serializer,
+    compressor: Box<dyn TCompressor + Send + Sync>,
+}
+
+impl Compressor {
+    /// Fails with `ConfigError` if `options` are invalid for their algorithm
+    pub fn new(
+        options: CompressionOptions,
+        serializer: Serializer,
+    ) -> Result<Self, CompressorError> {
+        Ok(Compressor {
+            options,
+            serializer,
+            compressor: options.build()?,
+        })
+    }
+
+    pub fn options(&self) -> &CompressionOptions {
+        &self.options
+    }
+
+    pub fn serializer(&self) -> Serializer {
+        self.serializer
+    }
+
+    pub fn encode<T: Serialize + ?Sized>(&self, value: &T) -> Result<Vec<u8>, CompressorError> {
+        let algorithm = self.options.algorithm;
+        let compressed = self
+            .compressor
+            .compress(&self.serializer.serialize(value)?)
+            .map_err(|err| err.with_algorithm(algorithm))?;
+
+        let header = TaggedHeader {
+            algorithm,
+            serializer: Some(self.serializer),
+        };
+        Ok(header.encode(&compressed))
+    }
+
+    pub fn decode<T: DeserializeOwned>(&self, data: &[u8]) -> Result<T, CompressorError> {
+        decompress_tagged_to(data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compressors::CompressionAlgorithm;
+    use serde::Deserialize;
+
+    #[derive(Debug, Serialize, Deserialize, PartialEq)]
+    struct Settings {
+        name: String,
+        retries: u32,
+    }
+
+    #[test]
+    fn test_encode_decode() {
+        let compressor = Compressor::new(
+            CompressionOptions::new(CompressionAlgorithm::Snappy),
+            Serializer::Json,
+        )
+        .unwrap();
+        let settings = Settings {
+            name: "facade".to_string(),
+            retries: 3,
+        };
+
+        let encoded = compressor.encode(&settings).unwrap();
+        assert_eq!(compressor.decode::<Settings>(&encoded).unwrap(), settings);
+
+        // The blob records how it was produced
+        let (header, _) = TaggedHeader::parse(&encoded).unwrap();
+        assert_eq!(header.algorithm, CompressionAlgorithm::Snappy);
+        assert_eq!(header.serializer, Some(Serializer::Json));
+        assert_eq!(
+            decompress_tagged_to::<Settings>(&encoded).unwrap(),
+            settings
+        );
+
+        let identity = Compressor::new(
+            CompressionOptions::new(CompressionAlgorithm::Identity),
+            Serializer::Json,
+        )
+        .unwrap();
+        assert_eq!(identity.decode::<Settings>(&encoded).unwrap(), settings);
+        assert!(identity.decode::<Settings>(b"untagged").is_err());
+    }
+
+    #[cfg(feature = "gzip")]
+    #[test]
+    fn test_configured_levels() {
+        let options = CompressionOptions::new(CompressionAlgorithm::Gzip);
+        let value = "gzip level ".repeat(1000);
+
+        let fast = Compressor::new(options.with_level(1), Serializer::Json).unwrap();
+        let best = Compressor::new(options.with_level(9), Serializer::Json).unwrap();
+        assert_eq!(
+            fast.decode::<String>(&best.encode(&value).unwrap())
+                .unwrap(),
+            value
+        );
+
+        assert!(matches!(
+            Compressor::new(options.with_level(42), Serializer::Json),
+            Err(CompressorError::ConfigError(_))
+        ));
+    }
+}
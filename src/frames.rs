@@ -0,0 +1,422 @@
+use crate::compressors::{CompressionAlgorithm, CompressorFactory, TCompressor};
+use crate::error::CompressorError;
+use crate::inspect;
+use std::collections::BTreeMap;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, PoisonError};
+
+/// Length of the u32 LE compressed-length prefix of a record
+const RECORD_PREFIX_LEN: usize = 4;
+
+/// Serializes `append_compressed_record` calls within the process, and remembers the
+/// length of each file after this process's last append, which is known to end on a
+/// record boundary
+static APPEND_LOCK: Mutex<BTreeMap<PathBuf, u64>> = Mutex::new(BTreeMap::new());
+
+/// Compresses `data` as a new, independently decodable frame appended to `existing`.
+///
+/// Earlier frames are left untouched, which makes this suitable for append-only logs:
+/// each append only costs compressing the new data.
+pub fn append_frame(
+    existing: &mut Vec<u8>,
+    data: &[u8],
+    algorithm: CompressionAlgorithm,
+) -> Result<(), CompressorError> {
+    let frame = CompressorFactory::get_compressor(algorithm)
+        .compress(data)
+        .map_err(|err| err.with_algorithm(algorithm))?;
+
+    existing.extend_from_slice(&frame);
+    Ok(())
+}
+
+/// Decompresses a buffer of frames concatenated by `append_frame`, returning their
+/// content joined in order. Every built-in algorithm's format allows concatenation.
+pub fn decompress_concatenated(
+    data: &[u8],
+    algorithm: CompressionAlgorithm,
+) -> Result<Vec<u8>, CompressorError> {
+    CompressorFactory::get_compressor(algorithm)
+        .decompress(data)
+        .map_err(|err| err.with_algorithm(algorithm))
+}
+
+/// Lazily decompresses a buffer of concatenated frames one frame at a time, e.g. to stop
+/// at the first frame of interest without decoding the rest. Frame boundaries are found
+/// by parsing headers, except for gzip, whose members are delimited while decoding them;
+/// identity data is a single frame and skippable zstd frames are passed over.
+///
+/// An error ends the iteration, since the following frame boundaries are unknown.
+pub fn frames(
+    data: &[u8],
+    algorithm: CompressionAlgorithm,
+) -> impl Iterator<Item = Result<Vec<u8>, CompressorError>> + '_ {
+    let compressor = CompressorFactory::get_compressor(algorithm);
+    let mut remaining = data;
+
+    std::iter::from_fn(move || loop {
+        if remaining.is_empty() {
+            return None;
+        }
+
+        match next_frame(remaining, algorithm, &*compressor) {
+            Ok((frame_len, content)) => {
+                remaining = &remaining[frame_len..];
+                if let Some(content) = content {
+                    return Some(Ok(content));
+                }
+            }
+            Err(err) => {
+                remaining = &[];
+                return Some(Err(err.with_algorithm(algorithm)));
+            }
+        }
+    })
+}
+
+/// Decompresses the frame at the start of `data`, returning its length and its content,
+/// `None` for frames without content
+fn next_frame(
+    data: &[u8],
+    algorithm: CompressionAlgorithm,
+    compressor: &dyn TCompressor,
+) -> Result<(usize, Option<Vec<u8>>), CompressorError> {
+    #[cfg(feature = "gzip")]
+    if algorithm == CompressionAlgorithm::Gzip {
+        let mut decoder = flate2::bufread::GzDecoder::new(data);
+        let mut content = Vec::new();
+        decoder
+            .read_to_end(&mut content)
+            .map_err(|err| CompressorError::DecompressionError(err.to_string()))?;
+        return Ok((data.len() - decoder.into_inner().len(), Some(content)));
+    }
+
+    let (frame_len, has_content) = inspect::frame_len(data, algorithm)?;
+    let content = has_content
+        .then(|| compressor.decompress(&data[..frame_len]))
+        .transpose()?;
+    Ok((frame_len, content))
+}
+
+/// Appends `data` to the file at `path` as a compressed record: the u32 LE length of the
+/// compressed frame followed by the frame. The file is fsynced before returning, and so is
+/// its directory when the file is created.
+///
+/// Appends from the same process are serialized; other processes must coordinate on their
+/// own. A crash during an append can only leave a truncated last record, which
+/// `read_compressed_records` reports and the next append cuts off before writing, so
+/// records appended after a crash stay readable.
+pub fn append_compressed_record(
+    path: &Path,
+    data: &[u8],
+    algorithm: CompressionAlgorithm,
+) -> Result<(), CompressorError> {
+    let frame = CompressorFactory::get_compressor(algorithm)
+        .compress(data)
+        .map_err(|err| err.with_algorithm(algorithm))?;
+    let frame_len = u32::try_from(frame.len())
+        .map_err(|_| CompressorError::CompressionError("record too large".to_string()))?;
+
+    let mut record = Vec::with_capacity(RECORD_PREFIX_LEN + frame.len());
+    record.extend_from_slice(&frame_len.to_le_bytes());
+    record.extend_from_slice(&frame);
+
+    let io_error =
+        |err: io::Error| CompressorError::IoError(format!("{}: {}", path.display(), err));
+    let mut known_lens = APPEND_LOCK.lock().unwrap_or_else(PoisonError::into_inner);
+
+    // `create_new` never replaces a file another process created in the meantime
+    let (mut file, created) = match OpenOptions::new()
+        .read(true)
+        .append(true)
+        .create_new(true)
+        .open(path)
+    {
+        Ok(file) => (file, true),
+        Err(err) if err.kind() == io::ErrorKind::AlreadyExists => {
+            let file = OpenOptions::new()
+                .read(true)
+                .append(true)
+                .open(path)
+                .map_err(io_error)?;
+            (file, false)
+        }
+        Err(err) => return Err(io_error(err)),
+    };
+
+    let file_len = file.metadata().map_err(io_error)?.len();
+    if known_lens.get(path) != Some(&file_len) {
+        let intact_len = intact_records_len(&mut file, file_len).map_err(io_error)?;
+        if intact_len < file_len {
+            file.set_len(intact_len).map_err(io_error)?;
+        }
+    }
+
+    file.write_all(&record).map_err(io_error)?;
+    file.sync_all().map_err(io_error)?;
+    if created {
+        sync_parent_dir(path).map_err(io_error)?;
+    }
+    let file_len = file.metadata().map_err(io_error)?.len();
+    known_lens.insert(path.to_path_buf(), file_len);
+    Ok(())
+}
+
+/// Length of the complete records at the start of `file`, found by walking the length
+/// prefixes without reading the frames
+fn intact_records_len(file: &mut File, file_len: u64) -> io::Result<u64> {
+    let mut offset = 0;
+
+    while file_len - offset >= RECORD_PREFIX_LEN as u64 {
+        let mut prefix = [0; RECORD_PREFIX_LEN];
+        file.seek(SeekFrom::Start(offset))?;
+        file.read_exact(&mut prefix)?;
+
+        let end = offset + RECORD_PREFIX_LEN as u64 + u64::from(u32::from_le_bytes(prefix));
+        if end > file_len {
+            break;
+        }
+        offset = end;
+    }
+
+    Ok(offset)
+}
+
+/// Makes a newly created directory entry durable; directories cannot be opened as files
+/// on every platform, so this is a no-op outside Unix
+fn sync_parent_dir(path: &Path) -> io::Result<()> {
+    #[cfg(unix)]
+    {
+        let parent = match path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent,
+            _ => Path::new("."),
+        };
+        File::open(parent)?.sync_all()?;
+    }
+    #[cfg(not(unix))]
+    let _ = path;
+
+    Ok(())
+}
+
+/// Reads back the records appended by `append_compressed_record`, in order, along with
+/// the offset of a truncated last record if a crash left one. The records before it are
+/// intact and returned; a record that is complete but fails to decompress is an error.
+pub fn read_compressed_records(
+    data: &[u8],
+    algorithm: CompressionAlgorithm,
+) -> Result<(Vec<Vec<u8>>, Option<usize>), CompressorError> {
+    let compressor = CompressorFactory::get_compressor(algorithm);
+    let mut records = Vec::new();
+    let mut offset = 0;
+
+    while offset < data.len() {
+        let remaining = &data[offset..];
+        if remaining.len() < RECORD_PREFIX_LEN {
+            return Ok((records, Some(offset)));
+        }
+
+        let (prefix, rest) = remaining.split_at(RECORD_PREFIX_LEN);
+        let frame_len = u32::from_le_bytes([prefix[0], prefix[1], prefix[2], prefix[3]]) as usize;
+        let Some(frame) = rest.get(..frame_len) else {
+            return Ok((records, Some(offset)));
+        };
+
+        records.push(
+            compressor
+                .decompress(frame)
+                .map_err(|err| err.with_algorithm(algorithm).with_key(records.len()))?,
+        );
+        offset += RECORD_PREFIX_LEN + frame_len;
+    }
+
+    Ok((records, None))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn assert_appends_round_trip(algorithm: CompressionAlgorithm) {
+        let mut log = Vec::new();
+        append_frame(&mut log, b"first entry\n", algorithm).unwrap();
+        let after_first = log.clone();
+        append_frame(&mut log, b"second entry\n", algorithm).unwrap();
+        append_frame(&mut log, b"third entry\n", algorithm).unwrap();
+
+        assert!(log.starts_with(&after_first));
+        assert_eq!(
+            decompress_concatenated(&log, algorithm).unwrap(),
+            b"first entry\nsecond entry\nthird entry\n"
+        );
+
+        // Identity data has no frame boundaries and comes back whole
+        let expected: Vec<&[u8]> = match algorithm {
+            CompressionAlgorithm::Identity => vec![b"first entry\nsecond entry\nthird entry\n"],
+            _ => vec![b"first entry\n", b"second entry\n", b"third entry\n"],
+        };
+        let decoded: Vec<Vec<u8>> = frames(&log, algorithm).collect::<Result<_, _>>().unwrap();
+        assert_eq!(decoded, expected);
+    }
+
+    #[test]
+    fn test_frames_stop_early() {
+        let algorithm = CompressionAlgorithm::Snappy;
+        let mut blob = Vec::new();
+        append_frame(&mut blob, b"first", algorithm).unwrap();
+        append_frame(&mut blob, b"second", algorithm).unwrap();
+        append_frame(&mut blob, b"third", algorithm).unwrap();
+        // Corrupt the last byte of the third frame's content, leaving its headers intact
+        *blob.last_mut().unwrap() ^= 0xFF;
+
+        let mut iter = frames(&blob, algorithm);
+        assert_eq!(iter.next().unwrap().unwrap(), b"first");
+        assert_eq!(iter.next().unwrap().unwrap(), b"second");
+        drop(iter);
+
+        // Decoding the third frame would have failed its checksum
+        let mut iter = frames(&blob, algorithm).skip(2);
+        assert!(matches!(
+            iter.next(),
+            Some(Err(CompressorError::WithAlgorithm { .. }))
+        ));
+        assert!(iter.next().is_none());
+        assert!(frames(&[], algorithm).next().is_none());
+    }
+
+    #[test]
+    fn test_append_frames_snappy() {
+        assert_appends_round_trip(CompressionAlgorithm::Snappy);
+        assert_appends_round_trip(CompressionAlgorithm::Identity);
+    }
+
+    #[cfg(feature = "gzip")]
+    #[test]
+    fn test_append_frames_gzip() {
+        assert_appends_round_trip(CompressionAlgorithm::Gzip);
+    }
+
+    #[cfg(feature = "zstd")]
+    #[test]
+    fn test_append_frames_zstd() {
+        assert_appends_round_trip(CompressionAlgorithm::Zstd);
+    }
+
+    #[cfg(feature = "lz4")]
+    #[test]
+    fn test_append_frames_lz4() {
+        assert_appends_round_trip(CompressionAlgorithm::Lz4);
+    }
+
+    #[test]
+    fn test_append_compressed_records() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("wal.log");
+        let algorithm = CompressionAlgorithm::Snappy;
+
+        append_compressed_record(&path, b"begin", algorithm).unwrap();
+        std::thread::scope(|scope| {
+            for thread in 0..4 {
+                let path = &path;
+                scope.spawn(move || {
+                    for i in 0..10 {
+                        let entry = format!("thread {} entry {}", thread, i);
+                        append_compressed_record(path, entry.as_bytes(), algorithm).unwrap();
+                    }
+                });
+            }
+        });
+        append_compressed_record(&path, b"commit", algorithm).unwrap();
+
+        let written = fs::read(&path).unwrap();
+        let (records, torn_at) = read_compressed_records(&written, algorithm).unwrap();
+        assert_eq!(torn_at, None);
+        assert_eq!(records.len(), 42);
+        assert_eq!(records[0], b"begin");
+        assert_eq!(records[41], b"commit");
+        for thread in 0..4 {
+            let entries: Vec<String> = records
+                .iter()
+                .map(|record| String::from_utf8(record.clone()).unwrap())
+                .filter(|record| record.starts_with(&format!("thread {} ", thread)))
+                .collect();
+            let expected: Vec<String> = (0..10)
+                .map(|i| format!("thread {} entry {}", thread, i))
+                .collect();
+            assert_eq!(entries, expected);
+        }
+    }
+
+    #[test]
+    fn test_torn_record_recovery() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("wal.log");
+        let algorithm = CompressionAlgorithm::Snappy;
+
+        append_compressed_record(&path, b"first", algorithm).unwrap();
+        append_compressed_record(&path, b"second", algorithm).unwrap();
+        let intact = fs::read(&path).unwrap();
+
+        // A crash in the middle of a third append, in the frame or in the prefix
+        let mut torn = intact.clone();
+        torn.extend_from_slice(&[40, 0, 0, 0, 0xff, 0x06]);
+        let (records, torn_at) = read_compressed_records(&torn, algorithm).unwrap();
+        assert_eq!(records, [b"first".to_vec(), b"second".to_vec()]);
+        assert_eq!(torn_at, Some(intact.len()));
+        assert_eq!(
+            read_compressed_records(&torn[..intact.len() + 2], algorithm).unwrap(),
+            (records, Some(intact.len()))
+        );
+
+        // The next append, e.g. after a restart, writes over the torn tail
+        fs::write(&path, &torn).unwrap();
+        append_compressed_record(&path, b"third", algorithm).unwrap();
+        let (records, torn_at) =
+            read_compressed_records(&fs::read(&path).unwrap(), algorithm).unwrap();
+        assert_eq!(
+            records,
+            [b"first".to_vec(), b"second".to_vec(), b"third".to_vec()]
+        );
+        assert_eq!(torn_at, None);
+
+        // A complete record that does not decode is corruption, not a torn tail
+        let mut corrupt = intact;
+        corrupt[RECORD_PREFIX_LEN] ^= 0xff;
+        assert!(read_compressed_records(&corrupt, algorithm).is_err());
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn test_parallel_chunked_round_trip() {
+        use crate::compressors::{SnappyCompressor, TCompressor};
+
+        let data: Vec<u8> = (0..1_000_000u32).map(|i| (i % 251) as u8).collect();
+        let compressed = SnappyCompressor
+            .compress_parallel_chunked(&data, 100_000)
+            .unwrap();
+
+        assert_eq!(
+            decompress_concatenated(&compressed, CompressionAlgorithm::Snappy).unwrap(),
+            data
+        );
+        assert!(matches!(
+            SnappyCompressor.compress_parallel_chunked(&data, 0),
+            Err(CompressorError::ConfigError(_))
+        ));
+
+        #[cfg(feature = "zstd")]
+        {
+            let compressor = crate::compressors::ZstdCompressor::default();
+            let compressed = compressor
+                .compress_parallel_chunked(&data, 100_000)
+                .unwrap();
+            assert_eq!(
+                decompress_concatenated(&compressed, CompressionAlgorithm::Zstd).unwrap(),
+                data
+            );
+        }
+    }
+}
@@ -0,0 +1,357 @@
+//! Reads the parameters an encoder recorded in a compressed blob's frame headers,
+//! without decompressing it (except gzip, whose members can only be delimited by decoding).
+
+use crate::compressors::{CompressionAlgorithm, SnappyCompressor};
+use crate::error::CompressorError;
+
+/// Largest uncompressed chunk of the Snappy frame format
+const SNAPPY_MAX_CHUNK_LEN: u64 = 1 << 16;
+/// Deflate's fixed back-reference window
+#[cfg(feature = "gzip")]
+const DEFLATE_WINDOW: u64 = 1 << 15;
+#[cfg(feature = "zstd")]
+const ZSTD_MAGIC: u32 = 0xFD2F_B528;
+#[cfg(feature = "lz4")]
+const LZ4_MAGIC: u32 = 0x184D_2204;
+
+/// What `inspect` found in a compressed blob. Fields describe the first frame; formats
+/// that do not record a field leave it `None`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CompressionInfo {
+    pub algorithm: CompressionAlgorithm,
+    /// Back-reference window (zstd, gzip) or maximum block size (lz4, snappy) in bytes
+    pub window_size: Option<u64>,
+    /// The format verifies content with a checksum (zstd and lz4 record it per frame)
+    pub has_checksum: bool,
+    /// Number of concatenated frames (gzip members), skippable frames excluded
+    pub frame_count: usize,
+    /// Dictionary the frame was compressed with, if it names one
+    pub dictionary_id: Option<u32>,
+}
+
+/// Reports the window or block size, checksum, frame count and dictionary ID recorded in
+/// `data`, compressed with `algorithm`. Identity data is reported as a single frame.
+///
+/// Fails with `DecompressionError` if the frame headers are malformed or truncated.
+pub fn inspect(
+    data: &[u8],
+    algorithm: CompressionAlgorithm,
+) -> Result<CompressionInfo, CompressorError> {
+    let info = |window_size, has_checksum, frame_count| CompressionInfo {
+        algorithm,
+        window_size,
+        has_checksum,
+        frame_count,
+        dictionary_id: None,
+    };
+
+    match algorithm {
+        CompressionAlgorithm::Identity => Ok(info(None, false, 1)),
+        // Every data chunk carries a masked CRC32C of its content
+        CompressionAlgorithm::Snappy => Ok(info(
+            Some(SNAPPY_MAX_CHUNK_LEN),
+            true,
+            frame_count(data, algorithm)?,
+        )),
+        // The member trailer always holds a CRC32 of the content
+        #[cfg(feature = "gzip")]
+        CompressionAlgorithm::Gzip => Ok(info(
+            Some(DEFLATE_WINDOW),
+            true,
+            frame_count(data, algorithm)?,
+        )),
+        #[cfg(feature = "zstd")]
+        CompressionAlgorithm::Zstd => inspect_zstd(data),
+        #[cfg(feature = "lz4")]
+        CompressionAlgorithm::Lz4 => inspect_lz4(data),
+    }
+}
+
+#[cfg(any(feature = "zstd", feature = "lz4"))]
+fn malformed(format: &str, what: &str) -> CompressorError {
+    CompressorError::DecompressionError(format!("malformed {} frame: {}", format, what))
+}
+
+/// Number of content frames in `data`, skippable zstd frames excluded
+fn frame_count(data: &[u8], algorithm: CompressionAlgorithm) -> Result<usize, CompressorError> {
+    let mut frames = 0;
+    let mut remaining = data;
+
+    while !remaining.is_empty() {
+        let (frame_len, has_content) = frame_len(remaining, algorithm)?;
+        remaining = &remaining[frame_len..];
+        frames += usize::from(has_content);
+    }
+
+    Ok(frames)
+}
+
+/// Byte length of the frame at the start of `data` (gzip member, identity data as a whole)
+/// and whether it holds content, which zstd skippable frames do not
+pub(crate) fn frame_len(
+    data: &[u8],
+    algorithm: CompressionAlgorithm,
+) -> Result<(usize, bool), CompressorError> {
+    match algorithm {
+        CompressionAlgorithm::Identity => Ok((data.len(), true)),
+        CompressionAlgorithm::Snappy => Ok((SnappyCompressor.frame_len(data)?, true)),
+        #[cfg(feature = "gzip")]
+        CompressionAlgorithm::Gzip => {
+            // The buffered decoder stops right after the member's trailer
+            let mut decoder = flate2::bufread::GzDecoder::new(data);
+            std::io::copy(&mut decoder, &mut std::io::sink())
+                .map_err(|err| CompressorError::DecompressionError(err.to_string()))?;
+            Ok((data.len() - decoder.into_inner().len(), true))
+        }
+        #[cfg(feature = "zstd")]
+        CompressionAlgorithm::Zstd => {
+            let frame_len = zstd::zstd_safe::find_frame_compressed_size(data)
+                .map_err(|code| malformed("zstd", zstd::zstd_safe::get_error_name(code)))?;
+            // Skippable frames use magic numbers 0x184D2A50..=0x184D2A5F
+            Ok((
+                frame_len,
+                data.len() < 4 || read_le(&data[..4]) >> 4 != 0x184D2A5,
+            ))
+        }
+        #[cfg(feature = "lz4")]
+        CompressionAlgorithm::Lz4 => Ok((lz4_frame(data)?.0, true)),
+    }
+}
+
+#[cfg(any(feature = "zstd", feature = "lz4"))]
+fn read_le(bytes: &[u8]) -> u64 {
+    bytes
+        .iter()
+        .rev()
+        .fold(0, |value, &byte| value << 8 | u64::from(byte))
+}
+
+/// Parses the frame header described in RFC 8878, section 3.1.1.1
+#[cfg(feature = "zstd")]
+fn inspect_zstd(data: &[u8]) -> Result<CompressionInfo, CompressorError> {
+    let truncated = || malformed("zstd", "truncated header");
+    if data.len() < 5 || read_le(&data[..4]) != u64::from(ZSTD_MAGIC) {
+        return Err(malformed("zstd", "missing magic number"));
+    }
+
+    let descriptor = data[4];
+    let single_segment = descriptor & 0x20 != 0;
+    let has_checksum = descriptor & 0x04 != 0;
+    let dictionary_id_len = [0, 1, 2, 4][usize::from(descriptor & 0x03)];
+    let content_size_len = match descriptor >> 6 {
+        0 if single_segment => 1,
+        0 => 0,
+        flag => 1 << flag,
+    };
+
+    let mut offset = 5;
+    let mut window_size = None;
+    if !single_segment {
+        let window_descriptor = *data.get(offset).ok_or_else(truncated)?;
+        let base = 1u64 << (10 + (window_descriptor >> 3));
+        window_size = Some(base + base / 8 * u64::from(window_descriptor & 0x07));
+        offset += 1;
+    }
+
+    let dictionary_id = data
+        .get(offset..offset + dictionary_id_len)
+        .ok_or_else(truncated)?;
+    let dictionary_id = (dictionary_id_len > 0).then(|| read_le(dictionary_id) as u32);
+    offset += dictionary_id_len;
+
+    let content_size = data
+        .get(offset..offset + content_size_len)
+        .ok_or_else(truncated)?;
+    if single_segment {
+        // The window spans the whole content; 2-byte sizes are stored minus 256
+        let bias = if content_size_len == 2 { 256 } else { 0 };
+        window_size = Some(read_le(content_size) + bias);
+    }
+
+    Ok(CompressionInfo {
+        algorithm: CompressionAlgorithm::Zstd,
+        window_size,
+        has_checksum,
+        frame_count: frame_count(data, CompressionAlgorithm::Zstd)?,
+        dictionary_id: dictionary_id.filter(|&id| id != 0),
+    })
+}
+
+#[cfg(feature = "lz4")]
+fn inspect_lz4(data: &[u8]) -> Result<CompressionInfo, CompressorError> {
+    let mut first = None;
+    let mut frame_count = 0;
+    let mut remaining = data;
+
+    while !remaining.is_empty() {
+        let (frame_len, info) = lz4_frame(remaining)?;
+        first.get_or_insert(info);
+        frame_count += 1;
+        remaining = &remaining[frame_len..];
+    }
+
+    let info = first.ok_or_else(|| malformed("lz4", "truncated frame"))?;
+    Ok(CompressionInfo {
+        frame_count,
+        ..info
+    })
+}
+
+/// Parses the frame header and walks the blocks of the frame at the start of `data`, as
+/// laid out in the LZ4 frame format specification, returning its length and parameters
+#[cfg(feature = "lz4")]
+fn lz4_frame(data: &[u8]) -> Result<(usize, CompressionInfo), CompressorError> {
+    let truncated = || malformed("lz4", "truncated frame");
+    let header = data.get(..6).ok_or_else(truncated)?;
+    if read_le(&header[..4]) != u64::from(LZ4_MAGIC) {
+        return Err(malformed("lz4", "missing magic number"));
+    }
+
+    let flags = header[4];
+    let block_checksum = flags & 0x10 != 0;
+    let content_checksum = flags & 0x04 != 0;
+    let has_dictionary_id = flags & 0x01 != 0;
+    let block_size = match (header[5] >> 4) & 0x07 {
+        4 => 64 << 10,
+        5 => 256 << 10,
+        6 => 1 << 20,
+        7 => 4 << 20,
+        _ => return Err(malformed("lz4", "invalid block size")),
+    };
+
+    let mut offset = 6;
+    if flags & 0x08 != 0 {
+        offset += 8;
+    }
+    let dictionary_id = if has_dictionary_id {
+        let id = data.get(offset..offset + 4).ok_or_else(truncated)?;
+        offset += 4;
+        Some(read_le(id) as u32)
+    } else {
+        None
+    };
+    // Header checksum
+    offset += 1;
+
+    loop {
+        let block = data.get(offset..offset + 4).ok_or_else(truncated)?;
+        let block_len = (read_le(block) & 0x7FFF_FFFF) as usize;
+        offset += 4;
+        if block_len == 0 {
+            break;
+        }
+        offset += block_len + if block_checksum { 4 } else { 0 };
+    }
+    if content_checksum {
+        offset += 4;
+    }
+    if offset > data.len() {
+        return Err(truncated());
+    }
+
+    Ok((
+        offset,
+        CompressionInfo {
+            algorithm: CompressionAlgorithm::Lz4,
+            window_size: Some(block_size),
+            has_checksum: block_checksum || content_checksum,
+            frame_count: 1,
+            dictionary_id,
+        },
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compressors::TCompressor;
+
+    fn sample() -> Vec<u8> {
+        br#"{"inspect":"frame headers","values":[1,2,3]}"#.repeat(2000)
+    }
+
+    #[test]
+    fn test_inspect_snappy() {
+        let compressed = SnappyCompressor.compress(&sample()).unwrap();
+        let info = inspect(&compressed, CompressionAlgorithm::Snappy).unwrap();
+
+        assert!(info.has_checksum);
+        assert_eq!(info.window_size, Some(65536));
+        assert_eq!(info.frame_count, 1);
+        assert_eq!(info.dictionary_id, None);
+
+        let mut concatenated = compressed.clone();
+        concatenated.extend_from_slice(&compressed);
+        let info = inspect(&concatenated, CompressionAlgorithm::Snappy).unwrap();
+        assert_eq!(info.frame_count, 2);
+
+        assert!(inspect(b"not snappy", CompressionAlgorithm::Snappy).is_err());
+    }
+
+    #[test]
+    fn test_inspect_identity() {
+        let info = inspect(b"raw", CompressionAlgorithm::Identity).unwrap();
+        assert_eq!(info.frame_count, 1);
+        assert!(!info.has_checksum);
+        assert_eq!(info.window_size, None);
+    }
+
+    #[cfg(feature = "zstd")]
+    #[test]
+    fn test_inspect_zstd() {
+        use crate::compressors::ZstdCompressor;
+
+        let compressed = ZstdCompressor::new(3).compress(&sample()).unwrap();
+        let info = inspect(&compressed, CompressionAlgorithm::Zstd).unwrap();
+
+        // Streamed without a known size, so the window comes from the level's parameters
+        let window = info.window_size.unwrap();
+        assert!(window.is_power_of_two());
+        assert!((1 << 17..=1 << 23).contains(&window), "{}", window);
+        assert_eq!(info.frame_count, 1);
+        assert_eq!(info.dictionary_id, None);
+
+        let single_segment = zstd::bulk::compress(b"tiny", 3).unwrap();
+        let info = inspect(&single_segment, CompressionAlgorithm::Zstd).unwrap();
+        assert_eq!(info.window_size, Some(4));
+
+        let mut concatenated = compressed.clone();
+        concatenated.extend_from_slice(&single_segment);
+        let info = inspect(&concatenated, CompressionAlgorithm::Zstd).unwrap();
+        assert_eq!(info.frame_count, 2);
+
+        assert!(inspect(&compressed[..5], CompressionAlgorithm::Zstd).is_err());
+    }
+
+    #[cfg(feature = "gzip")]
+    #[test]
+    fn test_inspect_gzip() {
+        use crate::compressors::GzipCompressor;
+
+        let member = GzipCompressor::default().compress(&sample()).unwrap();
+        let info = inspect(&member.repeat(3), CompressionAlgorithm::Gzip).unwrap();
+        assert_eq!(info.window_size, Some(32768));
+        assert!(info.has_checksum);
+        assert_eq!(info.frame_count, 3);
+    }
+
+    #[cfg(feature = "lz4")]
+    #[test]
+    fn test_inspect_lz4() {
+        use crate::compressors::Lz4Compressor;
+
+        let compressed = Lz4Compressor::new().compress(&sample()).unwrap();
+        let info = inspect(&compressed, CompressionAlgorithm::Lz4).unwrap();
+        assert!(info.window_size.is_some());
+        assert!(info.has_checksum);
+        assert_eq!(info.frame_count, 1);
+
+        let info = inspect(&compressed.repeat(2), CompressionAlgorithm::Lz4).unwrap();
+        assert_eq!(info.frame_count, 2);
+        assert!(inspect(
+            &compressed[..compressed.len() - 2],
+            CompressionAlgorithm::Lz4
+        )
+        .is_err());
+    }
+}
@@ -1,74 +1,519 @@
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+
+extern crate alloc;
+
+#[cfg(all(
+    feature = "std",
+    feature = "default-serde-json",
+    feature = "default-serde-bincode"
+))]
+compile_error!(
+    "features `default-serde-json` and `default-serde-bincode` are mutually exclusive; \
+     disable default features to use `default-serde-bincode`"
+);
+#[cfg(all(
+    feature = "std",
+    not(any(feature = "default-serde-json", feature = "default-serde-bincode"))
+))]
+compile_error!(
+    "`std` requires one of the features `default-serde-json` or `default-serde-bincode`"
+);
+
+#[cfg(feature = "std")]
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+#[cfg(feature = "std")]
 use serde::{de::DeserializeOwned, Serialize};
+#[cfg(feature = "std")]
+use std::io::Read;
 
+#[cfg(feature = "std")]
+pub mod archive;
+#[cfg(feature = "std")]
+pub mod armor;
+pub mod chunking;
 pub mod compressors;
+#[cfg(feature = "std")]
+pub mod diagnostic;
+#[cfg(feature = "zstd")]
+pub mod dictionary;
+#[cfg(feature = "std")]
+pub mod envelope;
 pub mod error;
+#[cfg(feature = "std")]
+pub mod facade;
+#[cfg(feature = "std")]
+pub mod frames;
+#[cfg(feature = "std")]
+pub mod inspect;
+#[cfg(feature = "mmap")]
+pub mod mmap;
+#[cfg(feature = "std")]
+pub mod options;
+#[cfg(feature = "prost")]
+pub mod proto;
+#[cfg(feature = "std")]
+pub mod serializer;
+#[cfg(feature = "std")]
+pub mod stream;
+#[cfg(feature = "std")]
+pub mod tagged;
+#[cfg(feature = "std")]
+mod trace;
 
+#[cfg(feature = "std")]
+pub use archive::{compress_directory, extract_directory, Archive};
+#[cfg(feature = "std")]
+pub use armor::{armor, dearmor};
+pub use chunking::chunk_content_defined;
+#[cfg(feature = "aead")]
+pub use compressors::EncryptedCompressor;
+#[cfg(feature = "gzip")]
+pub use compressors::GzipCompressor;
+#[cfg(feature = "lz4")]
+pub use compressors::Lz4Compressor;
+#[cfg(feature = "std")]
+pub use compressors::{
+    snappy_raw_compress, snappy_raw_decompress, snappy_raw_decompress_auto,
+    ChunkedSnappyCompressor, CompressorFactory, DefaultCompressor, PooledCompressor,
+    SnappyBodyCompressor, SnappyCompressor, SnappyDecoder, TEncoder,
+};
 pub use compressors::{
-    CompressionAlgorithm, CompressorFactory, DefaultCompressor, SnappyCompressor, TCompressor,
+    ChainCompressor, CheckedCompressor, CompressionAlgorithm, CompressionStats, IdentityCompressor,
+    InstrumentedCompressor, PaddedCompressor, ResilientCompressor, SnappyRawCompressor,
+    TCompressor,
+};
+#[cfg(feature = "zstd")]
+pub use compressors::{ZstdCompressor, ZstdDictionaryCompressor};
+#[cfg(feature = "std")]
+pub use diagnostic::{
+    assert_equivalent, calibrate_costs, detect_compressed, estimate_entropy, is_likely_compressed,
+    ratio_report, select_fastest_meeting_ratio, self_test, CompressDiagnostic,
+};
+#[cfg(feature = "zstd")]
+pub use dictionary::DictionaryTrainer;
+#[cfg(feature = "std")]
+pub use envelope::{decode_envelope, encode_envelope};
+pub use error::{CompressorError, ErrorKind};
+#[cfg(feature = "std")]
+pub use facade::Compressor;
+#[cfg(feature = "std")]
+pub use frames::{
+    append_compressed_record, append_frame, decompress_concatenated, frames,
+    read_compressed_records,
+};
+#[cfg(feature = "std")]
+pub use inspect::{inspect, CompressionInfo};
+#[cfg(feature = "mmap")]
+pub use mmap::{compress_mmap, compress_mmap_to};
+#[cfg(feature = "std")]
+pub use options::{
+    compress_to_fit, compress_with_policy, CompressionOptions, CompressionPolicy, CompressorCache,
+};
+#[cfg(feature = "prost")]
+pub use proto::{compress_proto, decompress_proto};
+#[cfg(feature = "std")]
+pub use serializer::{FiniteJson, JsonLimits, SerdeFormat, Serializer};
+#[cfg(feature = "sha2")]
+pub use stream::decompress_and_hash;
+#[cfg(feature = "std")]
+pub use stream::{
+    compress_serialize_stream, compress_stream_with_stats, decompress_lines, decompress_stream,
+    decompress_to_channel, decompress_windowed, pipe_compress, pipe_decompress,
+    BoundedStreamingCompressor, CompressReader, DecompressReader, RotatingCompressWriter,
+    StreamingCompressor,
+};
+#[cfg(feature = "std")]
+pub use tagged::{
+    compress_batch_adaptive, compress_tagged, compress_tagged_with_fallback, decompress_detailed,
+    decompress_tagged, decompress_tagged_to, decompress_tagged_with_algorithm, retag_legacy,
+    scan_algorithms, to_vec_with_header, CompressionReport, DecompressResult, TaggedHeader,
 };
-pub use error::CompressorError;
 
-/// A trait for types that can be compressed and decompressed
+/// A trait for types that can be compressed and decompressed.
+///
+/// Methods that take no serializer use `Serializer::DEFAULT`: JSON, or bincode with the
+/// `default-serde-bincode` feature. The exceptions are JSON-only whatever the default:
+/// `compress_streaming`, `decompress_with_limits` and `decompress_strict_schema` (and
+/// `decompress_dispatch`). With `default-serde-bincode` they cannot read what
+/// `compress_with_algorithm` writes, so pair them with each other or with `Serializer::Json`.
+///
+/// Time types round-trip losslessly with either: `chrono`'s `DateTime` serializes as
+/// RFC 3339 text with nanoseconds and its UTC offset, `NaiveDate` as `YYYY-MM-DD`, and
+/// `std::time::Duration` as whole seconds plus nanoseconds.
+#[cfg(feature = "std")]
 pub trait TCompressible: Serialize + DeserializeOwned {
     fn compress(&self) -> Result<Vec<u8>, CompressorError> {
-        let serialized = serde_json::to_vec(self)
-            .map_err(|err| CompressorError::SerializationError(err.to_string()))?;
+        let serialized = Serializer::DEFAULT.serialize(self)?;
 
         let compressor = SnappyCompressor;
-        compressor.compress(&serialized)
+        trace::traced(
+            "compress",
+            Some(CompressionAlgorithm::Snappy),
+            serialized.len(),
+            || compressor.compress(&serialized),
+        )
     }
 
     fn decompress(compressed: &[u8]) -> Result<Self, CompressorError> {
         let compressor = SnappyCompressor;
-        let decompressed = compressor.decompress(compressed)?;
+        let decompressed = trace::traced(
+            "decompress",
+            Some(CompressionAlgorithm::Snappy),
+            compressed.len(),
+            || compressor.decompress(compressed),
+        )?;
 
-        serde_json::from_slice(&decompressed)
-            .map_err(|err| CompressorError::DeserializationError(err.to_string()))
+        Serializer::DEFAULT.deserialize(&decompressed)
     }
 
     fn compress_with<C: TCompressor>(&self, compressor: &C) -> Result<Vec<u8>, CompressorError> {
-        let serialized = serde_json::to_vec(self)
-            .map_err(|err| CompressorError::SerializationError(err.to_string()))?;
+        let serialized = Serializer::DEFAULT.serialize(self)?;
 
-        compressor.compress(&serialized)
+        trace::traced("compress", None, serialized.len(), || {
+            compressor.compress(&serialized)
+        })
     }
 
     fn decompress_with<C: TCompressor>(
         compressed: &[u8],
         compressor: &C,
     ) -> Result<Self, CompressorError> {
-        let decompressed = compressor.decompress(compressed)?;
+        let decompressed = trace::traced("decompress", None, compressed.len(), || {
+            compressor.decompress(compressed)
+        })?;
 
-        serde_json::from_slice(&decompressed)
-            .map_err(|err| CompressorError::DeserializationError(err.to_string()))
+        Serializer::DEFAULT.deserialize(&decompressed)
     }
 
     fn compress_with_algorithm(
         &self,
         algorithm: CompressionAlgorithm,
     ) -> Result<Vec<u8>, CompressorError> {
+        compress_serializable(self, algorithm)
+    }
+
+    fn decompress_with_algorithm(
+        compressed: &[u8],
+        algorithm: CompressionAlgorithm,
+    ) -> Result<Self, CompressorError> {
+        let compressor = CompressorFactory::get_compressor(algorithm);
+        let decompressed = compressor
+            .decompress(compressed)
+            .map_err(|err| err.with_algorithm(algorithm))?;
+
+        Serializer::DEFAULT.deserialize(&decompressed)
+    }
+
+    /// `compress_with_algorithm` without the intermediate JSON buffer: the value is
+    /// serialized straight into the compressor's encoder, so large fields are never held
+    /// in memory both serialized and compressed. The output is always JSON, whichever
+    /// serializer is the default.
+    fn compress_streaming(
+        &self,
+        algorithm: CompressionAlgorithm,
+    ) -> Result<Vec<u8>, CompressorError> {
+        stream::compress_serialize_stream(self, algorithm)
+    }
+
+    /// Decompresses untrusted JSON: decompression stops as soon as the output grows past
+    /// `limits.max_size`, and JSON nested deeper than `limits.max_depth` is rejected with a
+    /// `DeserializationError` before it is deserialized. Always reads JSON, see the
+    /// trait documentation.
+    fn decompress_with_limits(
+        compressed: &[u8],
+        algorithm: CompressionAlgorithm,
+        limits: JsonLimits,
+    ) -> Result<Self, CompressorError> {
+        let compressor = CompressorFactory::get_compressor(algorithm);
+        let decoder = compressor
+            .decoder(Box::new(compressed))
+            .map_err(|err| err.with_algorithm(algorithm))?;
+
+        let mut decompressed = Vec::new();
+        decoder
+            .take((limits.max_size as u64).saturating_add(1))
+            .read_to_end(&mut decompressed)
+            .map_err(|err| {
+                CompressorError::DecompressionError(err.to_string()).with_algorithm(algorithm)
+            })?;
+        if decompressed.len() > limits.max_size {
+            return Err(CompressorError::DecompressionError(format!(
+                "decompressed size exceeds the limit of {} bytes",
+                limits.max_size
+            )));
+        }
+
+        serializer::check_json_depth(&decompressed, limits.max_depth)?;
+        serializer::json_from_slice(&decompressed)
+    }
+
+    /// Decompresses JSON, rejecting fields `Self` does not declare as if it were marked
+    /// `#[serde(deny_unknown_fields)]`, to catch schema drift between writer and reader.
+    /// The `DeserializationError` names the path of the first unknown field. Always reads
+    /// JSON, see the trait documentation.
+    fn decompress_strict_schema(
+        compressed: &[u8],
+        algorithm: CompressionAlgorithm,
+    ) -> Result<Self, CompressorError> {
+        let decompressed = CompressorFactory::get_compressor(algorithm)
+            .decompress(compressed)
+            .map_err(|err| err.with_algorithm(algorithm))?;
+
+        let (value, unknown_field) = serializer::json_from_slice_reporting_unknown(&decompressed)?;
+        match unknown_field {
+            Some(field) => Err(CompressorError::DeserializationError(format!(
+                "unknown field '{}'",
+                field
+            ))),
+            None => Ok(value),
+        }
+    }
+
+    /// Serializes with any `SerdeFormat` and compresses with any `TCompressor`
+    fn compress_generic<F: SerdeFormat, C: TCompressor>(
+        &self,
+        format: &F,
+        compressor: &C,
+    ) -> Result<Vec<u8>, CompressorError> {
+        let serialized = format.serialize(self)?;
+
+        trace::traced("compress", None, serialized.len(), || {
+            compressor.compress(&serialized)
+        })
+    }
+
+    fn decompress_generic<F: SerdeFormat, C: TCompressor>(
+        compressed: &[u8],
+        format: &F,
+        compressor: &C,
+    ) -> Result<Self, CompressorError> {
+        let decompressed = trace::traced("decompress", None, compressed.len(), || {
+            compressor.decompress(compressed)
+        })?;
+
+        format.deserialize(&decompressed)
+    }
+
+    /// Serializes with `serializer` and compresses with `algorithm` into a self-describing
+    /// blob that `decompress_tagged_to` reads back without either being specified
+    fn to_vec_with_header(
+        &self,
+        serializer: Serializer,
+        algorithm: CompressionAlgorithm,
+    ) -> Result<Vec<u8>, CompressorError> {
+        tagged::to_vec_with_header(self, serializer, algorithm)
+    }
+
+    /// Compresses with `algorithm` and encodes the result as URL-safe base64 without padding
+    fn compress_to_base64(
+        &self,
+        algorithm: CompressionAlgorithm,
+    ) -> Result<String, CompressorError> {
+        let compressed = self.compress_with_algorithm(algorithm)?;
+
+        Ok(URL_SAFE_NO_PAD.encode(compressed))
+    }
+
+    fn decompress_from_base64(
+        encoded: &str,
+        algorithm: CompressionAlgorithm,
+    ) -> Result<Self, CompressorError> {
+        let compressed = URL_SAFE_NO_PAD
+            .decode(encoded)
+            .map_err(|err| CompressorError::DecodingError(format!("invalid base64: {}", err)))?;
+
+        Self::decompress_with_algorithm(&compressed, algorithm)
+    }
+
+    /// Compresses with `algorithm` and wraps the result in PEM-like ASCII armor, see
+    /// `armor::armor`
+    fn compress_armored(&self, algorithm: CompressionAlgorithm) -> Result<String, CompressorError> {
+        Ok(armor::armor(&self.compress_with_algorithm(algorithm)?))
+    }
+
+    /// Parses the first armored block in `text`, tolerating surrounding text and any line
+    /// wrapping, and decompresses it
+    fn decompress_armored(
+        text: &str,
+        algorithm: CompressionAlgorithm,
+    ) -> Result<Self, CompressorError> {
+        Self::decompress_with_algorithm(&armor::dearmor(text)?, algorithm)
+    }
+
+    /// Serializes with bincode instead of JSON before compressing, avoiding the JSON array
+    /// encoding of byte fields. The output starts with a serializer tag byte so that
+    /// `decompress_binary` knows how to deserialize it.
+    #[cfg(feature = "bincode")]
+    fn compress_binary(&self, algorithm: CompressionAlgorithm) -> Result<Vec<u8>, CompressorError> {
+        let serializer = Serializer::Bincode;
         let compressor = CompressorFactory::get_compressor(algorithm);
-        let serialized = serde_json::to_vec(self)
+        let serialized = serializer.serialize(self)?;
+
+        let compressed = compressor
+            .compress(&serialized)
+            .map_err(|err| err.with_algorithm(algorithm))?;
+
+        let mut tagged = Vec::with_capacity(compressed.len() + 1);
+        tagged.push(serializer.tag());
+        tagged.extend_from_slice(&compressed);
+        Ok(tagged)
+    }
+
+    #[cfg(feature = "bincode")]
+    fn decompress_binary(
+        data: &[u8],
+        algorithm: CompressionAlgorithm,
+    ) -> Result<Self, CompressorError> {
+        let (&tag, compressed) = data.split_first().ok_or_else(|| {
+            CompressorError::DeserializationError("missing serializer tag".to_string())
+        })?;
+        let serializer = Serializer::from_tag(tag)?;
+
+        let compressor = CompressorFactory::get_compressor(algorithm);
+        let decompressed = compressor
+            .decompress(compressed)
+            .map_err(|err| err.with_algorithm(algorithm))?;
+
+        serializer.deserialize(&decompressed)
+    }
+
+    /// Serializes with postcard instead of JSON before compressing with `algorithm`
+    #[cfg(feature = "postcard")]
+    fn compress_postcard(
+        &self,
+        algorithm: CompressionAlgorithm,
+    ) -> Result<Vec<u8>, CompressorError> {
+        let compressor = CompressorFactory::get_compressor(algorithm);
+        let serialized = postcard::to_allocvec(self)
             .map_err(|err| CompressorError::SerializationError(err.to_string()))?;
 
-        compressor.compress(&serialized)
+        compressor
+            .compress(&serialized)
+            .map_err(|err| err.with_algorithm(algorithm))
     }
 
-    fn decompress_with_algorithm(
+    #[cfg(feature = "postcard")]
+    fn decompress_postcard(
         compressed: &[u8],
         algorithm: CompressionAlgorithm,
     ) -> Result<Self, CompressorError> {
         let compressor = CompressorFactory::get_compressor(algorithm);
-        let decompressed = compressor.decompress(compressed)?;
+        let decompressed = compressor
+            .decompress(compressed)
+            .map_err(|err| err.with_algorithm(algorithm))?;
 
-        serde_json::from_slice(&decompressed)
+        postcard::from_bytes(&decompressed)
             .map_err(|err| CompressorError::DeserializationError(err.to_string()))
     }
 }
 
+#[cfg(feature = "std")]
 impl<T: Serialize + DeserializeOwned> TCompressible for T {}
 
+/// Compression of raw bytes as they are. `TCompressible` would serialize a `Vec<u8>`
+/// as a JSON array of numbers first.
+#[cfg(feature = "std")]
+pub trait BytesCompressExt: AsRef<[u8]> {
+    fn compress_bytes(&self, algorithm: CompressionAlgorithm) -> Result<Vec<u8>, CompressorError> {
+        CompressorFactory::get_compressor(algorithm)
+            .compress(self.as_ref())
+            .map_err(|err| err.with_algorithm(algorithm))
+    }
+
+    fn decompress_bytes(
+        compressed: &[u8],
+        algorithm: CompressionAlgorithm,
+    ) -> Result<Vec<u8>, CompressorError> {
+        CompressorFactory::get_compressor(algorithm)
+            .decompress(compressed)
+            .map_err(|err| err.with_algorithm(algorithm))
+    }
+}
+
+#[cfg(feature = "std")]
+impl BytesCompressExt for [u8] {}
+
+#[cfg(feature = "std")]
+impl BytesCompressExt for Vec<u8> {}
+
+/// Serializes `value` with `Serializer::DEFAULT` and compresses it with `algorithm`.
+///
+/// Unlike `TCompressible::compress_with_algorithm` this only needs `Serialize`, so it
+/// also accepts borrowed views and other types that cannot be deserialized.
+#[cfg(feature = "std")]
+pub fn compress_serializable<T: Serialize + ?Sized>(
+    value: &T,
+    algorithm: CompressionAlgorithm,
+) -> Result<Vec<u8>, CompressorError> {
+    compress_prepared(&Serializer::DEFAULT.serialize(value)?, algorithm)
+}
+
+/// Compresses bytes that are already serialized, e.g. by an earlier pipeline stage, with
+/// `algorithm`. For bytes serialized with `Serializer::DEFAULT` the output equals what
+/// `TCompressible::compress_with_algorithm` produces for the value.
+#[cfg(feature = "std")]
+pub fn compress_prepared(
+    serialized: &[u8],
+    algorithm: CompressionAlgorithm,
+) -> Result<Vec<u8>, CompressorError> {
+    CompressorFactory::get_compressor(algorithm)
+        .compress(serialized)
+        .map_err(|err| err.with_algorithm(algorithm))
+}
+
+/// Decompresses the output of `compress_prepared` or `TCompressible::compress_with_algorithm`
+/// back to the serialized bytes, leaving deserialization to the caller
+#[cfg(feature = "std")]
+pub fn decompress_to_bytes(
+    compressed: &[u8],
+    algorithm: CompressionAlgorithm,
+) -> Result<Vec<u8>, CompressorError> {
+    CompressorFactory::get_compressor(algorithm)
+        .decompress(compressed)
+        .map_err(|err| err.with_algorithm(algorithm))
+}
+
+/// Deserializer chosen by the `chooser` of `decompress_dispatch`
+#[cfg(feature = "std")]
+pub type DispatchFn<T> = Box<dyn FnOnce(serde_json::Value) -> Result<T, CompressorError>>;
+
+/// Decompresses JSON and lets `chooser` inspect it, e.g. its `type` field, to pick the
+/// deserializer that turns it into a `T`, typically one variant of a message enum.
+///
+/// The JSON is parsed once into a `serde_json::Value`, which the chosen deserializer
+/// receives by value. Input is always JSON, whatever `Serializer::DEFAULT` is.
+#[cfg(feature = "std")]
+pub fn decompress_dispatch<T, F>(
+    compressed: &[u8],
+    algorithm: CompressionAlgorithm,
+    chooser: F,
+) -> Result<T, CompressorError>
+where
+    F: Fn(&serde_json::Value) -> DispatchFn<T>,
+{
+    let decompressed = CompressorFactory::get_compressor(algorithm)
+        .decompress(compressed)
+        .map_err(|err| err.with_algorithm(algorithm))?;
+    let value: serde_json::Value = serializer::json_from_slice(&decompressed)?;
+
+    chooser(&value)(value)
+}
+
+/// Deterministic xorshift noise for test fixtures; incompressible for any nonzero `seed`
 #[cfg(test)]
+pub(crate) fn noise(seed: u64, len: usize) -> alloc::vec::Vec<u8> {
+    let mut state = seed;
+    (0..len)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state as u8
+        })
+        .collect()
+}
+
+#[cfg(all(test, feature = "std"))]
 mod tests {
     use super::*;
     use serde::{Deserialize, Serialize};
@@ -107,6 +552,101 @@ mod tests {
         assert_eq!(test_struct, decompressed);
     }
 
+    /// Pretty-printed JSON, standing in for any user-provided format
+    struct PrettyJson;
+
+    impl SerdeFormat for PrettyJson {
+        fn serialize<T: Serialize + ?Sized>(&self, value: &T) -> Result<Vec<u8>, CompressorError> {
+            serde_json::to_vec_pretty(value)
+                .map_err(|err| CompressorError::SerializationError(err.to_string()))
+        }
+
+        fn deserialize<T: DeserializeOwned>(&self, bytes: &[u8]) -> Result<T, CompressorError> {
+            serde_json::from_slice(bytes)
+                .map_err(|err| CompressorError::DeserializationError(err.to_string()))
+        }
+    }
+
+    #[test]
+    fn test_generic_format() {
+        let test_struct = TestStruct {
+            field1: "Generic".to_string(),
+            field2: 7,
+        };
+
+        let compressor = SnappyCompressor;
+        let compressed = test_struct
+            .compress_generic(&PrettyJson, &compressor)
+            .unwrap();
+        assert!(compressor.decompress(&compressed).unwrap().contains(&b'\n'));
+
+        let decompressed: TestStruct =
+            TCompressible::decompress_generic(&compressed, &PrettyJson, &compressor).unwrap();
+        assert_eq!(test_struct, decompressed);
+
+        let compressed = test_struct
+            .compress_generic(&Serializer::DEFAULT, &compressor)
+            .unwrap();
+        assert_eq!(compressed, test_struct.compress_with(&compressor).unwrap());
+    }
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Reading {
+        sensor: String,
+        samples: Vec<f64>,
+    }
+
+    #[test]
+    fn test_non_finite_floats() {
+        let compressor = SnappyCompressor;
+        let reading = Reading {
+            sensor: "probe".to_string(),
+            samples: vec![0.5, f64::NAN, 1.5],
+        };
+
+        // Plain JSON silently writes null, which then fails to read back as a float
+        let lossy = reading
+            .compress_generic(&Serializer::Json, &compressor)
+            .unwrap();
+        assert!(Reading::decompress_generic(&lossy, &Serializer::Json, &compressor).is_err());
+
+        let err = reading
+            .compress_generic(&FiniteJson, &compressor)
+            .unwrap_err();
+        assert!(matches!(
+            &err,
+            CompressorError::SerializationError(message)
+                if message == "non-finite float NaN at 'samples[1]' cannot be represented in JSON"
+        ));
+        let nested = std::collections::HashMap::from([("max".to_string(), f64::INFINITY)]);
+        assert!(matches!(
+            vec![nested].compress_generic(&FiniteJson, &compressor),
+            Err(CompressorError::SerializationError(message)) if message.contains("'[0].max'")
+        ));
+
+        let finite = Reading {
+            sensor: "probe".to_string(),
+            samples: vec![0.5, -0.0, f64::MAX],
+        };
+        let compressed = finite.compress_generic(&FiniteJson, &compressor).unwrap();
+        assert_eq!(
+            Reading::decompress_generic(&compressed, &FiniteJson, &compressor).unwrap(),
+            finite
+        );
+
+        // Bincode keeps the bits of non-finite floats
+        #[cfg(feature = "bincode")]
+        {
+            let compressed = reading
+                .compress_generic(&Serializer::Bincode, &compressor)
+                .unwrap();
+            let decompressed =
+                Reading::decompress_generic(&compressed, &Serializer::Bincode, &compressor)
+                    .unwrap();
+            assert!(decompressed.samples[1].is_nan());
+        }
+    }
+
     #[test]
     fn test_compression_with_algorithm() {
         let test_struct = TestStruct {
@@ -123,4 +663,776 @@ mod tests {
 
         assert_eq!(test_struct, decompressed);
     }
+
+    #[test]
+    fn test_base64_round_trip() {
+        let test_struct = TestStruct {
+            field1: "Base64".to_string(),
+            field2: 64,
+        };
+
+        let encoded = test_struct
+            .compress_to_base64(CompressionAlgorithm::Snappy)
+            .unwrap();
+        assert!(encoded
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_'));
+
+        let decoded: TestStruct =
+            TCompressible::decompress_from_base64(&encoded, CompressionAlgorithm::Snappy).unwrap();
+
+        assert_eq!(test_struct, decoded);
+    }
+
+    #[test]
+    fn test_base64_invalid_input() {
+        let result: Result<TestStruct, _> =
+            TCompressible::decompress_from_base64("not base64!", CompressionAlgorithm::Snappy);
+
+        assert!(matches!(result, Err(CompressorError::DecodingError(_))));
+    }
+
+    #[test]
+    fn test_time_types_round_trip() {
+        use chrono::{DateTime, FixedOffset, NaiveDate, TimeZone, Utc};
+        use std::time::Duration;
+
+        #[derive(Debug, Serialize, Deserialize, PartialEq)]
+        struct Event {
+            at: DateTime<Utc>,
+            local: DateTime<FixedOffset>,
+            day: NaiveDate,
+            took: Duration,
+        }
+
+        let event = Event {
+            at: Utc
+                .with_ymd_and_hms(2024, 2, 29, 23, 59, 59)
+                .unwrap()
+                .checked_add_signed(chrono::TimeDelta::nanoseconds(123_456_789))
+                .unwrap(),
+            local: FixedOffset::east_opt(5 * 3600 + 45 * 60)
+                .unwrap()
+                .with_ymd_and_hms(1969, 7, 20, 20, 17, 40)
+                .unwrap()
+                .checked_add_signed(chrono::TimeDelta::nanoseconds(1))
+                .unwrap(),
+            day: NaiveDate::from_ymd_opt(-44, 3, 15).unwrap(),
+            took: Duration::new(86_400 * 365, 999_999_999),
+        };
+
+        let decompressed: Event = TCompressible::decompress(&event.compress().unwrap()).unwrap();
+        assert_eq!(decompressed, event);
+        assert_eq!(decompressed.local.offset(), event.local.offset());
+
+        let compressed = event
+            .compress_generic(&Serializer::Json, &SnappyCompressor)
+            .unwrap();
+        let json: Event =
+            TCompressible::decompress_generic(&compressed, &Serializer::Json, &SnappyCompressor)
+                .unwrap();
+        assert_eq!(json, event);
+        let text = String::from_utf8(Serializer::Json.serialize(&event).unwrap()).unwrap();
+        assert!(text.contains("2024-02-29T23:59:59.123456789Z"), "{}", text);
+        assert!(text.contains("+05:45"), "{}", text);
+    }
+
+    #[cfg(feature = "arbitrary_precision")]
+    #[test]
+    fn test_arbitrary_precision_round_trip() {
+        #[derive(Debug, Serialize, Deserialize, PartialEq)]
+        struct Measurements {
+            whole: serde_json::Number,
+            large: serde_json::Number,
+            precise: serde_json::Number,
+        }
+
+        let json = r#"{"whole":1.0,"large":123456789012345678901234567890,"precise":0.10000000000000000000000000000001}"#;
+        let measurements: Measurements = serde_json::from_str(json).unwrap();
+
+        let compressed = measurements.compress().unwrap();
+        let decompressed: Measurements = TCompressible::decompress(&compressed).unwrap();
+
+        assert_eq!(measurements, decompressed);
+        assert_eq!(serde_json::to_string(&decompressed).unwrap(), json);
+    }
+
+    #[cfg(not(feature = "default-serde-bincode"))]
+    #[test]
+    fn test_flatten_round_trip() {
+        #[derive(Debug, Serialize, Deserialize, PartialEq)]
+        struct Metadata {
+            version: u32,
+            owner: String,
+        }
+
+        #[derive(Debug, Serialize, Deserialize, PartialEq)]
+        struct Document {
+            id: u64,
+            #[serde(flatten)]
+            metadata: Metadata,
+            #[serde(flatten)]
+            extra: std::collections::BTreeMap<String, serde_json::Value>,
+        }
+
+        let mut extra = std::collections::BTreeMap::new();
+        extra.insert("label".to_string(), serde_json::json!("draft"));
+        extra.insert("score".to_string(), serde_json::json!(0.5));
+        extra.insert("nested".to_string(), serde_json::json!({ "a": [1, 2, 3] }));
+        let document = Document {
+            id: 7,
+            metadata: Metadata {
+                version: 3,
+                owner: "bering".to_string(),
+            },
+            extra,
+        };
+
+        let compressed = document.compress().unwrap();
+        let decompressed: Document = TCompressible::decompress(&compressed).unwrap();
+
+        assert_eq!(document, decompressed);
+    }
+
+    #[cfg(not(feature = "default-serde-bincode"))]
+    #[test]
+    fn test_untagged_enum_round_trip() {
+        #[derive(Debug, Serialize, Deserialize, PartialEq)]
+        #[serde(untagged)]
+        enum Value {
+            Flag(bool),
+            Count(u64),
+            Ratio(f64),
+            Text(String),
+            Pair { left: i32, right: i32 },
+            List(Vec<Value>),
+        }
+
+        let values = vec![
+            Value::Flag(true),
+            Value::Count(u64::MAX),
+            Value::Ratio(-1.5),
+            Value::Text("untagged".to_string()),
+            Value::Pair { left: -1, right: 1 },
+            Value::List(vec![Value::Count(0), Value::Text(String::new())]),
+        ];
+
+        let compressed = values.compress().unwrap();
+        let decompressed: Vec<Value> = TCompressible::decompress(&compressed).unwrap();
+
+        assert_eq!(values, decompressed);
+    }
+
+    #[cfg(not(feature = "default-serde-bincode"))]
+    #[test]
+    fn test_tagged_enum_round_trip() {
+        #[derive(Debug, Serialize, Deserialize, PartialEq)]
+        enum External {
+            Unit,
+            Count(u64),
+            Pair { left: i32, ratio: f64 },
+        }
+
+        #[derive(Debug, Serialize, Deserialize, PartialEq)]
+        #[serde(tag = "t")]
+        enum Internal {
+            Unit,
+            Pair { left: i32, ratio: f64 },
+            Nested(External),
+        }
+
+        #[derive(Debug, Serialize, Deserialize, PartialEq)]
+        #[serde(tag = "t", content = "c")]
+        enum Adjacent {
+            Unit,
+            Count(u64),
+            Pair { left: i32, ratio: f64 },
+        }
+
+        #[derive(Debug, Serialize, Deserialize, PartialEq)]
+        struct Messages {
+            external: Vec<External>,
+            internal: Vec<Internal>,
+            adjacent: Vec<Adjacent>,
+        }
+
+        let messages = Messages {
+            external: vec![
+                External::Unit,
+                External::Count(u64::MAX),
+                External::Pair {
+                    left: -3,
+                    ratio: 0.25,
+                },
+            ],
+            internal: vec![
+                Internal::Unit,
+                Internal::Pair {
+                    left: i32::MIN,
+                    ratio: -1.5,
+                },
+                Internal::Nested(External::Count(7)),
+            ],
+            adjacent: vec![
+                Adjacent::Unit,
+                Adjacent::Count(0),
+                Adjacent::Pair {
+                    left: 1,
+                    ratio: 1e-9,
+                },
+            ],
+        };
+
+        for algorithm in [CompressionAlgorithm::Identity, CompressionAlgorithm::Snappy] {
+            let compressed = messages.compress_with_algorithm(algorithm).unwrap();
+            assert_eq!(
+                Messages::decompress_with_algorithm(&compressed, algorithm).unwrap(),
+                messages
+            );
+            assert_eq!(
+                Messages::decompress_with_limits(&compressed, algorithm, JsonLimits::default())
+                    .unwrap(),
+                messages
+            );
+            assert_eq!(
+                Messages::decompress_strict_schema(&compressed, algorithm).unwrap(),
+                messages
+            );
+
+            let streamed = messages.compress_streaming(algorithm).unwrap();
+            assert_eq!(
+                Messages::decompress_with_algorithm(&streamed, algorithm).unwrap(),
+                messages
+            );
+
+            let tagged = messages
+                .to_vec_with_header(Serializer::Json, algorithm)
+                .unwrap();
+            assert_eq!(decompress_tagged_to::<Messages>(&tagged).unwrap(), messages);
+        }
+    }
+
+    #[test]
+    fn test_decompress_with_limits() {
+        let limits = JsonLimits {
+            max_depth: 32,
+            max_size: 1 << 20,
+        };
+
+        let nested = format!("{}{}", "[".repeat(100_000), "]".repeat(100_000));
+        let compressed = SnappyCompressor.compress(nested.as_bytes()).unwrap();
+        let result: Result<serde_json::Value, _> = TCompressible::decompress_with_limits(
+            &compressed,
+            CompressionAlgorithm::Snappy,
+            limits,
+        );
+        assert!(matches!(
+            result,
+            Err(CompressorError::DeserializationError(_))
+        ));
+
+        // Brackets inside strings do not count towards the depth
+        let value = serde_json::json!({ "a": [[["[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[["]]] });
+        let compressed = value
+            .compress_streaming(CompressionAlgorithm::Snappy)
+            .unwrap();
+        let decompressed: serde_json::Value = TCompressible::decompress_with_limits(
+            &compressed,
+            CompressionAlgorithm::Snappy,
+            limits,
+        )
+        .unwrap();
+        assert_eq!(decompressed, value);
+
+        let large = vec![0u32; 1 << 20];
+        let compressed = large
+            .compress_streaming(CompressionAlgorithm::Snappy)
+            .unwrap();
+        let result: Result<Vec<u32>, _> = TCompressible::decompress_with_limits(
+            &compressed,
+            CompressionAlgorithm::Snappy,
+            limits,
+        );
+        assert!(matches!(
+            result,
+            Err(CompressorError::DecompressionError(_))
+        ));
+    }
+
+    #[test]
+    fn test_compress_streaming_large_field() {
+        #[derive(Serialize, Deserialize, Debug, PartialEq)]
+        struct Upload {
+            name: String,
+            content_type: String,
+            body: Vec<u8>,
+        }
+
+        let upload = Upload {
+            name: "upload.bin".to_string(),
+            content_type: "application/octet-stream".to_string(),
+            body: (0..5 * 1024 * 1024).map(|i| (i % 251) as u8).collect(),
+        };
+
+        let compressed = upload
+            .compress_streaming(CompressionAlgorithm::Snappy)
+            .unwrap();
+        assert_eq!(
+            compressed,
+            upload
+                .compress_generic(&Serializer::Json, &SnappyCompressor)
+                .unwrap()
+        );
+        assert_eq!(
+            Upload::decompress_generic(&compressed, &Serializer::Json, &SnappyCompressor).unwrap(),
+            upload
+        );
+    }
+
+    #[test]
+    fn test_decompress_strict_schema() {
+        #[derive(Serialize, Deserialize, Debug, PartialEq)]
+        struct Inner {
+            id: u32,
+        }
+
+        #[derive(Serialize, Deserialize, Debug, PartialEq)]
+        struct Current {
+            name: String,
+            inner: Inner,
+        }
+
+        let algorithm = CompressionAlgorithm::Snappy;
+        let compressed = Current {
+            name: "current".to_string(),
+            inner: Inner { id: 1 },
+        }
+        .compress_streaming(algorithm)
+        .unwrap();
+        assert_eq!(
+            Current::decompress_strict_schema(&compressed, algorithm)
+                .unwrap()
+                .inner,
+            Inner { id: 1 }
+        );
+
+        let drifted = serde_json::json!({
+            "name": "drifted",
+            "inner": { "id": 2, "legacy_flag": true },
+        })
+        .compress_streaming(algorithm)
+        .unwrap();
+        // The lenient path silently drops the field
+        assert!(
+            Current::decompress_generic(&drifted, &Serializer::Json, &SnappyCompressor).is_ok()
+        );
+
+        match Current::decompress_strict_schema(&drifted, algorithm) {
+            Err(CompressorError::DeserializationError(message)) => {
+                assert_eq!(message, "unknown field 'inner.legacy_flag'")
+            }
+            other => panic!("expected a DeserializationError, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_bytes_compress_ext() {
+        let data: Vec<u8> = (0..10_000).map(|i| (i % 200) as u8).collect();
+        let algorithm = CompressionAlgorithm::Snappy;
+
+        let compressed = data.compress_bytes(algorithm).unwrap();
+        assert_eq!(compressed, data[..].compress_bytes(algorithm).unwrap());
+        assert!(compressed.len() < data.compress_with_algorithm(algorithm).unwrap().len());
+        assert_eq!(
+            Vec::<u8>::decompress_bytes(&compressed, algorithm).unwrap(),
+            data
+        );
+    }
+
+    #[test]
+    fn test_armored_round_trip() {
+        let value = TestStruct {
+            field1: "armored for email ".repeat(20),
+            field2: 42,
+        };
+        let algorithm = CompressionAlgorithm::Snappy;
+
+        let armored = value.compress_armored(algorithm).unwrap();
+        assert!(armored.starts_with(armor::ARMOR_BEGIN));
+        assert!(armored.is_ascii());
+
+        let embedded = format!(
+            "Hi,\n\nthe payload is below.\n\n{}\n-- \nsent from a CLI",
+            armored
+        );
+        assert_eq!(
+            TestStruct::decompress_armored(&embedded, algorithm).unwrap(),
+            value
+        );
+    }
+
+    #[test]
+    fn test_armored_ragged_lines() {
+        let value = TestStruct {
+            field1: "ragged wrapping ".repeat(30),
+            field2: -1,
+        };
+        let algorithm = CompressionAlgorithm::Snappy;
+
+        let armored = value.compress_armored(algorithm).unwrap();
+        let body: String = armored
+            .lines()
+            .filter(|line| !line.starts_with("-----"))
+            .collect();
+
+        // Rewrap at widths of 1, 2, 3, ... with indentation and CRLF line endings
+        let mut ragged = format!("  {}\r\n", armor::ARMOR_BEGIN);
+        let mut rest = body.as_str();
+        let mut width = 1;
+        while !rest.is_empty() {
+            let (line, tail) = rest.split_at(width.min(rest.len()));
+            ragged.push_str(&format!("    {}\r\n", line));
+            rest = tail;
+            width += 1;
+        }
+        ragged.push_str(armor::ARMOR_END);
+
+        assert_eq!(
+            TestStruct::decompress_armored(&ragged, algorithm).unwrap(),
+            value
+        );
+    }
+
+    #[test]
+    fn test_decompress_json_with_bom() {
+        let mut json = b"\xef\xbb\xbf \n\t".to_vec();
+        json.extend_from_slice(br#"{"field1":"bom","field2":7}"#);
+        let compressed = SnappyCompressor.compress(&json).unwrap();
+
+        let value: TestStruct =
+            TCompressible::decompress_generic(&compressed, &Serializer::Json, &SnappyCompressor)
+                .unwrap();
+        assert_eq!(
+            value,
+            TestStruct {
+                field1: "bom".to_string(),
+                field2: 7,
+            }
+        );
+
+        // Only a leading mark is skipped
+        let doubled = [b"\xef\xbb\xbf".as_slice(), &json].concat();
+        let compressed = SnappyCompressor.compress(&doubled).unwrap();
+        assert!(
+            TestStruct::decompress_generic(&compressed, &Serializer::Json, &SnappyCompressor)
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_decompress_dispatch() {
+        #[derive(Serialize, Deserialize, Debug, PartialEq)]
+        struct Login {
+            user: String,
+        }
+
+        #[derive(Serialize, Deserialize, Debug, PartialEq)]
+        struct Upload {
+            file: String,
+            size: u64,
+        }
+
+        #[derive(Debug, PartialEq)]
+        enum Message {
+            Login(Login),
+            Upload(Upload),
+        }
+
+        fn variant<V: DeserializeOwned + 'static>(wrap: fn(V) -> Message) -> DispatchFn<Message> {
+            Box::new(move |value| {
+                serde_json::from_value(value)
+                    .map(wrap)
+                    .map_err(|err| CompressorError::DeserializationError(err.to_string()))
+            })
+        }
+
+        let chooser = |value: &serde_json::Value| match value["type"].as_str() {
+            Some("login") => variant(Message::Login),
+            Some("upload") => variant(Message::Upload),
+            other => {
+                let message = format!("unknown message type {:?}", other);
+                Box::new(move |_| Err(CompressorError::DeserializationError(message)))
+            }
+        };
+
+        let algorithm = CompressionAlgorithm::Snappy;
+        let blobs: Vec<Vec<u8>> = [
+            br#"{"type":"login","user":"ada"}"#.as_slice(),
+            br#"{"type":"upload","file":"a.log","size":12}"#,
+            br#"{"type":"logout"}"#,
+        ]
+        .iter()
+        .map(|json| SnappyCompressor.compress(json).unwrap())
+        .collect();
+
+        assert_eq!(
+            decompress_dispatch(&blobs[0], algorithm, chooser).unwrap(),
+            Message::Login(Login {
+                user: "ada".to_string()
+            })
+        );
+        assert_eq!(
+            decompress_dispatch(&blobs[1], algorithm, chooser).unwrap(),
+            Message::Upload(Upload {
+                file: "a.log".to_string(),
+                size: 12,
+            })
+        );
+        assert!(matches!(
+            decompress_dispatch(&blobs[2], algorithm, chooser),
+            Err(CompressorError::DeserializationError(_))
+        ));
+    }
+
+    #[test]
+    fn test_compress_serializable_borrowed() {
+        #[derive(Serialize)]
+        struct View<'a> {
+            name: &'a str,
+            count: u32,
+        }
+
+        #[derive(Serialize, Deserialize, Debug, PartialEq)]
+        struct Owned {
+            name: String,
+            count: u32,
+        }
+
+        let name = String::from("borrowed view");
+        let view = View {
+            name: &name,
+            count: 3,
+        };
+        let algorithm = CompressionAlgorithm::Snappy;
+
+        let compressed = compress_serializable(&view, algorithm).unwrap();
+        assert_eq!(
+            Owned::decompress_with_algorithm(&compressed, algorithm).unwrap(),
+            Owned {
+                name: "borrowed view".to_string(),
+                count: 3,
+            }
+        );
+    }
+
+    #[cfg(not(feature = "default-serde-bincode"))]
+    #[test]
+    fn test_compress_prepared() {
+        let test_struct = TestStruct {
+            field1: "Prepared".to_string(),
+            field2: 201,
+        };
+        let serialized = serde_json::to_vec(&test_struct).unwrap();
+
+        let compressed = compress_prepared(&serialized, CompressionAlgorithm::Snappy).unwrap();
+        assert_eq!(compressed, test_struct.compress().unwrap());
+        assert_eq!(
+            compressed,
+            test_struct
+                .compress_with_algorithm(CompressionAlgorithm::Snappy)
+                .unwrap()
+        );
+
+        let decompressed = decompress_to_bytes(&compressed, CompressionAlgorithm::Snappy).unwrap();
+        assert_eq!(decompressed, serialized);
+        assert!(matches!(
+            decompress_to_bytes(b"not snappy", CompressionAlgorithm::Snappy),
+            Err(CompressorError::WithAlgorithm { .. })
+        ));
+    }
+
+    #[test]
+    fn test_collection_edge_cases() {
+        use std::collections::{BTreeMap, HashMap};
+
+        #[derive(Serialize, Deserialize, Debug, PartialEq)]
+        struct Collections {
+            empty_vec: Vec<u32>,
+            single_vec: Vec<u32>,
+            none: Option<String>,
+            some: Option<Vec<u32>>,
+            empty_map: BTreeMap<String, u32>,
+            single_map: HashMap<String, Vec<u32>>,
+        }
+
+        let values = [
+            Collections {
+                empty_vec: vec![],
+                single_vec: vec![7],
+                none: None,
+                some: Some(vec![]),
+                empty_map: BTreeMap::new(),
+                single_map: HashMap::from([("only".to_string(), vec![])]),
+            },
+            Collections {
+                empty_vec: vec![],
+                single_vec: vec![0],
+                none: None,
+                some: None,
+                empty_map: BTreeMap::new(),
+                single_map: HashMap::from([(String::new(), vec![1])]),
+            },
+        ];
+
+        for algorithm in [CompressionAlgorithm::Identity, CompressionAlgorithm::Snappy] {
+            for value in &values {
+                let compressed = value.compress_with_algorithm(algorithm).unwrap();
+                assert_eq!(
+                    compressed,
+                    value.compress_with_algorithm(algorithm).unwrap()
+                );
+                assert_eq!(
+                    &Collections::decompress_with_algorithm(&compressed, algorithm).unwrap(),
+                    value
+                );
+            }
+
+            let empty: Vec<Option<u8>> = vec![];
+            let compressed = empty.compress_with_algorithm(algorithm).unwrap();
+            assert_eq!(
+                Vec::<Option<u8>>::decompress_with_algorithm(&compressed, algorithm).unwrap(),
+                empty
+            );
+
+            let single = vec![None::<u8>];
+            let compressed = single.compress_with_algorithm(algorithm).unwrap();
+            assert_eq!(
+                Vec::<Option<u8>>::decompress_with_algorithm(&compressed, algorithm).unwrap(),
+                single
+            );
+        }
+    }
+
+    #[cfg(feature = "default-serde-bincode")]
+    #[test]
+    fn test_default_serializer_bincode() {
+        #[derive(Serialize, Deserialize, Debug, PartialEq)]
+        struct Readings {
+            sensor: u32,
+            values: Vec<f64>,
+        }
+
+        let readings = Readings {
+            sensor: 7,
+            values: (0..1000).map(|i| i as f64 * 1.37).collect(),
+        };
+
+        let compressed = readings.compress().unwrap();
+        let serialized = SnappyCompressor.decompress(&compressed).unwrap();
+        assert_eq!(serialized, bincode::serialize(&readings).unwrap());
+        assert!(serialized.len() < serde_json::to_vec(&readings).unwrap().len());
+        assert_eq!(Readings::decompress(&compressed).unwrap(), readings);
+    }
+
+    #[test]
+    fn test_algorithm_error_context() {
+        let result: Result<TestStruct, _> = TCompressible::decompress_with_algorithm(
+            b"not compressed",
+            CompressionAlgorithm::Snappy,
+        );
+
+        let err = result.unwrap_err();
+        assert!(matches!(
+            &err,
+            CompressorError::WithAlgorithm {
+                algorithm: CompressionAlgorithm::Snappy,
+                source,
+            } if matches!(**source, CompressorError::DecompressionError(_))
+        ));
+        assert!(err.to_string().contains("Snappy"));
+    }
+
+    #[cfg(feature = "postcard")]
+    #[test]
+    fn test_postcard_round_trip() {
+        #[derive(Debug, Serialize, Deserialize, PartialEq)]
+        enum Reading {
+            Empty,
+            Single(u16),
+            Pair { low: i64, high: i64 },
+        }
+
+        #[derive(Debug, Serialize, Deserialize, PartialEq)]
+        struct Sensor {
+            id: u32,
+            inner: TestStruct,
+            readings: Vec<Reading>,
+        }
+
+        let sensor = Sensor {
+            id: 7,
+            inner: TestStruct {
+                field1: "nested".to_string(),
+                field2: -3,
+            },
+            readings: vec![
+                Reading::Empty,
+                Reading::Single(512),
+                Reading::Pair {
+                    low: -1,
+                    high: 1 << 40,
+                },
+            ],
+        };
+
+        let compressed = sensor
+            .compress_postcard(CompressionAlgorithm::Snappy)
+            .unwrap();
+        let decompressed: Sensor =
+            TCompressible::decompress_postcard(&compressed, CompressionAlgorithm::Snappy).unwrap();
+
+        assert_eq!(sensor, decompressed);
+    }
+
+    #[cfg(feature = "postcard")]
+    #[test]
+    fn test_postcard_smaller_than_json() {
+        let values: Vec<u64> = (0..10_000).map(|i| i * 7919 % 100_003).collect();
+
+        let postcard = values
+            .compress_postcard(CompressionAlgorithm::Snappy)
+            .unwrap();
+        let json = values
+            .compress_with_algorithm(CompressionAlgorithm::Snappy)
+            .unwrap();
+
+        assert!(postcard.len() < json.len());
+    }
+
+    #[cfg(feature = "bincode")]
+    #[test]
+    fn test_compress_binary_byte_field() {
+        #[derive(Debug, Serialize, Deserialize, PartialEq)]
+        struct Attachment {
+            name: String,
+            content: Vec<u8>,
+        }
+
+        let attachment = Attachment {
+            name: "blob.bin".to_string(),
+            content: noise(0x9e37_79b9, 50 * 1024),
+        };
+
+        let binary = attachment
+            .compress_binary(CompressionAlgorithm::Snappy)
+            .unwrap();
+        let json = attachment
+            .compress_streaming(CompressionAlgorithm::Snappy)
+            .unwrap();
+        assert!(binary.len() < json.len());
+
+        let decompressed: Attachment =
+            TCompressible::decompress_binary(&binary, CompressionAlgorithm::Snappy).unwrap();
+        assert_eq!(attachment, decompressed);
+    }
 }
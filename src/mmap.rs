@@ -0,0 +1,95 @@
+use crate::compressors::{CompressionAlgorithm, CompressorFactory};
+use crate::error::CompressorError;
+use memmap2::Mmap;
+use std::fs::File;
+use std::io::Write;
+use std::path::Path;
+
+/// Size of the slices of the mapping handed to the encoder at a time
+const MMAP_CHUNK_SIZE: usize = 1024 * 1024;
+
+fn map_file(path: &Path) -> Result<Mmap, CompressorError> {
+    let file = File::open(path)
+        .map_err(|err| CompressorError::IoError(format!("{}: {}", path.display(), err)))?;
+
+    // Safety: the mapping is only read, and callers are expected not to truncate or
+    // modify the file while it is being compressed
+    unsafe { Mmap::map(&file) }
+        .map_err(|err| CompressorError::IoError(format!("{}: {}", path.display(), err)))
+}
+
+/// Memory-maps the file at `path` and compresses its content without copying it into a buffer
+pub fn compress_mmap(
+    path: &Path,
+    algorithm: CompressionAlgorithm,
+) -> Result<Vec<u8>, CompressorError> {
+    let mut compressed = Vec::new();
+    compress_mmap_to(path, algorithm, &mut compressed)?;
+
+    Ok(compressed)
+}
+
+/// Memory-maps the file at `path` and streams its compressed content into `writer`.
+///
+/// The mapping is fed to the encoder sequentially in chunks, so the kernel is free to
+/// evict pages that have already been compressed instead of keeping the whole file resident.
+pub fn compress_mmap_to<W: Write>(
+    path: &Path,
+    algorithm: CompressionAlgorithm,
+    writer: &mut W,
+) -> Result<(), CompressorError> {
+    let mmap = map_file(path)?;
+
+    #[cfg(unix)]
+    mmap.advise(memmap2::Advice::Sequential)
+        .map_err(|err| CompressorError::IoError(err.to_string()))?;
+
+    let compressor = CompressorFactory::get_compressor(algorithm);
+    let mut encoder = compressor
+        .encoder(Box::new(writer))
+        .map_err(|err| err.with_algorithm(algorithm))?;
+
+    for chunk in mmap.chunks(MMAP_CHUNK_SIZE) {
+        encoder.write_all(chunk).map_err(|err| {
+            CompressorError::CompressionError(err.to_string()).with_algorithm(algorithm)
+        })?;
+    }
+
+    encoder
+        .finish()
+        .map_err(|err| err.with_algorithm(algorithm))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compressors::TCompressor;
+
+    #[test]
+    fn test_compress_mmap() {
+        let data = b"memory mapped content ".repeat(100_000);
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        file.write_all(&data).unwrap();
+
+        let compressed = compress_mmap(file.path(), CompressionAlgorithm::Snappy).unwrap();
+        let expected = CompressorFactory::get_compressor(CompressionAlgorithm::Snappy)
+            .compress(&std::fs::read(file.path()).unwrap())
+            .unwrap();
+
+        assert_eq!(compressed, expected);
+        assert_eq!(
+            crate::SnappyCompressor.decompress(&compressed).unwrap(),
+            data
+        );
+    }
+
+    #[test]
+    fn test_compress_mmap_missing_file() {
+        let result = compress_mmap(
+            Path::new("/nonexistent/bering-compression"),
+            CompressionAlgorithm::Snappy,
+        );
+
+        assert!(matches!(result, Err(CompressorError::IoError(_))));
+    }
+}
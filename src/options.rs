@@ -0,0 +1,771 @@
+#[cfg(feature = "lz4")]
+use crate::compressors::lz4::{Lz4Compressor, HC_MAX_LEVEL, HC_MIN_LEVEL};
+#[cfg(feature = "gzip")]
+use crate::compressors::GzipCompressor;
+#[cfg(feature = "zstd")]
+use crate::compressors::ZstdCompressor;
+use crate::compressors::{CompressionAlgorithm, CompressorFactory, TCompressor};
+use crate::error::CompressorError;
+use std::collections::HashMap;
+use std::env;
+use std::str::FromStr;
+use std::sync::{Arc, PoisonError, RwLock};
+
+/// An algorithm together with its tuning parameters.
+///
+/// The textual form is `algorithm[:key=value,...]`, e.g. `snappy` or `zstd:level=7,workers=4`.
+/// Supported keys are `level` (gzip, zstd and lz4), `workers` (zstd only) and
+/// `deterministic` (`true` or `false`, any algorithm).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct CompressionOptions {
+    pub algorithm: CompressionAlgorithm,
+    pub level: Option<i32>,
+    pub workers: Option<u32>,
+    /// See `CompressionOptions::deterministic`
+    pub deterministic: bool,
+}
+
+impl CompressionOptions {
+    pub fn new(algorithm: CompressionAlgorithm) -> Self {
+        CompressionOptions {
+            algorithm,
+            level: None,
+            workers: None,
+            deterministic: false,
+        }
+    }
+
+    pub fn with_level(mut self, level: i32) -> Self {
+        self.level = Some(level);
+        self
+    }
+
+    pub fn with_workers(mut self, workers: u32) -> Self {
+        self.workers = Some(workers);
+        self
+    }
+
+    /// Pins every parameter that affects the output, so the same input compresses to the
+    /// same bytes on every run and platform, e.g. for reproducible build artifacts.
+    /// Parameters that cannot be pinned, zstd's `workers`, fail `build` with `ConfigError`.
+    ///
+    /// What each algorithm guarantees in this mode:
+    /// - identity: always byte-identical.
+    /// - snappy: pure Rust (`snap`, or the crate's own encoder for raw blocks), identical
+    ///   across platforms; may change with a new `snap` release.
+    /// - gzip: header mtime zeroed and OS byte "unknown" (always the case), level pinned
+    ///   to `default_level` unless given; the pure-Rust deflate backend is identical across platforms but
+    ///   may change with a new `miniz_oxide` release.
+    /// - zstd: single-threaded at an explicit level, identical across platforms for the
+    ///   same bundled libzstd; a libzstd upgrade may change the output.
+    /// - lz4: identical across platforms for the same bundled liblz4; an upgrade may
+    ///   change the output.
+    ///
+    /// No algorithm promises identical output across versions of its backing library, so
+    /// pin those in `Cargo.lock` alongside this option. Decompression is unaffected.
+    pub fn deterministic(mut self) -> Self {
+        self.deterministic = true;
+        self
+    }
+
+    /// Short deterministic token identifying the compressor these options build, e.g.
+    /// `snappy`, `zstd-l7-w4` or `gzip-l6-det`, for cache keys and logs.
+    ///
+    /// Options that build the same compressor share a fingerprint: an unset level is
+    /// reported as the algorithm's `default_level`, lz4 levels below the high-compression
+    /// range as level 0, and zero workers are left out.
+    pub fn fingerprint(&self) -> String {
+        let mut fingerprint = self.algorithm.name().to_string();
+
+        let level = match (self.algorithm, self.level) {
+            // All build the fast compressor, see `build`
+            #[cfg(feature = "lz4")]
+            (CompressionAlgorithm::Lz4, Some(level)) if level < HC_MIN_LEVEL as i32 => Some(0),
+            (algorithm, level) => {
+                level.or_else(|| algorithm.default_level().map(|level| level as i32))
+            }
+        };
+        if let Some(level) = level {
+            fingerprint.push_str(&format!("-l{}", level));
+        }
+        if let Some(workers) = self.workers.filter(|&workers| workers > 0) {
+            fingerprint.push_str(&format!("-w{}", workers));
+        }
+        if self.deterministic {
+            fingerprint.push_str("-det");
+        }
+
+        fingerprint
+    }
+
+    /// Reads options from the environment variable `var_name`, e.g. `BERING_COMPRESSION=zstd:level=7`
+    pub fn from_env(var_name: &str) -> Result<Self, CompressorError> {
+        Self::from_env_value(var_name, env::var(var_name))
+    }
+
+    /// `from_env` on an already read `value`, naming `var_name` in configuration errors
+    fn from_env_value(
+        var_name: &str,
+        value: Result<String, env::VarError>,
+    ) -> Result<Self, CompressorError> {
+        let value =
+            value.map_err(|err| CompressorError::ConfigError(format!("{}: {}", var_name, err)))?;
+
+        value.parse().map_err(|err: CompressorError| match err {
+            CompressorError::ConfigError(message) => {
+                CompressorError::ConfigError(format!("{}: {}", var_name, message))
+            }
+            err => err,
+        })
+    }
+
+    /// Builds a compressor configured with these options
+    pub fn build(&self) -> Result<Box<dyn TCompressor + Send + Sync>, CompressorError> {
+        self.validate()?;
+
+        match self.algorithm {
+            #[cfg(feature = "gzip")]
+            CompressionAlgorithm::Gzip => {
+                let compressor = match self.level {
+                    Some(level) => GzipCompressor::new(level as u32),
+                    // flate2's default level is not ours to pin
+                    None if self.deterministic => {
+                        GzipCompressor::new(self.algorithm.default_level().unwrap_or(6))
+                    }
+                    None => GzipCompressor::default(),
+                };
+                Ok(Box::new(compressor))
+            }
+            #[cfg(feature = "zstd")]
+            CompressionAlgorithm::Zstd => {
+                let level = self.level.unwrap_or(zstd::DEFAULT_COMPRESSION_LEVEL);
+                let compressor = ZstdCompressor::new(level).with_workers(self.workers.unwrap_or(0));
+                Ok(Box::new(compressor))
+            }
+            #[cfg(feature = "lz4")]
+            CompressionAlgorithm::Lz4 => {
+                let compressor = match self.level {
+                    Some(level) if level as u32 >= HC_MIN_LEVEL => {
+                        Lz4Compressor::high_compression(level as u32)
+                    }
+                    _ => Lz4Compressor::new(),
+                };
+                Ok(Box::new(compressor))
+            }
+            algorithm => Ok(CompressorFactory::get_compressor(algorithm)),
+        }
+    }
+
+    fn validate(&self) -> Result<(), CompressorError> {
+        match self.algorithm {
+            #[cfg(feature = "gzip")]
+            CompressionAlgorithm::Gzip => match (self.level, self.workers) {
+                (Some(level), _) if !(0..=9).contains(&level) => Err(CompressorError::ConfigError(
+                    format!("gzip level {} is outside 0..=9", level),
+                )),
+                (_, Some(_)) => Err(CompressorError::ConfigError(
+                    "gzip does not take workers".to_string(),
+                )),
+                _ => Ok(()),
+            },
+            #[cfg(feature = "zstd")]
+            CompressionAlgorithm::Zstd => {
+                let range = zstd::compression_level_range();
+                match (self.level, self.workers) {
+                    (Some(level), _) if !range.contains(&level) => {
+                        Err(CompressorError::ConfigError(format!(
+                            "zstd level {} is outside {}..={}",
+                            level,
+                            range.start(),
+                            range.end()
+                        )))
+                    }
+                    (_, Some(workers)) if workers > 0 && self.deterministic => {
+                        Err(CompressorError::ConfigError(
+                            "zstd workers make the output depend on the worker count, \
+                             which deterministic mode forbids"
+                                .to_string(),
+                        ))
+                    }
+                    _ => Ok(()),
+                }
+            }
+            #[cfg(feature = "lz4")]
+            CompressionAlgorithm::Lz4 => match (self.level, self.workers) {
+                (Some(level), _) if !(0..=HC_MAX_LEVEL as i32).contains(&level) => {
+                    Err(CompressorError::ConfigError(format!(
+                        "lz4 level {} is outside 0..={}",
+                        level, HC_MAX_LEVEL
+                    )))
+                }
+                (_, Some(_)) => Err(CompressorError::ConfigError(
+                    "lz4 does not take workers".to_string(),
+                )),
+                _ => Ok(()),
+            },
+            algorithm => {
+                if self.level.is_some() || self.workers.is_some() {
+                    return Err(CompressorError::ConfigError(format!(
+                        "{} does not take a level or workers",
+                        algorithm
+                    )));
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Compressors built from `CompressionOptions`, kept for reuse so options that are costly to
+/// set up are only built once. The cache is `Send + Sync`; share it between threads by
+/// reference or in an `Arc`.
+#[derive(Default)]
+pub struct CompressorCache {
+    compressors: RwLock<HashMap<CompressionOptions, SharedCompressor>>,
+}
+
+type SharedCompressor = Arc<dyn TCompressor + Send + Sync>;
+
+impl CompressorCache {
+    pub fn new() -> Self {
+        CompressorCache::default()
+    }
+
+    /// Returns the compressor cached for `opts`, building and caching it on first use.
+    /// Invalid options fail with `ConfigError` and are not cached.
+    pub fn get_or_create(
+        &self,
+        opts: &CompressionOptions,
+    ) -> Result<SharedCompressor, CompressorError> {
+        // The map only ever gains complete entries, so it stays usable after a panic
+        if let Some(compressor) = self
+            .compressors
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get(opts)
+        {
+            return Ok(Arc::clone(compressor));
+        }
+
+        let mut compressors = self
+            .compressors
+            .write()
+            .unwrap_or_else(PoisonError::into_inner);
+        // Another thread may have built it while the lock was released
+        if let Some(compressor) = compressors.get(opts) {
+            return Ok(Arc::clone(compressor));
+        }
+
+        let compressor: SharedCompressor = Arc::from(opts.build()?);
+        compressors.insert(*opts, Arc::clone(&compressor));
+        Ok(compressor)
+    }
+
+    /// Number of cached compressors
+    pub fn len(&self) -> usize {
+        self.compressors
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// Compresses `value` with each of `ladder` in order, e.g. from fastest to strongest, and
+/// returns the first options whose output is at most `max_output` bytes, with that output.
+///
+/// This is `compress_with_policy` with a `max_output` budget, except that the first
+/// invalid options or compression error is returned at once instead of skipped.
+///
+/// Fails with `ConfigError` for an empty ladder or invalid options, and with
+/// `CompressionError` naming the smallest output reached when no options fit.
+pub fn compress_to_fit(
+    value: &[u8],
+    max_output: usize,
+    ladder: &[CompressionOptions],
+) -> Result<(CompressionOptions, Vec<u8>), CompressorError> {
+    let policy = CompressionPolicy::new(ladder.to_vec()).with_max_output(max_output);
+    run_policy(value, &policy, false)
+}
+
+/// Options to try in order when compressing, e.g. a fast algorithm first and stronger
+/// ones as retries, optionally with a size budget every output must meet.
+/// See `compress_with_policy`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CompressionPolicy {
+    pub attempts: Vec<CompressionOptions>,
+    /// Largest acceptable output in bytes; larger outputs count as failed attempts
+    pub max_output: Option<usize>,
+}
+
+impl CompressionPolicy {
+    pub fn new(attempts: Vec<CompressionOptions>) -> Self {
+        CompressionPolicy {
+            attempts,
+            max_output: None,
+        }
+    }
+
+    pub fn with_max_output(mut self, max_output: usize) -> Self {
+        self.max_output = Some(max_output);
+        self
+    }
+}
+
+/// Compresses `value` with each of `policy.attempts` in order until one succeeds, and
+/// returns the options used with the output. An attempt fails if its options are invalid,
+/// compression fails or the output exceeds `policy.max_output`; the next one is then tried.
+///
+/// Unlike `compress_to_fit`, which stops at the first error, every attempt gets its turn.
+/// If all fail and any output was over budget, the `CompressionError` names the smallest
+/// one; otherwise the last attempt's error is returned. A policy without attempts fails
+/// with `ConfigError`.
+pub fn compress_with_policy(
+    value: &[u8],
+    policy: &CompressionPolicy,
+) -> Result<(CompressionOptions, Vec<u8>), CompressorError> {
+    run_policy(value, policy, true)
+}
+
+fn run_policy(
+    value: &[u8],
+    policy: &CompressionPolicy,
+    skip_errors: bool,
+) -> Result<(CompressionOptions, Vec<u8>), CompressorError> {
+    let mut smallest: Option<(CompressionAlgorithm, usize)> = None;
+    let mut last_error = None;
+
+    for options in &policy.attempts {
+        let attempt = options.build().and_then(|compressor| {
+            compressor
+                .compress(value)
+                .map_err(|err| err.with_algorithm(options.algorithm))
+        });
+
+        match attempt {
+            Ok(compressed) => match policy.max_output {
+                Some(max_output) if compressed.len() > max_output => {
+                    if smallest.is_none_or(|(_, len)| compressed.len() < len) {
+                        smallest = Some((options.algorithm, compressed.len()));
+                    }
+                }
+                _ => return Ok((*options, compressed)),
+            },
+            Err(err) if skip_errors => last_error = Some(err),
+            Err(err) => return Err(err),
+        }
+    }
+
+    match (smallest, policy.max_output) {
+        (Some((algorithm, len)), Some(max_output)) => {
+            Err(CompressorError::CompressionError(format!(
+                "no options fit {} bytes; the smallest output was {} bytes with {}",
+                max_output, len, algorithm
+            )))
+        }
+        _ => Err(last_error
+            .unwrap_or_else(|| CompressorError::ConfigError("no options to try".to_string()))),
+    }
+}
+
+impl FromStr for CompressionOptions {
+    type Err = CompressorError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (algorithm, params) = match s.split_once(':') {
+            Some((algorithm, params)) => (algorithm, Some(params)),
+            None => (s, None),
+        };
+
+        let mut options = CompressionOptions::new(algorithm.parse()?);
+
+        for param in params.into_iter().flat_map(|params| params.split(',')) {
+            let (key, value) = param
+                .split_once('=')
+                .map(|(key, value)| (key.trim(), value.trim()))
+                .filter(|(key, value)| !key.is_empty() && !value.is_empty())
+                .ok_or_else(|| {
+                    CompressorError::ConfigError(format!(
+                        "expected key=value, got '{}'",
+                        param.trim()
+                    ))
+                })?;
+
+            let invalid = |err: std::num::ParseIntError| {
+                CompressorError::ConfigError(format!("invalid {} '{}': {}", key, value, err))
+            };
+
+            match key {
+                "level" if options.level.is_none() => {
+                    options.level = Some(value.parse().map_err(invalid)?)
+                }
+                "workers" if options.workers.is_none() => {
+                    options.workers = Some(value.parse().map_err(invalid)?)
+                }
+                "deterministic" => {
+                    options.deterministic = value.parse().map_err(|_| {
+                        CompressorError::ConfigError(format!(
+                            "invalid deterministic '{}': expected true or false",
+                            value
+                        ))
+                    })?
+                }
+                "level" | "workers" => {
+                    return Err(CompressorError::ConfigError(format!(
+                        "duplicate option '{}'",
+                        key
+                    )))
+                }
+                _ => {
+                    return Err(CompressorError::ConfigError(format!(
+                        "unknown option '{}'",
+                        key
+                    )))
+                }
+            }
+        }
+
+        options.validate()?;
+        Ok(options)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_valid() {
+        assert_eq!(
+            "snappy".parse::<CompressionOptions>().unwrap(),
+            CompressionOptions::new(CompressionAlgorithm::Snappy)
+        );
+        assert_eq!(
+            " Identity ".parse::<CompressionOptions>().unwrap(),
+            CompressionOptions::new(CompressionAlgorithm::Identity)
+        );
+
+        #[cfg(feature = "gzip")]
+        assert_eq!(
+            "gzip:level=9".parse::<CompressionOptions>().unwrap(),
+            CompressionOptions::new(CompressionAlgorithm::Gzip).with_level(9)
+        );
+
+        #[cfg(feature = "zstd")]
+        assert_eq!(
+            "zstd:level=7, workers=2"
+                .parse::<CompressionOptions>()
+                .unwrap(),
+            CompressionOptions::new(CompressionAlgorithm::Zstd)
+                .with_level(7)
+                .with_workers(2)
+        );
+
+        #[cfg(feature = "lz4")]
+        assert_eq!(
+            "lz4:level=9".parse::<CompressionOptions>().unwrap(),
+            CompressionOptions::new(CompressionAlgorithm::Lz4).with_level(9)
+        );
+    }
+
+    #[test]
+    fn test_parse_invalid() {
+        for invalid in [
+            "",
+            "lzma",
+            "snappy:level=3",
+            "snappy:",
+            "snappy:level",
+            "snappy:=3",
+            "snappy:speed=fast",
+        ] {
+            assert!(
+                matches!(
+                    invalid.parse::<CompressionOptions>(),
+                    Err(CompressorError::ConfigError(_))
+                ),
+                "{:?} should be rejected",
+                invalid
+            );
+        }
+
+        #[cfg(feature = "gzip")]
+        for invalid in ["gzip:level=10", "gzip:workers=2"] {
+            assert!(invalid.parse::<CompressionOptions>().is_err());
+        }
+
+        #[cfg(feature = "zstd")]
+        for invalid in ["zstd:level=high", "zstd:level=99", "zstd:level=3,level=4"] {
+            assert!(invalid.parse::<CompressionOptions>().is_err());
+        }
+
+        #[cfg(feature = "lz4")]
+        for invalid in ["lz4:level=13", "lz4:level=-1", "lz4:workers=2"] {
+            assert!(invalid.parse::<CompressionOptions>().is_err());
+        }
+    }
+
+    #[test]
+    fn test_deterministic_output() {
+        let data = br#"{"artifact":"release","files":["a","b","c"]}"#.repeat(400);
+
+        for &algorithm in CompressionAlgorithm::all() {
+            let options = CompressionOptions::new(algorithm).deterministic();
+            let first = options.build().unwrap().compress(&data).unwrap();
+
+            for _ in 0..3 {
+                let compressor = options.build().unwrap();
+                assert_eq!(compressor.compress(&data).unwrap(), first, "{}", algorithm);
+                assert_eq!(compressor.decompress(&first).unwrap(), data);
+            }
+        }
+
+        assert_eq!(
+            "snappy:deterministic=true"
+                .parse::<CompressionOptions>()
+                .unwrap(),
+            CompressionOptions::new(CompressionAlgorithm::Snappy).deterministic()
+        );
+        assert!("snappy:deterministic=yes"
+            .parse::<CompressionOptions>()
+            .is_err());
+
+        #[cfg(feature = "zstd")]
+        {
+            assert!(matches!(
+                "zstd:workers=2,deterministic=true".parse::<CompressionOptions>(),
+                Err(CompressorError::ConfigError(_))
+            ));
+            assert!("zstd:workers=0,deterministic=true"
+                .parse::<CompressionOptions>()
+                .is_ok());
+        }
+    }
+
+    #[test]
+    fn test_compress_to_fit() {
+        let data = br#"{"slot":"fixed","payload":"aaaaaaaaaaaaaaaa"}"#.repeat(100);
+        let ladder = [
+            CompressionOptions::new(CompressionAlgorithm::Identity),
+            CompressionOptions::new(CompressionAlgorithm::Snappy),
+        ];
+
+        let (options, compressed) = compress_to_fit(&data, data.len() / 2, &ladder).unwrap();
+        assert_eq!(options.algorithm, CompressionAlgorithm::Snappy);
+        assert!(compressed.len() <= data.len() / 2);
+        assert_eq!(
+            options.build().unwrap().decompress(&compressed).unwrap(),
+            data
+        );
+
+        let (options, _) = compress_to_fit(&data, data.len(), &ladder).unwrap();
+        assert_eq!(options.algorithm, CompressionAlgorithm::Identity);
+
+        let err = compress_to_fit(&data, 10, &ladder).unwrap_err();
+        assert!(
+            matches!(&err, CompressorError::CompressionError(message) if message.contains("snappy"))
+        );
+        assert!(matches!(
+            compress_to_fit(&data, usize::MAX, &[]),
+            Err(CompressorError::ConfigError(_))
+        ));
+
+        // Invalid options end the ladder instead of being skipped
+        let invalid = CompressionOptions::new(CompressionAlgorithm::Snappy).with_level(1);
+        assert!(matches!(
+            compress_to_fit(&data, usize::MAX, &[invalid, ladder[1]]),
+            Err(CompressorError::ConfigError(_))
+        ));
+    }
+
+    #[test]
+    fn test_compress_with_policy() {
+        let data = br#"{"attempt":"retry","payload":"bbbbbbbbbbbbbbbb"}"#.repeat(100);
+        let fast = CompressionOptions::new(CompressionAlgorithm::Identity);
+        let stronger = CompressionOptions::new(CompressionAlgorithm::Snappy);
+
+        let policy = CompressionPolicy::new(vec![fast, stronger]).with_max_output(data.len() / 2);
+        let (used, compressed) = compress_with_policy(&data, &policy).unwrap();
+        assert_eq!(used, stronger);
+        assert_eq!(
+            stronger.build().unwrap().decompress(&compressed).unwrap(),
+            data
+        );
+
+        // Without a budget the first attempt wins
+        let (used, _) =
+            compress_with_policy(&data, &CompressionPolicy::new(vec![fast, stronger])).unwrap();
+        assert_eq!(used, fast);
+
+        // An invalid attempt is skipped rather than ending the policy
+        let invalid = CompressionOptions::new(CompressionAlgorithm::Snappy).with_level(1);
+        let (used, _) =
+            compress_with_policy(&data, &CompressionPolicy::new(vec![invalid, stronger])).unwrap();
+        assert_eq!(used, stronger);
+
+        let err = compress_with_policy(&data, &policy.clone().with_max_output(10)).unwrap_err();
+        assert!(
+            matches!(&err, CompressorError::CompressionError(message) if message.contains("with snappy"))
+        );
+        assert!(matches!(
+            compress_with_policy(&data, &CompressionPolicy::default()),
+            Err(CompressorError::ConfigError(_))
+        ));
+    }
+
+    #[cfg(feature = "zstd")]
+    #[test]
+    fn test_compress_to_fit_escalates_to_strongest() {
+        // A block repeated further back than low levels' window (512 KiB at level 1)
+        let mut state = 0x9e37_79b9u32;
+        let block: Vec<u8> = (0..768 * 1024)
+            .map(|_| {
+                state = state.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+                b'a' + (state >> 16) as u8 % 26
+            })
+            .collect();
+        let data = block.repeat(2);
+
+        let ladder = [
+            CompressionOptions::new(CompressionAlgorithm::Snappy),
+            CompressionOptions::new(CompressionAlgorithm::Zstd).with_level(1),
+            CompressionOptions::new(CompressionAlgorithm::Zstd).with_level(19),
+        ];
+        let sizes: Vec<usize> = ladder
+            .iter()
+            .map(|options| options.build().unwrap().compress(&data).unwrap().len())
+            .collect();
+        assert!(sizes[0] > sizes[2] && sizes[1] > sizes[2], "{:?}", sizes);
+
+        let (options, compressed) = compress_to_fit(&data, sizes[2], &ladder).unwrap();
+        assert_eq!(options, ladder[2]);
+        assert_eq!(compressed.len(), sizes[2]);
+    }
+
+    #[test]
+    fn test_fingerprint() {
+        let snappy = CompressionOptions::new(CompressionAlgorithm::Snappy);
+        assert_eq!(snappy.fingerprint(), "snappy");
+        assert_eq!(
+            snappy.fingerprint(),
+            "snappy"
+                .parse::<CompressionOptions>()
+                .unwrap()
+                .fingerprint()
+        );
+        assert_eq!(snappy.deterministic().fingerprint(), "snappy-det");
+        assert_ne!(
+            snappy.fingerprint(),
+            CompressionOptions::new(CompressionAlgorithm::Identity).fingerprint()
+        );
+
+        #[cfg(feature = "zstd")]
+        {
+            let zstd = CompressionOptions::new(CompressionAlgorithm::Zstd);
+            assert_eq!(zstd.fingerprint(), "zstd-l3");
+            assert_eq!(zstd.with_level(3).fingerprint(), zstd.fingerprint());
+            assert_eq!(zstd.with_workers(0).fingerprint(), zstd.fingerprint());
+            assert_eq!(
+                zstd.with_level(7).with_workers(4).fingerprint(),
+                "zstd-l7-w4"
+            );
+            assert_eq!(zstd.with_level(-5).fingerprint(), "zstd-l-5");
+            assert_ne!(zstd.with_level(7).fingerprint(), zstd.fingerprint());
+        }
+
+        #[cfg(feature = "gzip")]
+        assert_eq!(
+            "gzip:level=9,deterministic=true"
+                .parse::<CompressionOptions>()
+                .unwrap()
+                .fingerprint(),
+            "gzip-l9-det"
+        );
+
+        #[cfg(feature = "lz4")]
+        {
+            let lz4 = CompressionOptions::new(CompressionAlgorithm::Lz4);
+            assert_eq!(lz4.fingerprint(), "lz4-l0");
+            assert_eq!(lz4.with_level(0).fingerprint(), lz4.fingerprint());
+            assert_eq!(lz4.with_level(1).fingerprint(), lz4.fingerprint());
+            assert_eq!(lz4.with_level(2).fingerprint(), "lz4-l2");
+        }
+    }
+
+    #[test]
+    fn test_compressor_cache() {
+        let cache = CompressorCache::new();
+        let snappy = CompressionOptions::new(CompressionAlgorithm::Snappy);
+
+        let first = cache.get_or_create(&snappy).unwrap();
+        let second = cache
+            .get_or_create(&CompressionOptions::new(CompressionAlgorithm::Snappy))
+            .unwrap();
+        assert!(Arc::ptr_eq(&first, &second));
+
+        let identity = cache
+            .get_or_create(&CompressionOptions::new(CompressionAlgorithm::Identity))
+            .unwrap();
+        assert!(!Arc::ptr_eq(&first, &identity));
+        assert_eq!(cache.len(), 2);
+
+        let invalid = CompressionOptions::new(CompressionAlgorithm::Snappy).with_level(3);
+        assert!(matches!(
+            cache.get_or_create(&invalid),
+            Err(CompressorError::ConfigError(_))
+        ));
+        assert_eq!(cache.len(), 2);
+
+        let cached: Vec<_> = std::thread::scope(|scope| {
+            let handles: Vec<_> = (0..8)
+                .map(|_| scope.spawn(|| cache.get_or_create(&snappy).unwrap()))
+                .collect();
+            handles
+                .into_iter()
+                .map(|handle| handle.join().unwrap())
+                .collect()
+        });
+        assert!(cached
+            .iter()
+            .all(|compressor| Arc::ptr_eq(compressor, &first)));
+
+        let data = b"cached compressor ".repeat(50);
+        assert_eq!(
+            first.decompress(&first.compress(&data).unwrap()).unwrap(),
+            data
+        );
+    }
+
+    #[test]
+    fn test_from_env() {
+        // The environment is only read, never modified, since tests run in parallel
+        // threads of one process
+        let var_name = "BERING_COMPRESSION";
+        let options =
+            CompressionOptions::from_env_value(var_name, Ok("snappy".to_string())).unwrap();
+        assert_eq!(
+            options,
+            CompressionOptions::new(CompressionAlgorithm::Snappy)
+        );
+
+        for value in [
+            Ok("snappy:level=x".to_string()),
+            Ok("brotli".to_string()),
+            Err(env::VarError::NotPresent),
+        ] {
+            assert!(matches!(
+                CompressionOptions::from_env_value(var_name, value),
+                Err(CompressorError::ConfigError(message)) if message.starts_with("BERING_COMPRESSION: ")
+            ));
+        }
+
+        // Cargo sets the package name for test runs, and it is no algorithm
+        assert!(matches!(
+            CompressionOptions::from_env("CARGO_PKG_NAME"),
+            Err(CompressorError::ConfigError(message)) if message.starts_with("CARGO_PKG_NAME: ")
+        ));
+    }
+}
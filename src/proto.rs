@@ -0,0 +1,71 @@
+//! Compression of protobuf messages encoded with `prost`.
+
+use crate::compressors::{CompressionAlgorithm, CompressorFactory};
+use crate::error::CompressorError;
+use prost::Message;
+
+/// Encodes `message` to protobuf bytes and compresses them with `algorithm`
+pub fn compress_proto<M: Message>(
+    message: &M,
+    algorithm: CompressionAlgorithm,
+) -> Result<Vec<u8>, CompressorError> {
+    CompressorFactory::get_compressor(algorithm)
+        .compress(&message.encode_to_vec())
+        .map_err(|err| err.with_algorithm(algorithm))
+}
+
+/// Decompresses `data` with `algorithm` and decodes the protobuf message it holds
+pub fn decompress_proto<M: Message + Default>(
+    data: &[u8],
+    algorithm: CompressionAlgorithm,
+) -> Result<M, CompressorError> {
+    let decompressed = CompressorFactory::get_compressor(algorithm)
+        .decompress(data)
+        .map_err(|err| err.with_algorithm(algorithm))?;
+
+    M::decode(decompressed.as_slice())
+        .map_err(|err| CompressorError::DeserializationError(err.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Clone, PartialEq, Message)]
+    struct Heartbeat {
+        #[prost(string, tag = "1")]
+        service: String,
+        #[prost(uint64, tag = "2")]
+        sequence: u64,
+        #[prost(bytes = "vec", tag = "3")]
+        payload: Vec<u8>,
+    }
+
+    #[test]
+    fn test_proto_round_trip() {
+        let heartbeat = Heartbeat {
+            service: "relay".to_string(),
+            sequence: 42,
+            payload: vec![7; 256],
+        };
+
+        let compressed = compress_proto(&heartbeat, CompressionAlgorithm::Snappy).unwrap();
+        let decompressed: Heartbeat =
+            decompress_proto(&compressed, CompressionAlgorithm::Snappy).unwrap();
+
+        assert_eq!(heartbeat, decompressed);
+        assert!(compressed.len() < heartbeat.encoded_len());
+    }
+
+    #[test]
+    fn test_proto_invalid_message() {
+        let compressed = CompressorFactory::get_compressor(CompressionAlgorithm::Snappy)
+            .compress(&[0xff, 0xff, 0xff])
+            .unwrap();
+
+        assert!(matches!(
+            decompress_proto::<Heartbeat>(&compressed, CompressionAlgorithm::Snappy),
+            Err(CompressorError::DeserializationError(_))
+        ));
+    }
+}
@@ -0,0 +1,570 @@
+use crate::error::CompressorError;
+use serde::{de::DeserializeOwned, ser, Serialize};
+use std::fmt;
+
+/// A pluggable serialization format for `TCompressible::compress_generic`
+pub trait SerdeFormat {
+    fn serialize<T: Serialize + ?Sized>(&self, value: &T) -> Result<Vec<u8>, CompressorError>;
+    fn deserialize<T: DeserializeOwned>(&self, bytes: &[u8]) -> Result<T, CompressorError>;
+}
+
+/// Serialization formats applied before compression
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Serializer {
+    /// Writes non-finite floats (`NaN`, infinities) as `null`, which cannot be read back
+    /// as a float; use `FiniteJson` to reject them instead
+    Json,
+    #[cfg(feature = "bincode")]
+    Bincode,
+}
+
+impl Serializer {
+    /// Format of the `TCompressible` methods that take no serializer, selected with the
+    /// `default-serde-json` (default) or `default-serde-bincode` feature
+    #[cfg(not(feature = "default-serde-bincode"))]
+    pub const DEFAULT: Serializer = Serializer::Json;
+    #[cfg(feature = "default-serde-bincode")]
+    pub const DEFAULT: Serializer = Serializer::Bincode;
+
+    /// Stable byte identifying the format inside self-describing blobs
+    pub fn tag(&self) -> u8 {
+        match self {
+            Serializer::Json => 1,
+            #[cfg(feature = "bincode")]
+            Serializer::Bincode => 2,
+        }
+    }
+
+    pub fn from_tag(tag: u8) -> Result<Self, CompressorError> {
+        match tag {
+            1 => Ok(Serializer::Json),
+            #[cfg(feature = "bincode")]
+            2 => Ok(Serializer::Bincode),
+            _ => Err(CompressorError::DeserializationError(format!(
+                "unknown serializer tag {}",
+                tag
+            ))),
+        }
+    }
+
+    pub fn serialize<T: Serialize + ?Sized>(&self, value: &T) -> Result<Vec<u8>, CompressorError> {
+        match self {
+            Serializer::Json => serde_json::to_vec(value)
+                .map_err(|err| CompressorError::SerializationError(err.to_string())),
+            #[cfg(feature = "bincode")]
+            Serializer::Bincode => bincode::serialize(value)
+                .map_err(|err| CompressorError::SerializationError(err.to_string())),
+        }
+    }
+
+    pub fn deserialize<T: DeserializeOwned>(&self, bytes: &[u8]) -> Result<T, CompressorError> {
+        match self {
+            Serializer::Json => json_from_slice(bytes),
+            #[cfg(feature = "bincode")]
+            Serializer::Bincode => bincode::deserialize(bytes)
+                .map_err(|err| CompressorError::DeserializationError(err.to_string())),
+        }
+    }
+}
+
+impl SerdeFormat for Serializer {
+    fn serialize<T: Serialize + ?Sized>(&self, value: &T) -> Result<Vec<u8>, CompressorError> {
+        Serializer::serialize(self, value)
+    }
+
+    fn deserialize<T: DeserializeOwned>(&self, bytes: &[u8]) -> Result<T, CompressorError> {
+        Serializer::deserialize(self, bytes)
+    }
+}
+
+/// JSON that fails with `SerializationError` naming the field when the value holds a
+/// non-finite float, instead of silently writing it as `null` like `Serializer::Json`.
+/// Use it with `TCompressible::compress_generic` to learn at compress time that data
+/// cannot round-trip; bincode, by contrast, preserves `NaN` and infinities bit for bit.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct FiniteJson;
+
+impl SerdeFormat for FiniteJson {
+    fn serialize<T: Serialize + ?Sized>(&self, value: &T) -> Result<Vec<u8>, CompressorError> {
+        value
+            .serialize(&mut FloatCheck::default())
+            .map_err(|NonFinite(message)| CompressorError::SerializationError(message))?;
+        Serializer::Json.serialize(value)
+    }
+
+    fn deserialize<T: DeserializeOwned>(&self, bytes: &[u8]) -> Result<T, CompressorError> {
+        json_from_slice(bytes)
+    }
+}
+
+/// Walks a value without producing output, failing at the first non-finite float with
+/// its path, e.g. `readings[2].value`
+#[derive(Default)]
+struct FloatCheck {
+    path: String,
+    /// Path length and next element index of each open sequence, map or struct
+    frames: Vec<(usize, usize)>,
+    /// Path segment of the map key whose value comes next
+    key: Option<String>,
+}
+
+#[derive(Debug)]
+struct NonFinite(String);
+
+impl fmt::Display for NonFinite {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for NonFinite {}
+
+impl ser::Error for NonFinite {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        NonFinite(msg.to_string())
+    }
+}
+
+impl FloatCheck {
+    fn check(&self, value: f64) -> Result<(), NonFinite> {
+        if value.is_finite() {
+            return Ok(());
+        }
+        let path = self.path.strip_prefix('.').unwrap_or(&self.path);
+        Err(NonFinite(if path.is_empty() {
+            format!("non-finite float {} cannot be represented in JSON", value)
+        } else {
+            format!(
+                "non-finite float {} at '{}' cannot be represented in JSON",
+                value, path
+            )
+        }))
+    }
+
+    fn open(&mut self, variant: Option<&str>) -> Result<&mut Self, NonFinite> {
+        self.frames.push((self.path.len(), 0));
+        if let Some(variant) = variant {
+            self.path.push('.');
+            self.path.push_str(variant);
+        }
+        Ok(self)
+    }
+
+    fn close(&mut self) -> Result<(), NonFinite> {
+        if let Some((len, _)) = self.frames.pop() {
+            self.path.truncate(len);
+        }
+        Ok(())
+    }
+
+    /// Checks `value` with `segment` appended to the path
+    fn nested<T: Serialize + ?Sized>(&mut self, segment: &str, value: &T) -> Result<(), NonFinite> {
+        let len = self.path.len();
+        self.path.push_str(segment);
+        value.serialize(&mut *self)?;
+        self.path.truncate(len);
+        Ok(())
+    }
+
+    fn element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), NonFinite> {
+        let index = match self.frames.last_mut() {
+            Some((_, next)) => {
+                *next += 1;
+                *next - 1
+            }
+            None => 0,
+        };
+        self.nested(&format!("[{}]", index), value)
+    }
+
+    fn field<T: Serialize + ?Sized>(&mut self, key: &str, value: &T) -> Result<(), NonFinite> {
+        self.nested(&format!(".{}", key), value)
+    }
+}
+
+impl ser::Serializer for &mut FloatCheck {
+    type Ok = ();
+    type Error = NonFinite;
+    type SerializeSeq = Self;
+    type SerializeTuple = Self;
+    type SerializeTupleStruct = Self;
+    type SerializeTupleVariant = Self;
+    type SerializeMap = Self;
+    type SerializeStruct = Self;
+    type SerializeStructVariant = Self;
+
+    fn serialize_bool(self, _: bool) -> Result<(), NonFinite> {
+        Ok(())
+    }
+
+    fn serialize_i8(self, _: i8) -> Result<(), NonFinite> {
+        Ok(())
+    }
+
+    fn serialize_i16(self, _: i16) -> Result<(), NonFinite> {
+        Ok(())
+    }
+
+    fn serialize_i32(self, _: i32) -> Result<(), NonFinite> {
+        Ok(())
+    }
+
+    fn serialize_i64(self, _: i64) -> Result<(), NonFinite> {
+        Ok(())
+    }
+
+    fn serialize_i128(self, _: i128) -> Result<(), NonFinite> {
+        Ok(())
+    }
+
+    fn serialize_u8(self, _: u8) -> Result<(), NonFinite> {
+        Ok(())
+    }
+
+    fn serialize_u16(self, _: u16) -> Result<(), NonFinite> {
+        Ok(())
+    }
+
+    fn serialize_u32(self, _: u32) -> Result<(), NonFinite> {
+        Ok(())
+    }
+
+    fn serialize_u64(self, _: u64) -> Result<(), NonFinite> {
+        Ok(())
+    }
+
+    fn serialize_u128(self, _: u128) -> Result<(), NonFinite> {
+        Ok(())
+    }
+
+    fn serialize_f32(self, value: f32) -> Result<(), NonFinite> {
+        self.check(value.into())
+    }
+
+    fn serialize_f64(self, value: f64) -> Result<(), NonFinite> {
+        self.check(value)
+    }
+
+    fn serialize_char(self, _: char) -> Result<(), NonFinite> {
+        Ok(())
+    }
+
+    fn serialize_str(self, _: &str) -> Result<(), NonFinite> {
+        Ok(())
+    }
+
+    fn serialize_bytes(self, _: &[u8]) -> Result<(), NonFinite> {
+        Ok(())
+    }
+
+    fn serialize_none(self) -> Result<(), NonFinite> {
+        Ok(())
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<(), NonFinite> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<(), NonFinite> {
+        Ok(())
+    }
+
+    fn serialize_unit_struct(self, _: &'static str) -> Result<(), NonFinite> {
+        Ok(())
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _: &'static str,
+        _: u32,
+        _: &'static str,
+    ) -> Result<(), NonFinite> {
+        Ok(())
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _: &'static str,
+        value: &T,
+    ) -> Result<(), NonFinite> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _: &'static str,
+        _: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<(), NonFinite> {
+        self.field(variant, value)
+    }
+
+    fn serialize_seq(self, _: Option<usize>) -> Result<Self, NonFinite> {
+        self.open(None)
+    }
+
+    fn serialize_tuple(self, _: usize) -> Result<Self, NonFinite> {
+        self.open(None)
+    }
+
+    fn serialize_tuple_struct(self, _: &'static str, _: usize) -> Result<Self, NonFinite> {
+        self.open(None)
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _: &'static str,
+        _: u32,
+        variant: &'static str,
+        _: usize,
+    ) -> Result<Self, NonFinite> {
+        self.open(Some(variant))
+    }
+
+    fn serialize_map(self, _: Option<usize>) -> Result<Self, NonFinite> {
+        self.open(None)
+    }
+
+    fn serialize_struct(self, _: &'static str, _: usize) -> Result<Self, NonFinite> {
+        self.open(None)
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _: &'static str,
+        _: u32,
+        variant: &'static str,
+        _: usize,
+    ) -> Result<Self, NonFinite> {
+        self.open(Some(variant))
+    }
+}
+
+impl ser::SerializeSeq for &mut FloatCheck {
+    type Ok = ();
+    type Error = NonFinite;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), NonFinite> {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<(), NonFinite> {
+        self.close()
+    }
+}
+
+impl ser::SerializeTuple for &mut FloatCheck {
+    type Ok = ();
+    type Error = NonFinite;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), NonFinite> {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<(), NonFinite> {
+        self.close()
+    }
+}
+
+impl ser::SerializeTupleStruct for &mut FloatCheck {
+    type Ok = ();
+    type Error = NonFinite;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), NonFinite> {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<(), NonFinite> {
+        self.close()
+    }
+}
+
+impl ser::SerializeTupleVariant for &mut FloatCheck {
+    type Ok = ();
+    type Error = NonFinite;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), NonFinite> {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<(), NonFinite> {
+        self.close()
+    }
+}
+
+impl ser::SerializeMap for &mut FloatCheck {
+    type Ok = ();
+    type Error = NonFinite;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<(), NonFinite> {
+        // JSON keys are strings; other key types only need a readable path segment
+        let key = match serde_json::to_value(key) {
+            Ok(serde_json::Value::String(key)) => key,
+            Ok(key) => key.to_string(),
+            Err(_) => "?".to_string(),
+        };
+        self.key = Some(key);
+        Ok(())
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), NonFinite> {
+        let key = self.key.take().unwrap_or_default();
+        self.field(&key, value)
+    }
+
+    fn end(self) -> Result<(), NonFinite> {
+        self.close()
+    }
+}
+
+impl ser::SerializeStruct for &mut FloatCheck {
+    type Ok = ();
+    type Error = NonFinite;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), NonFinite> {
+        self.field(key, value)
+    }
+
+    fn end(self) -> Result<(), NonFinite> {
+        self.close()
+    }
+}
+
+impl ser::SerializeStructVariant for &mut FloatCheck {
+    type Ok = ();
+    type Error = NonFinite;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), NonFinite> {
+        self.field(key, value)
+    }
+
+    fn end(self) -> Result<(), NonFinite> {
+        self.close()
+    }
+}
+
+/// Bounds applied when deserializing untrusted JSON with
+/// `TCompressible::decompress_with_limits`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct JsonLimits {
+    /// Deepest nesting of arrays and objects accepted
+    pub max_depth: usize,
+    /// Largest decompressed size in bytes; decompression stops once it is exceeded
+    pub max_size: usize,
+}
+
+impl Default for JsonLimits {
+    /// serde_json's own recursion limit and no size limit
+    fn default() -> Self {
+        JsonLimits {
+            max_depth: 128,
+            max_size: usize::MAX,
+        }
+    }
+}
+
+/// Rejects JSON nested deeper than `max_depth` with a single pass over the bytes,
+/// before any recursive deserialization starts
+pub(crate) fn check_json_depth(bytes: &[u8], max_depth: usize) -> Result<(), CompressorError> {
+    let mut depth = 0usize;
+    let mut in_string = false;
+    let mut escaped = false;
+
+    for &byte in bytes {
+        if in_string {
+            match byte {
+                _ if escaped => escaped = false,
+                b'\\' => escaped = true,
+                b'"' => in_string = false,
+                _ => {}
+            }
+            continue;
+        }
+
+        match byte {
+            b'"' => in_string = true,
+            b'[' | b'{' => {
+                depth += 1;
+                if depth > max_depth {
+                    return Err(CompressorError::DeserializationError(format!(
+                        "JSON nesting exceeds the depth limit of {}",
+                        max_depth
+                    )));
+                }
+            }
+            b']' | b'}' => depth = depth.saturating_sub(1),
+            _ => {}
+        }
+    }
+
+    Ok(())
+}
+
+const UTF8_BOM: &[u8] = b"\xef\xbb\xbf";
+
+/// Deserializes JSON bytes, the entry point shared by every JSON path in the crate.
+///
+/// With `arbitrary_precision`, numbers inside `#[serde(untagged)]` enums and other
+/// buffered content fail to deserialize straight from a slice, while going through
+/// `serde_json::Value` breaks `serde_json::Number` fields under `#[serde(flatten)]`.
+/// The slice is tried first and the `Value` route is only taken when it fails.
+///
+/// A leading UTF-8 byte order mark, which some tools prepend and serde_json rejects, is
+/// skipped; valid JSON never starts with one. Leading whitespace is valid JSON already.
+pub(crate) fn json_from_slice<T: DeserializeOwned>(bytes: &[u8]) -> Result<T, CompressorError> {
+    let bytes = strip_bom(bytes);
+    let result = serde_json::from_slice(bytes);
+
+    #[cfg(feature = "arbitrary_precision")]
+    let result = result.or_else(|err| {
+        serde_json::from_slice::<serde_json::Value>(bytes)
+            .and_then(serde_json::from_value)
+            .map_err(|_| err)
+    });
+
+    result.map_err(|err| CompressorError::DeserializationError(err.to_string()))
+}
+
+/// `json_from_slice` that also returns the path of the first field `T` does not declare,
+/// with the same `arbitrary_precision` fallback
+pub(crate) fn json_from_slice_reporting_unknown<T: DeserializeOwned>(
+    bytes: &[u8],
+) -> Result<(T, Option<String>), CompressorError> {
+    let bytes = strip_bom(bytes);
+    let mut unknown_field = None;
+
+    let mut deserializer = serde_json::Deserializer::from_slice(bytes);
+    let result = serde_ignored::deserialize(&mut deserializer, |path| {
+        unknown_field.get_or_insert_with(|| path.to_string());
+    })
+    .and_then(|value| deserializer.end().map(|()| value));
+
+    #[cfg(feature = "arbitrary_precision")]
+    let result = result.or_else(|err| {
+        unknown_field = None;
+        serde_json::from_slice::<serde_json::Value>(bytes)
+            .and_then(|value| {
+                serde_ignored::deserialize(value, |path| {
+                    unknown_field.get_or_insert_with(|| path.to_string());
+                })
+            })
+            .map_err(|_| err)
+    });
+
+    result
+        .map(|value| (value, unknown_field))
+        .map_err(|err| CompressorError::DeserializationError(err.to_string()))
+}
+
+fn strip_bom(bytes: &[u8]) -> &[u8] {
+    bytes.strip_prefix(UTF8_BOM).unwrap_or(bytes)
+}
@@ -0,0 +1,1259 @@
+use crate::compressors::{
+    CompressionAlgorithm, CompressionStats, CompressorFactory, TCompressor, TEncoder,
+};
+use crate::error::CompressorError;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::cell::{Cell, RefCell};
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::iter;
+use std::mem;
+use std::rc::Rc;
+use std::sync::mpsc::Sender;
+use std::thread;
+use std::time::Duration;
+
+/// Lazily decompresses `reader` and yields its content line by line.
+///
+/// Line terminators (`\n` or `\r\n`) are stripped and a final line without a
+/// trailing newline is still yielded. A line that is not valid UTF-8 yields a
+/// `DecodingError` without ending the iteration, while a decompression failure
+/// is yielded once, tagged with `algorithm`, and ends it.
+pub fn decompress_lines<'a, R: Read + 'a>(
+    reader: R,
+    algorithm: CompressionAlgorithm,
+) -> impl Iterator<Item = Result<String, CompressorError>> + 'a {
+    let (mut reader, mut failure) =
+        match CompressorFactory::get_compressor(algorithm).decoder(Box::new(reader)) {
+            Ok(decoder) => (Some(BufReader::new(decoder)), None),
+            Err(err) => (None, Some(err.with_algorithm(algorithm))),
+        };
+
+    iter::from_fn(move || {
+        if let Some(err) = failure.take() {
+            return Some(Err(err));
+        }
+
+        let mut line = Vec::new();
+        match reader.as_mut()?.read_until(b'\n', &mut line) {
+            Ok(0) => None,
+            Ok(_) => {
+                if line.ends_with(b"\n") {
+                    line.pop();
+                    if line.ends_with(b"\r") {
+                        line.pop();
+                    }
+                }
+
+                Some(String::from_utf8(line).map_err(|err| {
+                    CompressorError::DecodingError(format!("invalid UTF-8 in line: {}", err))
+                }))
+            }
+            Err(err) => {
+                reader = None;
+                Some(Err(
+                    CompressorError::DecompressionError(err.to_string()).with_algorithm(algorithm)
+                ))
+            }
+        }
+    })
+}
+
+/// Lazily decompresses `reader` and deserializes the whitespace-separated JSON values
+/// it contains (concatenated objects, NDJSON, ...) one at a time.
+///
+/// Only one value is held in memory at a time. The first error ends the iteration; a
+/// decompression failure is tagged with `algorithm`.
+pub fn decompress_stream<'a, T, R>(
+    reader: R,
+    algorithm: CompressionAlgorithm,
+) -> impl Iterator<Item = Result<T, CompressorError>> + 'a
+where
+    T: DeserializeOwned + 'a,
+    R: Read + 'a,
+{
+    let (mut values, mut failure) =
+        match CompressorFactory::get_compressor(algorithm).decoder(Box::new(reader)) {
+            Ok(decoder) => (
+                Some(serde_json::Deserializer::from_reader(BufReader::new(decoder)).into_iter()),
+                None,
+            ),
+            Err(err) => (None, Some(err.with_algorithm(algorithm))),
+        };
+
+    iter::from_fn(move || {
+        if let Some(err) = failure.take() {
+            return Some(Err(err));
+        }
+
+        match values.as_mut()?.next()? {
+            Ok(value) => Some(Ok(value)),
+            Err(err) => {
+                values = None;
+                Some(Err(if err.is_io() {
+                    CompressorError::DecompressionError(err.to_string()).with_algorithm(algorithm)
+                } else {
+                    CompressorError::DeserializationError(err.to_string())
+                }))
+            }
+        }
+    })
+}
+
+/// Size of the single buffer `pipe_compress`, `compress_stream_with_stats` and
+/// `pipe_decompress` move data through
+const PIPE_BUFFER_SIZE: usize = 8 * 1024;
+
+/// First and longest pause of `retry_paced` after a `WouldBlock`
+const MIN_BACKOFF: Duration = Duration::from_millis(1);
+const MAX_BACKOFF: Duration = Duration::from_millis(50);
+
+/// Streams `reader` through the decoder for `algorithm` into `writer` and returns the
+/// number of decompressed bytes written. Only one fixed-size buffer is held at a time.
+///
+/// The decoder only runs ahead of `writer` by that buffer: short writes are continued,
+/// and a non-blocking `writer` that reports `WouldBlock` is retried after a pause that
+/// grows to 50ms while it stays blocked, so nothing is dropped and no CPU is burned
+/// waiting. The final flush is retried the same way. Other write errors are returned as
+/// `IoError`.
+pub fn pipe_decompress<R: Read, W: Write>(
+    reader: R,
+    mut writer: W,
+    algorithm: CompressionAlgorithm,
+) -> Result<u64, CompressorError> {
+    let written = decompress_windowed(reader, algorithm, PIPE_BUFFER_SIZE, |chunk| {
+        write_paced(&mut writer, chunk).map_err(|err| CompressorError::IoError(err.to_string()))
+    })?;
+
+    retry_paced(|| writer.flush()).map_err(|err| CompressorError::IoError(err.to_string()))?;
+
+    Ok(written)
+}
+
+/// `write_all` that also waits out `WouldBlock`, see `pipe_decompress`
+fn write_paced<W: Write>(writer: &mut W, mut buf: &[u8]) -> io::Result<()> {
+    while !buf.is_empty() {
+        match retry_paced(|| writer.write(buf))? {
+            0 => return Err(io::ErrorKind::WriteZero.into()),
+            written => buf = &buf[written..],
+        }
+    }
+
+    Ok(())
+}
+
+/// Runs `attempt` until it succeeds or fails with anything but `Interrupted` or
+/// `WouldBlock`, pausing after each `WouldBlock` with the backoff of `pipe_decompress`
+fn retry_paced<T>(mut attempt: impl FnMut() -> io::Result<T>) -> io::Result<T> {
+    let mut backoff = MIN_BACKOFF;
+
+    loop {
+        match attempt() {
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+            Err(err) if err.kind() == io::ErrorKind::WouldBlock => {
+                thread::sleep(backoff);
+                backoff = (backoff * 2).min(MAX_BACKOFF);
+            }
+            result => return result,
+        }
+    }
+}
+
+/// Decompresses `reader` in windows of at most `window_size` bytes, handing each one to
+/// `on_chunk` so the full output never has to be held in memory. Returns the total number
+/// of decompressed bytes; an error from `on_chunk` stops decoding and is returned as is.
+///
+/// Every built-in algorithm decodes incrementally (Snappy chunk by chunk); a compressor
+/// relying on the buffered default `decoder` still decompresses everything up front.
+pub fn decompress_windowed<R: Read>(
+    reader: R,
+    algorithm: CompressionAlgorithm,
+    window_size: usize,
+    mut on_chunk: impl FnMut(&[u8]) -> Result<(), CompressorError>,
+) -> Result<u64, CompressorError> {
+    if window_size == 0 {
+        return Err(CompressorError::ConfigError(
+            "window size must be greater than zero".to_string(),
+        ));
+    }
+
+    let compressor = CompressorFactory::get_compressor(algorithm);
+    let mut decoder = compressor
+        .decoder(Box::new(reader))
+        .map_err(|err| err.with_algorithm(algorithm))?;
+
+    let mut window = vec![0u8; window_size];
+    let mut total = 0u64;
+    loop {
+        let read = match decoder.read(&mut window) {
+            Ok(0) => break,
+            Ok(read) => read,
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+            Err(err) => {
+                return Err(
+                    CompressorError::DecompressionError(err.to_string()).with_algorithm(algorithm)
+                )
+            }
+        };
+
+        on_chunk(&window[..read])?;
+        total += read as u64;
+    }
+
+    Ok(total)
+}
+
+/// Size of the chunks `decompress_to_channel` sends
+const CHANNEL_CHUNK_SIZE: usize = 64 * 1024;
+
+/// Decompresses `reader` and sends the output over `sender` in chunks of up to 64 KiB as
+/// it is produced. A failure is sent as the last message. Decoding stops early, without
+/// an error, once the receiver is dropped.
+pub fn decompress_to_channel<R: Read>(
+    reader: R,
+    algorithm: CompressionAlgorithm,
+    sender: Sender<Result<Vec<u8>, CompressorError>>,
+) {
+    let result = decompress_windowed(reader, algorithm, CHANNEL_CHUNK_SIZE, |chunk| {
+        sender
+            .send(Ok(chunk.to_vec()))
+            .map_err(|_| CompressorError::IoError("channel receiver dropped".to_string()))
+    });
+
+    if let Err(err) = result {
+        // Fails only if the receiver is gone, in which case nobody is left to tell
+        let _ = sender.send(Err(err));
+    }
+}
+
+/// Decompresses `reader` and checks the SHA-256 digest of its content against `expected`
+/// without buffering the output, failing with `IntegrityError` on a mismatch
+#[cfg(feature = "sha2")]
+pub fn decompress_and_hash<R: Read>(
+    reader: R,
+    algorithm: CompressionAlgorithm,
+    expected: &[u8],
+) -> Result<(), CompressorError> {
+    use sha2::{Digest, Sha256};
+
+    let mut hasher = Sha256::new();
+    decompress_windowed(reader, algorithm, PIPE_BUFFER_SIZE, |chunk| {
+        hasher.update(chunk);
+        Ok(())
+    })?;
+
+    let actual = hasher.finalize();
+    if actual.as_slice() != expected {
+        return Err(CompressorError::IntegrityError(format!(
+            "SHA-256 mismatch: expected {}, got {}",
+            hex(expected),
+            hex(&actual)
+        )));
+    }
+
+    Ok(())
+}
+
+#[cfg(feature = "sha2")]
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// Streams `reader` through the encoder for `algorithm` into `writer` and returns the
+/// number of compressed bytes written
+pub fn pipe_compress<R: Read, W: Write>(
+    reader: R,
+    writer: W,
+    algorithm: CompressionAlgorithm,
+) -> Result<u64, CompressorError> {
+    compress_stream_with_stats(reader, writer, algorithm, |_| {}).map(|stats| stats.bytes_out)
+}
+
+/// `pipe_compress` that calls `on_stats` after every read from `reader` has been passed
+/// to the encoder and once more after the stream is finished, then returns those final
+/// totals.
+///
+/// Each read is forwarded as soon as it returns, so a slow source such as a socket is not
+/// held back waiting for a full buffer. `calls` counts the reads so far and
+/// `peak_buffer_bytes` is the read buffer size. `bytes_out` only includes what the encoder
+/// has written out, so it lags behind `bytes_in` until the encoder flushes.
+pub fn compress_stream_with_stats<R: Read, W: Write>(
+    mut reader: R,
+    writer: W,
+    algorithm: CompressionAlgorithm,
+    mut on_stats: impl FnMut(CompressionStats),
+) -> Result<CompressionStats, CompressorError> {
+    let written = Cell::new(0);
+    let compressor = CompressorFactory::get_compressor(algorithm);
+    let mut encoder = compressor
+        .encoder(Box::new(CountingWriter {
+            inner: writer,
+            written: &written,
+        }))
+        .map_err(|err| err.with_algorithm(algorithm))?;
+
+    let mut buffer = [0u8; PIPE_BUFFER_SIZE];
+    let mut stats = CompressionStats {
+        peak_buffer_bytes: buffer.len(),
+        ..CompressionStats::default()
+    };
+    loop {
+        let read = match reader.read(&mut buffer) {
+            Ok(0) => break,
+            Ok(read) => read,
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+            Err(err) => return Err(CompressorError::IoError(err.to_string())),
+        };
+
+        encoder.write_all(&buffer[..read]).map_err(|err| {
+            CompressorError::CompressionError(err.to_string()).with_algorithm(algorithm)
+        })?;
+
+        stats.calls += 1;
+        stats.bytes_in += read as u64;
+        stats.bytes_out = written.get();
+        on_stats(stats);
+    }
+
+    encoder
+        .finish()
+        .map_err(|err| err.with_algorithm(algorithm))?;
+
+    stats.bytes_out = written.get();
+    on_stats(stats);
+    Ok(stats)
+}
+
+/// Serializes `value` as JSON straight into the encoder for `algorithm`, so the JSON is
+/// compressed as it is produced and never buffered whole. The output decompresses to the
+/// same JSON as serializing first and compressing afterwards.
+pub fn compress_serialize_stream<T: Serialize + ?Sized>(
+    value: &T,
+    algorithm: CompressionAlgorithm,
+) -> Result<Vec<u8>, CompressorError> {
+    let compressor = CompressorFactory::get_compressor(algorithm);
+    let mut compressed = Vec::new();
+
+    let mut encoder = compressor
+        .encoder(Box::new(&mut compressed))
+        .map_err(|err| err.with_algorithm(algorithm))?;
+    value
+        .serialize(&mut serde_json::Serializer::new(&mut encoder))
+        .map_err(|err| {
+            if err.is_io() {
+                CompressorError::CompressionError(err.to_string()).with_algorithm(algorithm)
+            } else {
+                CompressorError::SerializationError(err.to_string())
+            }
+        })?;
+    encoder
+        .finish()
+        .map_err(|err| err.with_algorithm(algorithm))?;
+
+    Ok(compressed)
+}
+
+/// Writer that records how many bytes pass through it
+struct CountingWriter<'c, W> {
+    inner: W,
+    written: &'c Cell<u64>,
+}
+
+impl<W: Write> Write for CountingWriter<'_, W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.written.set(self.written.get() + written as u64);
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Incremental compressor for message-oriented protocols.
+///
+/// Data passed to `update` is compressed into an internal buffer. `flush` ends the
+/// current frame and returns everything compressed since the previous flush, so every
+/// flushed segment is a complete frame the peer can decode on its own; further `update`
+/// calls start a new frame. Concatenated segments also decode as a single stream.
+///
+/// The output buffer is kept, with its capacity, for the lifetime of the instance. The
+/// encoder is not: every frame gets a fresh one from the compressor, including its
+/// internal buffers, which is also what keeps one message's state out of the next.
+pub struct StreamingCompressor<'a> {
+    compressor: &'a dyn TCompressor,
+    output: SharedBuffer,
+    encoder: Option<Box<dyn TEncoder + 'a>>,
+}
+
+impl<'a> StreamingCompressor<'a> {
+    pub fn new(compressor: &'a dyn TCompressor) -> Self {
+        StreamingCompressor {
+            compressor,
+            output: SharedBuffer::default(),
+            encoder: None,
+        }
+    }
+
+    pub fn update(&mut self, data: &[u8]) -> Result<(), CompressorError> {
+        let encoder = match self.encoder.take() {
+            Some(encoder) => encoder,
+            None => self.compressor.encoder(Box::new(self.output.clone()))?,
+        };
+
+        self.encoder
+            .insert(encoder)
+            .write_all(data)
+            .map_err(|err| CompressorError::CompressionError(err.to_string()))
+    }
+
+    /// Ends the current frame and returns the compressed bytes produced since the last flush.
+    /// Returns an empty buffer if nothing was written in between. The bytes are copied
+    /// out, so the output buffer keeps its capacity for the next frame.
+    pub fn flush(&mut self) -> Result<Vec<u8>, CompressorError> {
+        if let Some(encoder) = self.encoder.take() {
+            encoder.finish()?;
+        }
+
+        let mut output = self.output.0.borrow_mut();
+        let segment = output.to_vec();
+        output.clear();
+        Ok(segment)
+    }
+
+    /// Discards the current frame and everything compressed since the last flush, so the
+    /// next `update` starts a new, independent message with a fresh encoder. The output
+    /// buffer keeps its capacity for reuse.
+    pub fn reset(&mut self) {
+        // Some encoders write their trailer on drop, so drop before clearing
+        drop(self.encoder.take());
+        self.output.0.borrow_mut().clear();
+    }
+
+    pub fn finish(mut self) -> Result<Vec<u8>, CompressorError> {
+        self.flush()
+    }
+}
+
+/// `Write` sink for log appenders that compresses everything written to it and rotates
+/// to a new segment once a segment has received `threshold` uncompressed bytes.
+///
+/// Rotation happens after the write that reaches the threshold, so a single write (one
+/// log record) never straddles two segments. Every finalized segment is a complete frame
+/// that decodes on its own, e.g. to be stored as its own file.
+pub struct RotatingCompressWriter<'a> {
+    compressor: StreamingCompressor<'a>,
+    threshold: usize,
+    /// Uncompressed bytes written to the current segment
+    segment_len: usize,
+    segments: Vec<Vec<u8>>,
+}
+
+impl<'a> RotatingCompressWriter<'a> {
+    pub fn new(compressor: &'a dyn TCompressor, threshold: usize) -> Self {
+        RotatingCompressWriter {
+            compressor: StreamingCompressor::new(compressor),
+            threshold: threshold.max(1),
+            segment_len: 0,
+            segments: Vec::new(),
+        }
+    }
+
+    /// Segments finalized so far, oldest first
+    pub fn segments(&self) -> &[Vec<u8>] {
+        &self.segments
+    }
+
+    /// Removes and returns the segments finalized so far, e.g. after persisting them
+    pub fn take_segments(&mut self) -> Vec<Vec<u8>> {
+        mem::take(&mut self.segments)
+    }
+
+    /// Finalizes the current segment, even if it is below the threshold
+    pub fn rotate(&mut self) -> Result<(), CompressorError> {
+        if self.segment_len > 0 {
+            self.segments.push(self.compressor.flush()?);
+            self.segment_len = 0;
+        }
+
+        Ok(())
+    }
+
+    /// Finalizes the current segment and returns all segments not taken yet
+    pub fn finish(mut self) -> Result<Vec<Vec<u8>>, CompressorError> {
+        self.rotate()?;
+        Ok(self.segments)
+    }
+}
+
+impl Write for RotatingCompressWriter<'_> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.compressor.update(buf).map_err(io::Error::other)?;
+
+        self.segment_len += buf.len();
+        if self.segment_len >= self.threshold {
+            self.rotate().map_err(io::Error::other)?;
+        }
+        Ok(buf.len())
+    }
+
+    /// Segments are only finalized on rotation, so there is nothing to flush
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Incremental compressor that buffers at most `window` uncompressed bytes, for producers
+/// that outpace their consumer.
+///
+/// `try_push` accepts input only up to the end of the current window and hands back that
+/// window's compressed frame as soon as it fills, so at most one window and one frame
+/// are held at a time. The producer pushes the rest of its data once it has passed the
+/// frame downstream, which is the backpressure.
+///
+/// Each frame is the output of one `compress` call. Frames concatenate into a single
+/// decodable stream only for self-delimiting formats: Snappy framing, zstd, gzip, LZ4
+/// and identity. For the others, e.g. `SnappyRawCompressor`, `PaddedCompressor` or
+/// `CheckedCompressor`, the consumer must keep the frames apart and decompress each on
+/// its own.
+pub struct BoundedStreamingCompressor<'a> {
+    compressor: &'a dyn TCompressor,
+    window: usize,
+    buffer: Vec<u8>,
+}
+
+impl<'a> BoundedStreamingCompressor<'a> {
+    /// A `window` of 0 is treated as 1
+    pub fn new(compressor: &'a dyn TCompressor, window: usize) -> Self {
+        let window = window.max(1);
+        BoundedStreamingCompressor {
+            compressor,
+            window,
+            buffer: Vec::with_capacity(window),
+        }
+    }
+
+    /// Uncompressed bytes waiting for the window to fill, always below `window`
+    pub fn buffered(&self) -> usize {
+        self.buffer.len()
+    }
+
+    /// Buffers the prefix of `data` that fits in the window and returns its length. If
+    /// that fills the window, its compressed frame is returned too; the caller pushes
+    /// the rest of `data` afterwards.
+    pub fn try_push(&mut self, data: &[u8]) -> Result<(usize, Option<Vec<u8>>), CompressorError> {
+        let accepted = data.len().min(self.window - self.buffer.len());
+        self.buffer.extend_from_slice(&data[..accepted]);
+
+        if self.buffer.len() < self.window {
+            return Ok((accepted, None));
+        }
+
+        let frame = self.compressor.compress(&self.buffer)?;
+        self.buffer.clear();
+        Ok((accepted, Some(frame)))
+    }
+
+    /// Compresses what is left in the window; empty if nothing is buffered
+    pub fn finish(self) -> Result<Vec<u8>, CompressorError> {
+        if self.buffer.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        self.compressor.compress(&self.buffer)
+    }
+}
+
+/// `Read` adapter that yields the compressed form of everything read from `source`, so
+/// compression slots into a chain of `Read` transforms. `DecompressReader` reverses it.
+///
+/// The source is read one `preferred_chunk_size` chunk at a time and the encoder is
+/// finished once the source is exhausted, so the output is a complete stream.
+pub struct CompressReader<'a, R> {
+    source: R,
+    encoder: Option<Box<dyn TEncoder + 'a>>,
+    output: SharedBuffer,
+    /// Bytes of `output` already handed out
+    position: usize,
+    chunk: Vec<u8>,
+}
+
+impl<'a, R: Read> CompressReader<'a, R> {
+    pub fn new(source: R, compressor: &'a dyn TCompressor) -> Result<Self, CompressorError> {
+        let output = SharedBuffer::default();
+        let encoder = compressor.encoder(Box::new(output.clone()))?;
+
+        Ok(CompressReader {
+            source,
+            encoder: Some(encoder),
+            output,
+            position: 0,
+            chunk: vec![0; compressor.preferred_chunk_size().max(1)],
+        })
+    }
+
+    pub fn into_inner(self) -> R {
+        self.source
+    }
+}
+
+impl<R: Read> Read for CompressReader<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        loop {
+            {
+                let mut output = self.output.0.borrow_mut();
+                if self.position < output.len() {
+                    let len = buf.len().min(output.len() - self.position);
+                    buf[..len].copy_from_slice(&output[self.position..self.position + len]);
+
+                    self.position += len;
+                    if self.position == output.len() {
+                        output.clear();
+                        self.position = 0;
+                    }
+                    return Ok(len);
+                }
+            }
+
+            if self.encoder.is_none() {
+                return Ok(0);
+            }
+
+            match self.source.read(&mut self.chunk)? {
+                0 => {
+                    if let Some(encoder) = self.encoder.take() {
+                        encoder.finish().map_err(io::Error::other)?;
+                    }
+                }
+                read => {
+                    if let Some(encoder) = self.encoder.as_mut() {
+                        encoder.write_all(&self.chunk[..read])?;
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// `Read` adapter that decompresses `source` through `TCompressor::decoder`, the
+/// counterpart of `CompressReader`
+pub struct DecompressReader<'a> {
+    inner: Box<dyn Read + 'a>,
+}
+
+impl<'a> DecompressReader<'a> {
+    pub fn new<R: Read + 'a>(
+        source: R,
+        compressor: &dyn TCompressor,
+    ) -> Result<Self, CompressorError> {
+        Ok(DecompressReader {
+            inner: compressor.decoder(Box::new(source))?,
+        })
+    }
+}
+
+impl Read for DecompressReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.inner.read(buf)
+    }
+}
+
+/// Output buffer shared between a `StreamingCompressor` and the encoder writing into it
+#[derive(Clone, Default)]
+struct SharedBuffer(Rc<RefCell<Vec<u8>>>);
+
+impl Write for SharedBuffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compressors::SnappyCompressor;
+
+    #[test]
+    fn test_decompress_lines() {
+        let log = b"first line\nsecond line\r\n\nlast line without newline";
+        let compressed = SnappyCompressor.compress(log).unwrap();
+
+        let lines: Vec<String> = decompress_lines(&compressed[..], CompressionAlgorithm::Snappy)
+            .collect::<Result<_, _>>()
+            .unwrap();
+
+        assert_eq!(
+            lines,
+            vec!["first line", "second line", "", "last line without newline"]
+        );
+    }
+
+    #[test]
+    fn test_decompress_lines_invalid_utf8() {
+        let log = b"valid\n\xff\xfe\nalso valid\n";
+        let compressed = SnappyCompressor.compress(log).unwrap();
+
+        let lines: Vec<_> =
+            decompress_lines(&compressed[..], CompressionAlgorithm::Snappy).collect();
+
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0].as_ref().unwrap(), "valid");
+        assert!(matches!(lines[1], Err(CompressorError::DecodingError(_))));
+        assert_eq!(lines[2].as_ref().unwrap(), "also valid");
+    }
+
+    #[test]
+    fn test_decompress_lines_corrupt_input() {
+        let lines: Vec<_> =
+            decompress_lines(&b"not a snappy frame"[..], CompressionAlgorithm::Snappy).collect();
+
+        assert_eq!(lines.len(), 1);
+        assert!(matches!(
+            lines[0],
+            Err(CompressorError::WithAlgorithm {
+                algorithm: CompressionAlgorithm::Snappy,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn test_decompress_stream() {
+        #[derive(Debug, serde::Deserialize, PartialEq)]
+        struct Event {
+            id: u32,
+            name: String,
+        }
+
+        let separators = [" ", "\n", "\r\n\t", ""];
+        let concatenated: String = (0..100)
+            .map(|id| {
+                format!(
+                    r#"{{"id":{},"name":"event-{}"}}{}"#,
+                    id,
+                    id,
+                    separators[id % separators.len()]
+                )
+            })
+            .collect();
+        let compressed = SnappyCompressor.compress(concatenated.as_bytes()).unwrap();
+
+        let events: Vec<Event> = decompress_stream(&compressed[..], CompressionAlgorithm::Snappy)
+            .collect::<Result<_, _>>()
+            .unwrap();
+
+        assert_eq!(events.len(), 100);
+        for (id, event) in events.iter().enumerate() {
+            assert_eq!(event.id, id as u32);
+            assert_eq!(event.name, format!("event-{}", id));
+        }
+    }
+
+    #[test]
+    fn test_decompress_stream_invalid_value() {
+        let compressed = SnappyCompressor.compress(br#"{"id":1} {"id":"#).unwrap();
+
+        let values: Vec<Result<serde_json::Value, _>> =
+            decompress_stream(&compressed[..], CompressionAlgorithm::Snappy).collect();
+
+        assert_eq!(values.len(), 2);
+        assert!(values[0].is_ok());
+        assert!(matches!(
+            values[1],
+            Err(CompressorError::DeserializationError(_))
+        ));
+    }
+
+    #[test]
+    fn test_pipe_round_trip() {
+        let data = b"relayed payload ".repeat(4096);
+
+        let mut compressed = Vec::new();
+        let compressed_len = pipe_compress(
+            io::Cursor::new(&data),
+            &mut compressed,
+            CompressionAlgorithm::Snappy,
+        )
+        .unwrap();
+        assert_eq!(compressed_len, compressed.len() as u64);
+        assert_eq!(SnappyCompressor.decompress(&compressed).unwrap(), data);
+
+        let mut decompressed = Vec::new();
+        let decompressed_len = pipe_decompress(
+            io::Cursor::new(&compressed),
+            &mut decompressed,
+            CompressionAlgorithm::Snappy,
+        )
+        .unwrap();
+        assert_eq!(decompressed_len, data.len() as u64);
+        assert_eq!(decompressed, data);
+    }
+
+    #[test]
+    fn test_compress_stream_with_stats() {
+        let data = b"live ratio gauge ".repeat(20_000);
+
+        let mut reported = Vec::new();
+        let mut compressed = Vec::new();
+        let stats = compress_stream_with_stats(
+            io::Cursor::new(&data),
+            &mut compressed,
+            CompressionAlgorithm::Snappy,
+            |stats| reported.push(stats),
+        )
+        .unwrap();
+
+        // One report per 8 KiB read, then the final report after finishing
+        let reads = data.len().div_ceil(PIPE_BUFFER_SIZE);
+        assert_eq!(reported.len(), reads + 1);
+        assert!(reported
+            .windows(2)
+            .all(|pair| pair[0].bytes_in <= pair[1].bytes_in));
+        assert_eq!(reported[0].bytes_in, PIPE_BUFFER_SIZE as u64);
+        assert_eq!(*reported.last().unwrap(), stats);
+
+        assert_eq!(stats.calls, reads as u64);
+        assert_eq!(stats.bytes_in, data.len() as u64);
+        assert_eq!(stats.bytes_out, compressed.len() as u64);
+        assert!(stats.ratio() < 0.1);
+        assert_eq!(SnappyCompressor.decompress(&compressed).unwrap(), data);
+    }
+
+    /// Source that trickles out a few bytes per read, like an interactive pipe
+    struct Trickle<'a>(&'a [u8]);
+
+    impl Read for Trickle<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let len = buf.len().min(self.0.len()).min(100);
+            buf[..len].copy_from_slice(&self.0[..len]);
+            self.0 = &self.0[len..];
+            Ok(len)
+        }
+    }
+
+    #[test]
+    fn test_compress_stream_forwards_each_read() {
+        let data = b"typed line\n".repeat(50);
+
+        let mut bytes_in = Vec::new();
+        let mut compressed = Vec::new();
+        compress_stream_with_stats(
+            Trickle(&data),
+            &mut compressed,
+            CompressionAlgorithm::Snappy,
+            |stats| bytes_in.push(stats.bytes_in),
+        )
+        .unwrap();
+
+        // Every read reaches the encoder right away instead of waiting for a full buffer
+        assert_eq!(bytes_in, [100, 200, 300, 400, 500, 550, 550]);
+        assert_eq!(SnappyCompressor.decompress(&compressed).unwrap(), data);
+    }
+
+    #[test]
+    fn test_read_adapters_round_trip() {
+        let data = b"file -> compress -> network ".repeat(10_000);
+
+        for &algorithm in CompressionAlgorithm::all() {
+            let compressor = CompressorFactory::get_compressor(algorithm);
+
+            let mut compressed = Vec::new();
+            CompressReader::new(&data[..], &*compressor)
+                .unwrap()
+                .read_to_end(&mut compressed)
+                .unwrap();
+            assert_eq!(compressor.decompress(&compressed).unwrap(), data);
+
+            let mut chained = DecompressReader::new(
+                CompressReader::new(&data[..], &*compressor).unwrap(),
+                &*compressor,
+            )
+            .unwrap();
+            // Small reads exercise partial hand-outs of the compressed buffer
+            let mut decompressed = Vec::new();
+            let mut buf = [0u8; 7];
+            loop {
+                match chained.read(&mut buf).unwrap() {
+                    0 => break,
+                    read => decompressed.extend_from_slice(&buf[..read]),
+                }
+            }
+            assert_eq!(decompressed, data, "{:?}", algorithm);
+        }
+
+        let mut empty = Vec::new();
+        CompressReader::new(&b""[..], &SnappyCompressor)
+            .unwrap()
+            .read_to_end(&mut empty)
+            .unwrap();
+        assert_eq!(empty, SnappyCompressor.compress(b"").unwrap());
+    }
+
+    /// Accepts at most `max_chunk` bytes per call and reports `WouldBlock` on every
+    /// other call, like a throttled non-blocking socket; the first flush blocks too
+    struct ThrottledWriter {
+        written: Vec<u8>,
+        max_chunk: usize,
+        calls: usize,
+        flushes: usize,
+    }
+
+    impl Write for ThrottledWriter {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.calls += 1;
+            if self.calls.is_multiple_of(2) {
+                return Err(io::ErrorKind::WouldBlock.into());
+            }
+
+            let len = buf.len().min(self.max_chunk);
+            self.written.extend_from_slice(&buf[..len]);
+            Ok(len)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            self.flushes += 1;
+            if self.flushes == 1 {
+                return Err(io::ErrorKind::WouldBlock.into());
+            }
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_pipe_decompress_into_throttled_writer() {
+        let data = b"slow sink ".repeat(2000);
+        let compressed = SnappyCompressor.compress(&data).unwrap();
+
+        let mut writer = ThrottledWriter {
+            written: Vec::new(),
+            max_chunk: 512,
+            calls: 0,
+            flushes: 0,
+        };
+        let written = pipe_decompress(
+            io::Cursor::new(&compressed),
+            &mut writer,
+            CompressionAlgorithm::Snappy,
+        )
+        .unwrap();
+
+        assert_eq!(written, data.len() as u64);
+        assert_eq!(writer.written, data);
+        assert!(writer.calls >= 2 * data.len() / 512);
+        assert_eq!(writer.flushes, 2);
+    }
+
+    #[test]
+    fn test_pipe_decompress_corrupt_input() {
+        let result = pipe_decompress(
+            &b"not a snappy frame"[..],
+            io::sink(),
+            CompressionAlgorithm::Snappy,
+        );
+
+        assert!(matches!(
+            result,
+            Err(CompressorError::WithAlgorithm {
+                algorithm: CompressionAlgorithm::Snappy,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn test_decompress_windowed() {
+        let data: Vec<u8> = (0..1_000_000u32).map(|i| (i % 253) as u8).collect();
+        let compressed = SnappyCompressor.compress(&data).unwrap();
+
+        let mut reassembled = Vec::new();
+        let mut largest_chunk = 0;
+        let total = decompress_windowed(
+            &compressed[..],
+            CompressionAlgorithm::Snappy,
+            4096,
+            |chunk| {
+                largest_chunk = largest_chunk.max(chunk.len());
+                reassembled.extend_from_slice(chunk);
+                Ok(())
+            },
+        )
+        .unwrap();
+
+        assert_eq!(total, data.len() as u64);
+        assert!(largest_chunk <= 4096);
+        assert_eq!(reassembled, data);
+
+        let aborted =
+            decompress_windowed(&compressed[..], CompressionAlgorithm::Snappy, 4096, |_| {
+                Err(CompressorError::IoError("consumer gone".to_string()))
+            });
+        assert!(matches!(aborted, Err(CompressorError::IoError(_))));
+    }
+
+    #[test]
+    fn test_decompress_to_channel() {
+        let data: Vec<u8> = (0..500_000u32).map(|i| (i % 241) as u8).collect();
+        let compressed = SnappyCompressor.compress(&data).unwrap();
+
+        let (sender, receiver) = std::sync::mpsc::channel();
+        let decompressor = std::thread::spawn(move || {
+            decompress_to_channel(&compressed[..], CompressionAlgorithm::Snappy, sender)
+        });
+
+        let chunks: Vec<Vec<u8>> = receiver.iter().collect::<Result<_, _>>().unwrap();
+        decompressor.join().unwrap();
+
+        assert!(chunks.len() > 1);
+        assert_eq!(chunks.concat(), data);
+    }
+
+    #[test]
+    fn test_decompress_to_channel_corrupt_input() {
+        let (sender, receiver) = std::sync::mpsc::channel();
+        decompress_to_channel(
+            &b"not a snappy frame"[..],
+            CompressionAlgorithm::Snappy,
+            sender,
+        );
+
+        let messages: Vec<_> = receiver.iter().collect();
+        assert!(matches!(
+            messages.last(),
+            Some(Err(CompressorError::WithAlgorithm { .. }))
+        ));
+    }
+
+    fn assert_flushed_segments_decode(compressor: &dyn TCompressor) {
+        let mut streaming = StreamingCompressor::new(compressor);
+
+        streaming.update(b"first message").unwrap();
+        let first = streaming.flush().unwrap();
+
+        streaming.update(b"second ").unwrap();
+        streaming.update(b"message").unwrap();
+        let second = streaming.flush().unwrap();
+
+        assert!(streaming.flush().unwrap().is_empty());
+        streaming.update(b"last").unwrap();
+        let last = streaming.finish().unwrap();
+
+        assert_eq!(compressor.decompress(&first).unwrap(), b"first message");
+        assert_eq!(compressor.decompress(&second).unwrap(), b"second message");
+        assert_eq!(compressor.decompress(&last).unwrap(), b"last");
+
+        let concatenated = [first, second, last].concat();
+        assert_eq!(
+            compressor.decompress(&concatenated).unwrap(),
+            b"first messagesecond messagelast"
+        );
+    }
+
+    #[test]
+    fn test_compress_serialize_stream() {
+        #[derive(serde::Serialize)]
+        struct Snapshot {
+            version: u32,
+            labels: Vec<String>,
+            samples: Vec<(u64, f64)>,
+        }
+
+        let snapshot = Snapshot {
+            version: 7,
+            labels: (0..1000).map(|i| format!("label-{}", i)).collect(),
+            samples: (0..200_000).map(|i| (i, i as f64 / 8.0)).collect(),
+        };
+        let buffered = CompressorFactory::get_compressor(CompressionAlgorithm::Snappy)
+            .compress(&serde_json::to_vec(&snapshot).unwrap())
+            .unwrap();
+
+        assert_eq!(
+            compress_serialize_stream(&snapshot, CompressionAlgorithm::Snappy).unwrap(),
+            buffered
+        );
+    }
+
+    #[test]
+    fn test_streaming_reset() {
+        let compressor = SnappyCompressor;
+        let mut streaming = StreamingCompressor::new(&compressor);
+
+        streaming.update(b"abandoned message").unwrap();
+        streaming.reset();
+        streaming.update(b"first message").unwrap();
+        let first = streaming.flush().unwrap();
+
+        streaming.update(b"second ").unwrap();
+        streaming.reset();
+        streaming.update(b"second message").unwrap();
+        let second = streaming.finish().unwrap();
+
+        assert_eq!(compressor.decompress(&first).unwrap(), b"first message");
+        assert_eq!(compressor.decompress(&second).unwrap(), b"second message");
+    }
+
+    #[test]
+    fn test_streaming_keeps_output_capacity() {
+        let compressor = SnappyCompressor;
+        let mut streaming = StreamingCompressor::new(&compressor);
+        let message = crate::noise(11, 10_000);
+
+        streaming.update(&message).unwrap();
+        let first = streaming.flush().unwrap();
+        let capacity = streaming.output.0.borrow().capacity();
+        assert!(capacity >= first.len());
+
+        streaming.update(&message).unwrap();
+        streaming.reset();
+        streaming.update(&message).unwrap();
+        let second = streaming.flush().unwrap();
+        assert_eq!(streaming.output.0.borrow().capacity(), capacity);
+
+        assert_eq!(compressor.decompress(&first).unwrap(), message);
+        assert_eq!(compressor.decompress(&second).unwrap(), message);
+    }
+
+    #[cfg(feature = "gzip")]
+    #[test]
+    fn test_streaming_reset_discards_trailer() {
+        let compressor = crate::GzipCompressor::default();
+        let mut streaming = StreamingCompressor::new(&compressor);
+
+        streaming.update(b"abandoned").unwrap();
+        streaming.reset();
+        streaming.update(b"kept").unwrap();
+
+        let compressed = streaming.finish().unwrap();
+        assert_eq!(compressor.decompress_strict(&compressed).unwrap(), b"kept");
+    }
+
+    #[cfg(feature = "sha2")]
+    #[test]
+    fn test_decompress_and_hash() {
+        use sha2::{Digest, Sha256};
+
+        let data = b"hashed archive content ".repeat(20_000);
+        let compressed = SnappyCompressor.compress(&data).unwrap();
+        let digest = Sha256::digest(&data);
+
+        decompress_and_hash(&compressed[..], CompressionAlgorithm::Snappy, &digest).unwrap();
+
+        let mut tampered = digest;
+        tampered[0] ^= 1;
+        assert!(matches!(
+            decompress_and_hash(&compressed[..], CompressionAlgorithm::Snappy, &tampered),
+            Err(CompressorError::IntegrityError(_))
+        ));
+    }
+
+    #[test]
+    fn test_rotating_writer() {
+        let compressor = SnappyCompressor;
+        let mut writer = RotatingCompressWriter::new(&compressor, 1000);
+
+        let record = |i: usize| format!("{:04} {}\n", i, "x".repeat(94));
+        for i in 0..25 {
+            writer.write_all(record(i).as_bytes()).unwrap();
+        }
+
+        // 2500 bytes with a 1000-byte threshold rotate twice
+        assert_eq!(writer.segments().len(), 2);
+        let finalized = writer.take_segments();
+        assert!(writer.segments().is_empty());
+
+        let first: String = (0..10).map(record).collect();
+        let second: String = (10..20).map(record).collect();
+        assert_eq!(
+            compressor.decompress(&finalized[0]).unwrap(),
+            first.as_bytes()
+        );
+        assert_eq!(
+            compressor.decompress(&finalized[1]).unwrap(),
+            second.as_bytes()
+        );
+
+        let rest = writer.finish().unwrap();
+        let third: String = (20..25).map(record).collect();
+        assert_eq!(rest.len(), 1);
+        assert_eq!(compressor.decompress(&rest[0]).unwrap(), third.as_bytes());
+    }
+
+    #[test]
+    fn test_bounded_streaming_compressor() {
+        let compressor = SnappyCompressor;
+        let mut bounded = BoundedStreamingCompressor::new(&compressor, 1000);
+        let bursts = [10, 2500, 489, 1, 5000, 0, 999, 3];
+
+        let mut input = Vec::new();
+        let mut compressed = Vec::new();
+        for (i, &len) in bursts.iter().enumerate() {
+            let burst: Vec<u8> = format!("burst {} ", i).bytes().cycle().take(len).collect();
+            input.extend_from_slice(&burst);
+
+            let mut rest = &burst[..];
+            loop {
+                let before = bounded.buffered();
+                let (accepted, frame) = bounded.try_push(rest).unwrap();
+                assert_eq!(accepted, rest.len().min(1000 - before));
+                assert_eq!(frame.is_some(), before + accepted == 1000);
+                compressed.extend(frame.unwrap_or_default());
+                assert!(bounded.buffered() < 1000);
+
+                rest = &rest[accepted..];
+                if rest.is_empty() {
+                    break;
+                }
+            }
+            assert_eq!(bounded.buffered(), input.len() % 1000);
+        }
+        compressed.extend_from_slice(&bounded.finish().unwrap());
+
+        assert_eq!(compressor.decompress(&compressed).unwrap(), input);
+        assert!(BoundedStreamingCompressor::new(&compressor, 0)
+            .finish()
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn test_bounded_streaming_keeps_raw_frames_apart() {
+        let compressor = crate::compressors::SnappyRawCompressor;
+        let mut bounded = BoundedStreamingCompressor::new(&compressor, 16);
+        let input: Vec<u8> = (0..64u8).collect();
+
+        let mut frames = Vec::new();
+        let mut rest = &input[..];
+        while !rest.is_empty() {
+            let (accepted, frame) = bounded.try_push(rest).unwrap();
+            assert_eq!(accepted, 16);
+            frames.push(frame.unwrap());
+            rest = &rest[accepted..];
+        }
+        assert_eq!(bounded.buffered(), 0);
+
+        assert_eq!(frames.len(), 4);
+        for (frame, window) in frames.iter().zip(input.chunks(16)) {
+            assert_eq!(compressor.decompress(frame).unwrap(), window);
+        }
+    }
+
+    #[test]
+    fn test_streaming_flush_snappy() {
+        assert_flushed_segments_decode(&SnappyCompressor);
+    }
+
+    #[cfg(feature = "zstd")]
+    #[test]
+    fn test_streaming_flush_zstd() {
+        assert_flushed_segments_decode(&crate::compressors::ZstdCompressor::default());
+    }
+}
@@ -0,0 +1,502 @@
+//! Self-describing blobs: a small header recording how the payload was produced.
+//!
+//! Layout: the magic `BRNG`, the algorithm tag, the serializer tag (0 for raw bytes),
+//! then the compressed payload.
+
+use crate::compressors::{CompressionAlgorithm, CompressorFactory};
+use crate::envelope::{EnvelopeHeader, ENVELOPE_MAGIC};
+use crate::error::CompressorError;
+use crate::serializer::Serializer;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::collections::HashSet;
+
+pub const TAGGED_MAGIC: &[u8; 4] = b"BRNG";
+pub const TAGGED_HEADER_LEN: usize = TAGGED_MAGIC.len() + 2;
+
+/// Serializer tag for payloads that are raw bytes rather than a serialized value
+const RAW_BYTES_TAG: u8 = 0;
+
+/// The parsed header of a tagged blob
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TaggedHeader {
+    pub algorithm: CompressionAlgorithm,
+    /// `None` when the payload is raw bytes
+    pub serializer: Option<Serializer>,
+}
+
+impl TaggedHeader {
+    /// Parses the header at the start of `data`, returning it with the compressed payload
+    pub fn parse(data: &[u8]) -> Result<(Self, &[u8]), CompressorError> {
+        if data.len() < TAGGED_HEADER_LEN || !data.starts_with(TAGGED_MAGIC) {
+            return Err(CompressorError::DecompressionError(
+                "missing tagged header".to_string(),
+            ));
+        }
+
+        let algorithm = CompressionAlgorithm::from_tag(data[4])?;
+        let serializer = match data[5] {
+            RAW_BYTES_TAG => None,
+            tag => Some(Serializer::from_tag(tag)?),
+        };
+
+        Ok((
+            TaggedHeader {
+                algorithm,
+                serializer,
+            },
+            &data[TAGGED_HEADER_LEN..],
+        ))
+    }
+
+    pub(crate) fn encode(&self, payload: &[u8]) -> Vec<u8> {
+        let mut tagged = Vec::with_capacity(TAGGED_HEADER_LEN + payload.len());
+        tagged.extend_from_slice(TAGGED_MAGIC);
+        tagged.push(self.algorithm.tag());
+        tagged.push(
+            self.serializer
+                .map_or(RAW_BYTES_TAG, |serializer| serializer.tag()),
+        );
+        tagged.extend_from_slice(payload);
+        tagged
+    }
+}
+
+/// Compresses raw bytes into a tagged blob that records `algorithm`
+pub fn compress_tagged(
+    data: &[u8],
+    algorithm: CompressionAlgorithm,
+) -> Result<Vec<u8>, CompressorError> {
+    let header = TaggedHeader {
+        algorithm,
+        serializer: None,
+    };
+
+    encode(header, data)
+}
+
+/// Upgrades a blob compressed with `assumed_algorithm` before the tagged format existed:
+/// checks that it decodes under that algorithm, then prepends the tagged header without
+/// recompressing it. The result decodes with `decompress_tagged`.
+///
+/// A blob that does not decode fails with the algorithm's error, and one that already
+/// has a valid tagged header fails with `ConfigError`, so a rerun of a migration cannot
+/// tag a blob twice.
+pub fn retag_legacy(
+    data: &[u8],
+    assumed_algorithm: CompressionAlgorithm,
+) -> Result<Vec<u8>, CompressorError> {
+    if TaggedHeader::parse(data).is_ok() {
+        return Err(CompressorError::ConfigError(
+            "blob already has a tagged header".to_string(),
+        ));
+    }
+
+    CompressorFactory::get_compressor(assumed_algorithm)
+        .validate(data)
+        .map_err(|err| err.with_algorithm(assumed_algorithm))?;
+
+    let header = TaggedHeader {
+        algorithm: assumed_algorithm,
+        serializer: None,
+    };
+    Ok(header.encode(data))
+}
+
+/// What `compress_tagged_with_fallback` actually did
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CompressionReport {
+    /// The algorithm recorded in the blob, `Identity` when the data was stored raw
+    pub algorithm: CompressionAlgorithm,
+    pub actually_compressed: bool,
+}
+
+/// `compress_tagged`, but stores `data` raw under the `Identity` tag when `algorithm`
+/// would not make it smaller. The blob decodes with `decompress_tagged` either way.
+pub fn compress_tagged_with_fallback(
+    data: &[u8],
+    algorithm: CompressionAlgorithm,
+) -> Result<(Vec<u8>, CompressionReport), CompressorError> {
+    let compressed = CompressorFactory::get_compressor(algorithm)
+        .compress(data)
+        .map_err(|err| err.with_algorithm(algorithm))?;
+
+    let actually_compressed = compressed.len() < data.len();
+    let (algorithm, payload) = if actually_compressed {
+        (algorithm, compressed.as_slice())
+    } else {
+        (CompressionAlgorithm::Identity, data)
+    };
+
+    let header = TaggedHeader {
+        algorithm,
+        serializer: None,
+    };
+    let report = CompressionReport {
+        algorithm,
+        actually_compressed,
+    };
+    Ok((header.encode(payload), report))
+}
+
+/// Decompresses a tagged blob with the algorithm recorded in its header.
+/// For blobs holding a serialized value this returns the serialized bytes.
+pub fn decompress_tagged(data: &[u8]) -> Result<Vec<u8>, CompressorError> {
+    decompress_tagged_with_algorithm(data).map(|(_, decompressed)| decompressed)
+}
+
+/// `decompress_tagged`, also returning the algorithm the blob was compressed with
+pub fn decompress_tagged_with_algorithm(
+    data: &[u8],
+) -> Result<(CompressionAlgorithm, Vec<u8>), CompressorError> {
+    let (header, payload) = TaggedHeader::parse(data)?;
+    let decompressed = CompressorFactory::get_compressor(header.algorithm)
+        .decompress(payload)
+        .map_err(|err| err.with_algorithm(header.algorithm))?;
+
+    Ok((header.algorithm, decompressed))
+}
+
+/// What `decompress_detailed` read from a blob: the content and how it was stored
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DecompressResult {
+    pub payload: Vec<u8>,
+    pub algorithm: CompressionAlgorithm,
+    /// Uncompressed length, as recorded by an envelope (and checked) or as decoded
+    pub original_len: u64,
+}
+
+/// Decompresses a tagged blob or an envelope, told apart by their magic, returning the
+/// content with the metadata from its header, e.g. to cache alongside the value.
+/// Envelopes are validated like `decode_envelope` does.
+pub fn decompress_detailed(data: &[u8]) -> Result<DecompressResult, CompressorError> {
+    let (algorithm, payload) = if data.starts_with(ENVELOPE_MAGIC) {
+        crate::envelope::decode_envelope(data)?
+    } else {
+        decompress_tagged_with_algorithm(data)?
+    };
+
+    Ok(DecompressResult {
+        original_len: payload.len() as u64,
+        payload,
+        algorithm,
+    })
+}
+
+/// Serializes `value` with `serializer` into a tagged blob recording both formats
+pub fn to_vec_with_header<T: Serialize + ?Sized>(
+    value: &T,
+    serializer: Serializer,
+    algorithm: CompressionAlgorithm,
+) -> Result<Vec<u8>, CompressorError> {
+    let header = TaggedHeader {
+        algorithm,
+        serializer: Some(serializer),
+    };
+
+    encode(header, &serializer.serialize(value)?)
+}
+
+/// Decodes a blob produced by `to_vec_with_header` using the recorded algorithm and serializer
+pub fn decompress_tagged_to<T: DeserializeOwned>(data: &[u8]) -> Result<T, CompressorError> {
+    let (header, _) = TaggedHeader::parse(data)?;
+    let serializer = header.serializer.ok_or_else(|| {
+        CompressorError::DeserializationError(
+            "tagged blob holds raw bytes, not a serialized value".to_string(),
+        )
+    })?;
+
+    serializer.deserialize(&decompress_tagged(data)?)
+}
+
+/// Compresses each of `records` with every one of `candidates` and keeps the smallest
+/// output, as a tagged blob recording the winner so `decompress_tagged` needs no hint.
+/// Ties go to the earlier candidate, so list cheaper algorithms first.
+///
+/// Fails with `ConfigError` if `candidates` is empty and there are records; a compression
+/// failure is returned keyed by the record's index.
+pub fn compress_batch_adaptive(
+    records: &[&[u8]],
+    candidates: &[CompressionAlgorithm],
+) -> Result<Vec<(CompressionAlgorithm, Vec<u8>)>, CompressorError> {
+    let compressors: Vec<_> = candidates
+        .iter()
+        .map(|&algorithm| (algorithm, CompressorFactory::get_compressor(algorithm)))
+        .collect();
+
+    records
+        .iter()
+        .enumerate()
+        .map(|(index, record)| {
+            // Only the smallest output so far is kept; ties keep the earlier one
+            let mut smallest: Option<(CompressionAlgorithm, Vec<u8>)> = None;
+            for (algorithm, compressor) in &compressors {
+                let compressed = compressor
+                    .compress(record)
+                    .map_err(|err| err.with_algorithm(*algorithm).with_key(index))?;
+                if smallest
+                    .as_ref()
+                    .is_none_or(|(_, best)| compressed.len() < best.len())
+                {
+                    smallest = Some((*algorithm, compressed));
+                }
+            }
+
+            let (algorithm, compressed) = smallest.ok_or_else(|| {
+                CompressorError::ConfigError("no candidate algorithms given".to_string())
+            })?;
+            let header = TaggedHeader {
+                algorithm,
+                serializer: None,
+            };
+            Ok((algorithm, header.encode(&compressed)))
+        })
+        .collect()
+}
+
+/// Collects the algorithms recorded in the headers of tagged blobs and envelopes without
+/// decompressing them, e.g. to find out which features a dataset needs.
+///
+/// A blob with neither header, or with an unknown algorithm tag, fails with an error
+/// keyed by its index in `blobs`.
+pub fn scan_algorithms<I: IntoIterator<Item = Vec<u8>>>(
+    blobs: I,
+) -> Result<HashSet<CompressionAlgorithm>, CompressorError> {
+    let mut algorithms = HashSet::new();
+
+    for (index, blob) in blobs.into_iter().enumerate() {
+        let algorithm = if blob.starts_with(ENVELOPE_MAGIC) {
+            EnvelopeHeader::parse(&blob).map(|header| header.algorithm)
+        } else {
+            TaggedHeader::parse(&blob).map(|(header, _)| header.algorithm)
+        };
+        algorithms.insert(algorithm.map_err(|err| err.with_key(index))?);
+    }
+
+    Ok(algorithms)
+}
+
+fn encode(header: TaggedHeader, data: &[u8]) -> Result<Vec<u8>, CompressorError> {
+    let compressed = CompressorFactory::get_compressor(header.algorithm)
+        .compress(data)
+        .map_err(|err| err.with_algorithm(header.algorithm))?;
+
+    Ok(header.encode(&compressed))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+
+    #[derive(Debug, Serialize, Deserialize, PartialEq)]
+    struct Record {
+        id: u64,
+        tags: Vec<String>,
+    }
+
+    fn record() -> Record {
+        Record {
+            id: 42,
+            tags: vec!["tagged".to_string(), "container".to_string()],
+        }
+    }
+
+    #[test]
+    fn test_tagged_bytes_round_trip() {
+        let tagged = compress_tagged(b"raw bytes", CompressionAlgorithm::Snappy).unwrap();
+
+        let (header, _) = TaggedHeader::parse(&tagged).unwrap();
+        assert_eq!(header.algorithm, CompressionAlgorithm::Snappy);
+        assert_eq!(header.serializer, None);
+        assert_eq!(decompress_tagged(&tagged).unwrap(), b"raw bytes");
+
+        assert!(decompress_tagged_to::<Record>(&tagged).is_err());
+        assert!(decompress_tagged(b"BRN").is_err());
+    }
+
+    #[test]
+    fn test_scan_algorithms() {
+        let blobs = vec![
+            compress_tagged(b"first", CompressionAlgorithm::Snappy).unwrap(),
+            compress_tagged(b"second", CompressionAlgorithm::Identity).unwrap(),
+            to_vec_with_header(&record(), Serializer::Json, CompressionAlgorithm::Snappy).unwrap(),
+            crate::envelope::encode_envelope(b"third", CompressionAlgorithm::Identity).unwrap(),
+        ];
+
+        assert_eq!(
+            scan_algorithms(blobs.clone()).unwrap(),
+            HashSet::from([CompressionAlgorithm::Snappy, CompressionAlgorithm::Identity])
+        );
+        assert!(scan_algorithms(Vec::new()).unwrap().is_empty());
+
+        let mut with_untagged = blobs;
+        with_untagged.push(b"untagged".to_vec());
+        assert!(matches!(
+            scan_algorithms(with_untagged),
+            Err(CompressorError::KeyedError { key, .. }) if key == "4"
+        ));
+    }
+
+    #[test]
+    fn test_decompress_detailed() {
+        let data = b"cached value ".repeat(40);
+
+        let tagged = compress_tagged(&data, CompressionAlgorithm::Snappy).unwrap();
+        assert_eq!(
+            decompress_detailed(&tagged).unwrap(),
+            DecompressResult {
+                payload: data.clone(),
+                algorithm: CompressionAlgorithm::Snappy,
+                original_len: data.len() as u64,
+            }
+        );
+
+        let envelope =
+            crate::envelope::encode_envelope(&data, CompressionAlgorithm::Identity).unwrap();
+        let result = decompress_detailed(&envelope).unwrap();
+        assert_eq!(result.algorithm, CompressionAlgorithm::Identity);
+        assert_eq!(result.original_len, data.len() as u64);
+        assert_eq!(result.payload, data);
+
+        assert!(decompress_detailed(&envelope[..envelope.len() - 1]).is_err());
+        assert!(decompress_detailed(b"untagged").is_err());
+    }
+
+    #[test]
+    fn test_compress_batch_adaptive() {
+        let text = br#"{"kind":"text","body":"repeated words repeated words"}"#.repeat(50);
+        let binary = crate::noise(0x2545_f491_4f6c_dd1d, 4096);
+        let records: [&[u8]; 3] = [&text, &binary, b""];
+        let candidates = [CompressionAlgorithm::Identity, CompressionAlgorithm::Snappy];
+
+        let compressed = compress_batch_adaptive(&records, &candidates).unwrap();
+        let chosen: Vec<_> = compressed.iter().map(|(algorithm, _)| *algorithm).collect();
+        assert_eq!(
+            chosen,
+            [
+                CompressionAlgorithm::Snappy,
+                CompressionAlgorithm::Identity,
+                CompressionAlgorithm::Identity
+            ]
+        );
+
+        for ((algorithm, blob), record) in compressed.iter().zip(records) {
+            assert_eq!(
+                decompress_tagged_with_algorithm(blob).unwrap(),
+                (*algorithm, record.to_vec())
+            );
+        }
+
+        assert!(matches!(
+            compress_batch_adaptive(&records, &[]),
+            Err(CompressorError::ConfigError(_))
+        ));
+        assert!(compress_batch_adaptive(&[], &candidates)
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn test_retag_legacy() {
+        let data = b"legacy snappy blob ".repeat(100);
+        let legacy = CompressorFactory::get_compressor(CompressionAlgorithm::Snappy)
+            .compress(&data)
+            .unwrap();
+
+        let tagged = retag_legacy(&legacy, CompressionAlgorithm::Snappy).unwrap();
+        assert_eq!(&tagged[TAGGED_HEADER_LEN..], legacy.as_slice());
+        assert_eq!(
+            decompress_tagged_with_algorithm(&tagged).unwrap(),
+            (CompressionAlgorithm::Snappy, data)
+        );
+
+        assert!(matches!(
+            retag_legacy(&tagged, CompressionAlgorithm::Snappy),
+            Err(CompressorError::ConfigError(_))
+        ));
+        assert!(matches!(
+            retag_legacy(b"not snappy", CompressionAlgorithm::Snappy),
+            Err(CompressorError::WithAlgorithm {
+                algorithm: CompressionAlgorithm::Snappy,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn test_decompress_tagged_with_algorithm() {
+        for algorithm in [CompressionAlgorithm::Identity, CompressionAlgorithm::Snappy] {
+            let tagged = compress_tagged(b"which algorithm?", algorithm).unwrap();
+
+            assert_eq!(
+                decompress_tagged_with_algorithm(&tagged).unwrap(),
+                (algorithm, b"which algorithm?".to_vec())
+            );
+        }
+    }
+
+    #[test]
+    fn test_compress_with_fallback_report() {
+        let random = crate::noise(0x9e37_79b9_7f4a_7c15, 16_384);
+
+        let (tagged, report) =
+            compress_tagged_with_fallback(&random, CompressionAlgorithm::Snappy).unwrap();
+        assert_eq!(
+            report,
+            CompressionReport {
+                algorithm: CompressionAlgorithm::Identity,
+                actually_compressed: false,
+            }
+        );
+        assert_eq!(tagged.len(), TAGGED_HEADER_LEN + random.len());
+        assert_eq!(decompress_tagged(&tagged).unwrap(), random);
+
+        let text = b"compressible text ".repeat(1000);
+        let (tagged, report) =
+            compress_tagged_with_fallback(&text, CompressionAlgorithm::Snappy).unwrap();
+        assert_eq!(
+            report,
+            CompressionReport {
+                algorithm: CompressionAlgorithm::Snappy,
+                actually_compressed: true,
+            }
+        );
+        assert_eq!(decompress_tagged(&tagged).unwrap(), text);
+    }
+
+    #[test]
+    fn test_is_tagged() {
+        let tagged = compress_tagged(b"mixed store", CompressionAlgorithm::Snappy).unwrap();
+        let raw = CompressorFactory::get_compressor(CompressionAlgorithm::Snappy)
+            .compress(b"mixed store")
+            .unwrap();
+
+        assert!(CompressorFactory::is_tagged(&tagged));
+        assert!(!CompressorFactory::is_tagged(&raw));
+        assert!(!CompressorFactory::is_tagged(b"plain legacy bytes"));
+        assert!(!CompressorFactory::is_tagged(b""));
+    }
+
+    #[test]
+    fn test_json_snappy_round_trip() {
+        let tagged =
+            to_vec_with_header(&record(), Serializer::Json, CompressionAlgorithm::Snappy).unwrap();
+
+        assert_eq!(decompress_tagged_to::<Record>(&tagged).unwrap(), record());
+    }
+
+    #[cfg(all(feature = "bincode", feature = "zstd"))]
+    #[test]
+    fn test_bincode_zstd_round_trip() {
+        let tagged =
+            to_vec_with_header(&record(), Serializer::Bincode, CompressionAlgorithm::Zstd).unwrap();
+
+        let (header, _) = TaggedHeader::parse(&tagged).unwrap();
+        assert_eq!(header.algorithm, CompressionAlgorithm::Zstd);
+        assert_eq!(header.serializer, Some(Serializer::Bincode));
+
+        // Neither the serializer nor the algorithm is specified by the reader
+        assert_eq!(decompress_tagged_to::<Record>(&tagged).unwrap(), record());
+    }
+}
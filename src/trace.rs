@@ -0,0 +1,311 @@
+//! Optional `tracing` instrumentation of compress and decompress calls.
+//!
+//! Without the `tracing` feature `traced` only calls its closure, so instrumented paths
+//! cost nothing.
+
+use crate::compressors::CompressionAlgorithm;
+#[cfg(feature = "tracing")]
+use crate::compressors::{TCompressor, TEncoder};
+use crate::error::CompressorError;
+#[cfg(feature = "tracing")]
+use std::borrow::Cow;
+#[cfg(feature = "tracing")]
+use std::io::{Read, Write};
+
+/// Runs `operation` (`"compress"`, `"decompress"`, ...) inside a debug-level `compression`
+/// span recording the algorithm, input and output sizes and duration. Compressors passed
+/// in by the caller have no known algorithm and are recorded as `custom`.
+#[cfg(not(feature = "tracing"))]
+#[inline(always)]
+pub(crate) fn traced(
+    _operation: &'static str,
+    _algorithm: Option<CompressionAlgorithm>,
+    _input_size: usize,
+    run: impl FnOnce() -> Result<Vec<u8>, CompressorError>,
+) -> Result<Vec<u8>, CompressorError> {
+    run()
+}
+
+#[cfg(feature = "tracing")]
+pub(crate) fn traced(
+    operation: &'static str,
+    algorithm: Option<CompressionAlgorithm>,
+    input_size: usize,
+    run: impl FnOnce() -> Result<Vec<u8>, CompressorError>,
+) -> Result<Vec<u8>, CompressorError> {
+    traced_sized(operation, algorithm, input_size, Vec::len, run)
+}
+
+/// `traced` for operations whose output is not a `Vec`, with `output_size` measuring it
+#[cfg(feature = "tracing")]
+fn traced_sized<T>(
+    operation: &'static str,
+    algorithm: Option<CompressionAlgorithm>,
+    input_size: usize,
+    output_size: impl FnOnce(&T) -> usize,
+    run: impl FnOnce() -> Result<T, CompressorError>,
+) -> Result<T, CompressorError> {
+    use tracing::field::Empty;
+
+    let span = tracing::debug_span!(
+        "compression",
+        operation,
+        algorithm = algorithm.map_or("custom", |algorithm| algorithm.name()),
+        input_size,
+        output_size = Empty,
+        duration_us = Empty,
+        error = Empty,
+    );
+    let _entered = span.enter();
+
+    let started = std::time::Instant::now();
+    let result = run();
+    span.record("duration_us", started.elapsed().as_micros() as u64);
+
+    match &result {
+        Ok(output) => span.record("output_size", output_size(output)),
+        Err(err) => span.record("error", tracing::field::display(err)),
+    };
+
+    result
+}
+
+/// Wraps every compressor handed out by `CompressorFactory` so that factory-driven
+/// paths are traced without each call site opting in
+#[cfg(feature = "tracing")]
+pub(crate) struct TracedCompressor {
+    algorithm: CompressionAlgorithm,
+    inner: Box<dyn TCompressor + Send + Sync>,
+}
+
+#[cfg(feature = "tracing")]
+impl TracedCompressor {
+    pub(crate) fn new(
+        algorithm: CompressionAlgorithm,
+        inner: Box<dyn TCompressor + Send + Sync>,
+    ) -> Self {
+        TracedCompressor { algorithm, inner }
+    }
+}
+
+#[cfg(feature = "tracing")]
+impl TCompressor for TracedCompressor {
+    fn compress(&self, value: &[u8]) -> Result<Vec<u8>, CompressorError> {
+        traced("compress", Some(self.algorithm), value.len(), || {
+            self.inner.compress(value)
+        })
+    }
+
+    fn decompress(&self, compressed: &[u8]) -> Result<Vec<u8>, CompressorError> {
+        traced("decompress", Some(self.algorithm), compressed.len(), || {
+            self.inner.decompress(compressed)
+        })
+    }
+
+    fn decompress_strict(&self, compressed: &[u8]) -> Result<Vec<u8>, CompressorError> {
+        traced(
+            "decompress_strict",
+            Some(self.algorithm),
+            compressed.len(),
+            || self.inner.decompress_strict(compressed),
+        )
+    }
+
+    fn compress_cow<'a>(&self, value: Cow<'a, [u8]>) -> Result<Cow<'a, [u8]>, CompressorError> {
+        let input_size = value.len();
+        traced_sized(
+            "compress",
+            Some(self.algorithm),
+            input_size,
+            |output: &Cow<'a, [u8]>| output.len(),
+            || self.inner.compress_cow(value),
+        )
+    }
+
+    fn compress_into(&self, value: &[u8], output: &mut Vec<u8>) -> Result<(), CompressorError> {
+        let start = output.len();
+        traced_sized(
+            "compress_into",
+            Some(self.algorithm),
+            value.len(),
+            |&written| written,
+            || {
+                self.inner.compress_into(value, output)?;
+                Ok(output.len() - start)
+            },
+        )
+        .map(drop)
+    }
+
+    fn decompress_into(
+        &self,
+        compressed: &[u8],
+        output: &mut Vec<u8>,
+    ) -> Result<(), CompressorError> {
+        let start = output.len();
+        traced_sized(
+            "decompress_into",
+            Some(self.algorithm),
+            compressed.len(),
+            |&written| written,
+            || {
+                self.inner.decompress_into(compressed, output)?;
+                Ok(output.len() - start)
+            },
+        )
+        .map(drop)
+    }
+
+    fn decompressed_len_hint(&self, compressed: &[u8]) -> Option<usize> {
+        self.inner.decompressed_len_hint(compressed)
+    }
+
+    fn overhead_bytes(&self) -> usize {
+        self.inner.overhead_bytes()
+    }
+
+    fn preferred_chunk_size(&self) -> usize {
+        self.inner.preferred_chunk_size()
+    }
+
+    fn decoder<'a>(
+        &self,
+        reader: Box<dyn Read + 'a>,
+    ) -> Result<Box<dyn Read + 'a>, CompressorError> {
+        self.inner.decoder(reader)
+    }
+
+    fn encoder<'a>(
+        &'a self,
+        writer: Box<dyn Write + 'a>,
+    ) -> Result<Box<dyn TEncoder + 'a>, CompressorError> {
+        self.inner.encoder(writer)
+    }
+}
+
+#[cfg(all(test, feature = "tracing"))]
+mod tests {
+    use super::*;
+    use crate::TCompressible;
+    use std::collections::HashMap;
+    use std::sync::{Arc, Mutex};
+    use tracing::field::{Field, Visit};
+    use tracing::span::{Attributes, Id, Record};
+    use tracing::{Event, Metadata, Subscriber};
+
+    type Fields = HashMap<String, String>;
+
+    /// Collects the fields of every span, including values recorded after creation
+    #[derive(Clone, Default)]
+    struct SpanCollector {
+        spans: Arc<Mutex<Vec<(&'static str, Fields)>>>,
+    }
+
+    struct FieldVisitor<'a>(&'a mut Fields);
+
+    impl Visit for FieldVisitor<'_> {
+        fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+            self.0
+                .insert(field.name().to_string(), format!("{:?}", value));
+        }
+
+        fn record_str(&mut self, field: &Field, value: &str) {
+            self.0.insert(field.name().to_string(), value.to_string());
+        }
+    }
+
+    impl Subscriber for SpanCollector {
+        fn enabled(&self, _: &Metadata<'_>) -> bool {
+            true
+        }
+
+        fn new_span(&self, span: &Attributes<'_>) -> Id {
+            let mut fields = Fields::new();
+            span.record(&mut FieldVisitor(&mut fields));
+
+            let mut spans = self.spans.lock().unwrap();
+            spans.push((span.metadata().name(), fields));
+            Id::from_u64(spans.len() as u64)
+        }
+
+        fn record(&self, span: &Id, values: &Record<'_>) {
+            let mut spans = self.spans.lock().unwrap();
+            let (_, fields) = &mut spans[span.into_u64() as usize - 1];
+            values.record(&mut FieldVisitor(fields));
+        }
+
+        fn record_follows_from(&self, _: &Id, _: &Id) {}
+
+        fn event(&self, _: &Event<'_>) {}
+
+        fn enter(&self, _: &Id) {}
+
+        fn exit(&self, _: &Id) {}
+    }
+
+    #[test]
+    fn test_compress_emits_span() {
+        let collector = SpanCollector::default();
+        let value = vec!["traced".to_string(); 100];
+
+        let compressed = tracing::subscriber::with_default(collector.clone(), || {
+            value
+                .compress_with_algorithm(CompressionAlgorithm::Snappy)
+                .unwrap()
+        });
+
+        let spans = collector.spans.lock().unwrap();
+        assert_eq!(spans.len(), 1);
+
+        let (name, fields) = &spans[0];
+        assert_eq!(*name, "compression");
+        assert_eq!(fields["operation"], "compress");
+        assert_eq!(fields["algorithm"], "snappy");
+        assert_eq!(
+            fields["input_size"],
+            serde_json::to_vec(&value).unwrap().len().to_string()
+        );
+        assert_eq!(fields["output_size"], compressed.len().to_string());
+        assert!(fields.contains_key("duration_us"));
+        assert!(!fields.contains_key("error"));
+    }
+
+    #[test]
+    fn test_into_methods_reach_inner() {
+        let traced = TracedCompressor::new(
+            CompressionAlgorithm::Snappy,
+            Box::new(crate::compressors::SnappyCompressor),
+        );
+        let data = b"traced into ".repeat(50);
+
+        let mut compressed = b"prefix".to_vec();
+        traced.compress_into(&data, &mut compressed).unwrap();
+        assert_eq!(
+            &compressed[6..],
+            crate::compressors::SnappyCompressor
+                .compress(&data)
+                .unwrap()
+                .as_slice()
+        );
+
+        let mut decompressed = Vec::new();
+        traced
+            .decompress_into(&compressed[6..], &mut decompressed)
+            .unwrap();
+        assert_eq!(decompressed, data);
+    }
+
+    #[test]
+    fn test_cow_and_hint_reach_inner() {
+        let identity = TracedCompressor::new(
+            CompressionAlgorithm::Identity,
+            Box::new(crate::compressors::IdentityCompressor),
+        );
+        let data = b"borrowed".to_vec();
+        assert!(matches!(
+            identity.compress_cow(Cow::Borrowed(&data)).unwrap(),
+            Cow::Borrowed(_)
+        ));
+        assert_eq!(identity.decompressed_len_hint(&data), Some(data.len()));
+    }
+}